@@ -0,0 +1,364 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, watch};
+
+use crate::player::Backend;
+use crate::privacy::Privacy;
+use crate::spotify::SpotifyError;
+use crate::SpotifyData;
+
+// how long a track has to stay current before hooks, notifications,
+// scrobbles and art fetches hear about it
+pub const DEFAULT_DWELL: Duration = Duration::from_secs(5);
+// right after a command, to show its effect quickly
+const AFTER_COMMAND: Duration = Duration::from_secs(5);
+const AFTER_COMMAND_INTERVAL: Duration = Duration::from_millis(500);
+// nothing has played for this long, so poll rarely
+const LONG_IDLE: Duration = Duration::from_secs(5 * 60);
+const LONG_IDLE_INTERVAL: Duration = Duration::from_secs(30);
+// polls aimed at the end of a track land just after it
+const TRACK_END_MARGIN: Duration = Duration::from_millis(250);
+// a token spotify turned down is refreshed at most this often
+const AUTH_RETRY: Duration = Duration::from_secs(60);
+// how much longer than asked for a wait between polls can take on the wall
+// clock before we assume the machine was asleep
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(30);
+
+// turns a stream of now-playing snapshots into "this track is actually being
+// listened to" events. intermediate tracks from rapid skipping never settle,
+// and a track is only reported once no matter how often it's polled.
+pub struct TrackDebouncer {
+    dwell: Duration,
+    pending: Option<(SpotifyData, Instant)>,
+    settled: Option<SpotifyData>,
+}
+
+impl TrackDebouncer {
+    pub fn new(dwell: Duration) -> Self {
+        TrackDebouncer {
+            dwell,
+            pending: None,
+            settled: None,
+        }
+    }
+
+    // returns the track the first time it has been current for the dwell time
+    pub fn observe(&mut self, data: &SpotifyData, now: Instant) -> Option<SpotifyData> {
+        if data.track_name.is_empty() {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match &self.pending {
+            Some((pending, since)) if pending.same_track(data) => *since,
+            _ => {
+                self.pending = Some((data.clone(), now));
+                now
+            }
+        };
+        if now.duration_since(since) < self.dwell {
+            return None;
+        }
+
+        // skipping away and straight back shouldn't count as a new play
+        if let Some(settled) = &self.settled {
+            if settled.same_track(data) {
+                return None;
+            }
+        }
+        self.settled = Some(data.clone());
+        Some(data.clone())
+    }
+}
+
+// how long to wait before the next poll. with adaptive polling the next poll
+// while a track plays lands just after it should end, with a heartbeat in
+// between for changes made elsewhere. it shrinks right after a command and
+// stretches while nothing plays
+pub struct PollSchedule {
+    pub base: Duration,
+    // spotify only, mpris players are local and cheap to ask so they're
+    // polled at the base interval while playing
+    pub heartbeat: Option<Duration>,
+    pub adaptive: bool,
+}
+
+impl PollSchedule {
+    pub fn next_delay(
+        &self,
+        data: Option<&SpotifyData>,
+        idle_for: Duration,
+        since_command: Option<Duration>,
+    ) -> Duration {
+        if !self.adaptive {
+            return self.base;
+        }
+        if since_command.is_some_and(|since| since < AFTER_COMMAND) {
+            return AFTER_COMMAND_INTERVAL.min(self.base);
+        }
+        let heartbeat = self
+            .heartbeat
+            .map_or(self.base, |heartbeat| heartbeat.max(self.base));
+        // paused never polls more often than playing does
+        let data = match data {
+            Some(data) if data.is_playing => data,
+            _ if idle_for > LONG_IDLE => return LONG_IDLE_INTERVAL.max(heartbeat),
+            _ => return (self.base * 3).max(heartbeat),
+        };
+        match (data.progress_ms, data.duration_ms) {
+            (Some(progress_ms), Some(duration_ms)) => {
+                let remaining = Duration::from_millis(duration_ms.saturating_sub(progress_ms));
+                (remaining + TRACK_END_MARGIN).min(heartbeat)
+            }
+            _ => self.base,
+        }
+    }
+}
+
+// polls the active source and broadcasts each track once it settles. tracks
+// that settle in privacy mode are dropped here so no sink ever sees them, and
+// so are explicit ones with hide_explicit, which are published without their
+// titles. every poll is published as well, for things that stay in the
+// process and care about playback rather than tracks
+pub fn spawn_aggregator(
+    backend: Backend,
+    dwell: Duration,
+    privacy: Arc<Privacy>,
+    hide_explicit: bool,
+    schedule: PollSchedule,
+    last_command: watch::Receiver<Option<Instant>>,
+) -> (
+    broadcast::Sender<SpotifyData>,
+    watch::Receiver<Option<SpotifyData>>,
+) {
+    let (tx, _) = broadcast::channel::<SpotifyData>(16);
+    let settled_tx = tx.clone();
+    let (polls_tx, polls_rx) = watch::channel(None);
+
+    tokio::spawn(async move {
+        let mut debouncer = TrackDebouncer::new(dwell);
+        let mut last_playing = Instant::now();
+        let mut delay = Duration::ZERO;
+        let mut last_auth_retry: Option<Instant> = None;
+        loop {
+            let before = SystemTime::now();
+            tokio::time::sleep(delay).await;
+
+            // whether the timer stops while suspended depends on the os
+            // (it does on linux, not on windows or macos), so the wait is
+            // measured on the wall clock, which always moves on. after a
+            // wake the token has probably expired, so refresh before polling
+            // rather than eating a 401 first
+            let waited = before.elapsed().unwrap_or_default();
+            if woke_from_sleep(delay, waited) {
+                eprintln!("resumed from sleep, refreshing auth");
+                if let Err(e) = backend.refresh_auth().await {
+                    eprintln!("refresh after sleep failed: {:?}", e);
+                }
+            }
+
+            let mut retry_after = None;
+            let data = match backend.now_playing().await {
+                Ok(data) => Some(data.hiding_explicit(hide_explicit)),
+                Err(e) => match e.downcast_ref::<SpotifyError>() {
+                    // nothing's wrong, there's just nothing to show
+                    Some(SpotifyError::NotPlaying) => Some(SpotifyData::default()),
+                    Some(SpotifyError::AuthError(_))
+                        if last_auth_retry.is_none_or(|at| at.elapsed() >= AUTH_RETRY) =>
+                    {
+                        eprintln!("{}, refreshing auth", e);
+                        last_auth_retry = Some(Instant::now());
+                        if let Err(e) = backend.refresh_auth().await {
+                            eprintln!("refresh failed: {}", e);
+                        }
+                        None
+                    }
+                    Some(SpotifyError::RateLimited { retry_after: wait }) => {
+                        eprintln!("{}", e);
+                        retry_after = *wait;
+                        None
+                    }
+                    _ => {
+                        eprintln!("poll failed: {:?}", e);
+                        None
+                    }
+                },
+            };
+            if let Some(data) = &data {
+                let settled = debouncer.observe(data, Instant::now());
+                let hidden =
+                    |track: &SpotifyData| privacy.is_active() || hide_explicit && track.explicit;
+                if let Some(track) = settled.filter(|track| !hidden(track)) {
+                    let _ = settled_tx.send(track);
+                }
+                if data.is_playing {
+                    last_playing = Instant::now();
+                }
+            }
+
+            let since_command = last_command.borrow().map(|at| at.elapsed());
+            delay = match &data {
+                Some(data) => {
+                    schedule.next_delay(Some(data), last_playing.elapsed(), since_command)
+                }
+                None => retry_after.unwrap_or_default().max(schedule.base),
+            };
+            // identical polls wake nothing downstream, so there's no relayout,
+            // redraw or lookup for a state that was already handled
+            polls_tx.send_if_modified(|last| {
+                let unchanged = match (last.as_ref(), data.as_ref()) {
+                    (Some(last), Some(data)) => data.unchanged_since(last),
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !unchanged {
+                    *last = data;
+                }
+                !unchanged
+            });
+        }
+    });
+
+    (tx, polls_rx)
+}
+
+fn woke_from_sleep(asked: Duration, waited: Duration) -> bool {
+    waited > asked + SLEEP_THRESHOLD
+}
+
+#[cfg(test)]
+fn track(name: &str) -> SpotifyData {
+    SpotifyData {
+        track_name: name.to_owned(),
+        artist_name: String::from("artist"),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_wall_clock_jump_is_sleep() {
+    let poll = Duration::from_secs(2);
+
+    assert!(!woke_from_sleep(poll, poll));
+    assert!(!woke_from_sleep(poll, Duration::from_secs(5)));
+    assert!(woke_from_sleep(poll, Duration::from_secs(60 * 60)));
+}
+
+#[test]
+fn test_rapid_skips_never_settle() {
+    let mut debouncer = TrackDebouncer::new(Duration::from_secs(5));
+    let start = Instant::now();
+
+    for (i, name) in ["a", "b", "c"].iter().enumerate() {
+        let now = start + Duration::from_secs(i as u64 * 2);
+        assert!(debouncer.observe(&track(name), now).is_none());
+    }
+
+    let settled = debouncer.observe(&track("c"), start + Duration::from_secs(9));
+    assert_eq!(settled.unwrap().track_name, "c");
+}
+
+#[test]
+fn test_settled_track_fires_once() {
+    let mut debouncer = TrackDebouncer::new(Duration::from_secs(5));
+    let start = Instant::now();
+
+    debouncer.observe(&track("a"), start);
+    assert!(debouncer
+        .observe(&track("a"), start + Duration::from_secs(5))
+        .is_some());
+    assert!(debouncer
+        .observe(&track("a"), start + Duration::from_secs(10))
+        .is_none());
+
+    // a brief detour back to the same song is a duplicate, not a new play
+    debouncer.observe(&track("b"), start + Duration::from_secs(11));
+    debouncer.observe(&track("a"), start + Duration::from_secs(12));
+    assert!(debouncer
+        .observe(&track("a"), start + Duration::from_secs(20))
+        .is_none());
+}
+
+#[test]
+fn test_adaptive_poll_delays() {
+    let schedule = PollSchedule {
+        base: Duration::from_secs(2),
+        heartbeat: Some(Duration::from_secs(15)),
+        adaptive: true,
+    };
+    let playing = SpotifyData {
+        is_playing: true,
+        progress_ms: Some(10_000),
+        duration_ms: Some(200_000),
+        ..track("a")
+    };
+    let ending = SpotifyData {
+        progress_ms: Some(199_000),
+        ..playing.clone()
+    };
+    let paused = SpotifyData {
+        is_playing: false,
+        ..playing.clone()
+    };
+    let idle = Duration::from_secs(60 * 60);
+
+    assert_eq!(
+        schedule.next_delay(Some(&playing), Duration::ZERO, None),
+        Duration::from_secs(15)
+    );
+    assert_eq!(
+        schedule.next_delay(Some(&ending), Duration::ZERO, None),
+        Duration::from_millis(1250)
+    );
+    let nearly_over = SpotifyData {
+        progress_ms: Some(192_000),
+        ..playing.clone()
+    };
+    assert_eq!(
+        schedule.next_delay(Some(&nearly_over), Duration::ZERO, None),
+        Duration::from_millis(8250)
+    );
+    assert_eq!(
+        schedule.next_delay(Some(&playing), Duration::ZERO, Some(Duration::from_secs(1))),
+        AFTER_COMMAND_INTERVAL
+    );
+    assert_eq!(
+        schedule.next_delay(Some(&paused), Duration::ZERO, None),
+        Duration::from_secs(15)
+    );
+    assert_eq!(schedule.next_delay(None, idle, None), LONG_IDLE_INTERVAL);
+
+    // mpris has no heartbeat, a playing track is polled at the base interval
+    let mpris = PollSchedule {
+        heartbeat: None,
+        ..schedule
+    };
+    assert_eq!(
+        mpris.next_delay(Some(&playing), Duration::ZERO, None),
+        mpris.base
+    );
+    assert_eq!(
+        mpris.next_delay(Some(&paused), Duration::ZERO, None),
+        mpris.base * 3
+    );
+}
+
+#[test]
+fn test_unchanged_polls() {
+    let stamped = |timestamp: u64, progress_ms: u64| SpotifyData {
+        timestamp: Some(timestamp),
+        progress_ms: Some(progress_ms),
+        is_playing: true,
+        ..track("a")
+    };
+    // progress moves between polls without the state changing
+    assert!(stamped(1000, 5000).unchanged_since(&stamped(1000, 3000)));
+    assert!(!stamped(2000, 5000).unchanged_since(&stamped(1000, 3000)));
+
+    let paused = SpotifyData {
+        is_playing: false,
+        ..track("a")
+    };
+    assert!(paused.unchanged_since(&paused.clone()));
+    assert!(!track("a").unchanged_since(&track("b")));
+}
@@ -0,0 +1,326 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::time::Instant;
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::musicbrainz;
+use crate::SpotifyData;
+
+type LookupFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<String>, anyhow::Error>> + Send + 'a>>;
+
+// somewhere to look up cover art for tracks whose source didn't send any,
+// which is common for MPRIS players. returns an image url, or None if the
+// provider doesn't know the album
+pub trait ArtProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    // requests to the provider are spaced at least this far apart
+    fn min_interval(&self) -> Duration;
+    fn lookup<'a>(&'a self, artist: &'a str, album: &'a str) -> LookupFuture<'a>;
+}
+
+// finds the release group on musicbrainz, then checks the cover art archive
+// actually has a front cover for it
+pub struct CoverArtArchive {
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct ReleaseGroupSearch {
+    #[serde(rename = "release-groups")]
+    release_groups: Vec<ReleaseGroup>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseGroup {
+    id: String,
+}
+
+impl ArtProvider for CoverArtArchive {
+    fn name(&self) -> &'static str {
+        "musicbrainz"
+    }
+
+    // spaced by musicbrainz::wait_turn instead, along with the enricher's
+    fn min_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn lookup<'a>(&'a self, artist: &'a str, album: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            musicbrainz::wait_turn().await;
+            let query = format!(
+                "releasegroup:\"{}\" AND artist:\"{}\"",
+                musicbrainz::escape(album),
+                musicbrainz::escape(artist)
+            );
+            let search = self
+                .client
+                .get("https://musicbrainz.org/ws/2/release-group/")
+                .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "1")])
+                .send_retrying(Endpoint::Art)
+                .await?
+                .error_for_status()?
+                .json::<ReleaseGroupSearch>()
+                .await?;
+            let release_group = match search.release_groups.first() {
+                Some(release_group) => release_group,
+                None => return Ok(None),
+            };
+
+            let url = format!(
+                "https://coverartarchive.org/release-group/{}/front-500",
+                release_group.id
+            );
+            let found = self
+                .client
+                .head(&url)
+                .send_retrying(Endpoint::Art)
+                .await?
+                .status()
+                .is_success();
+            Ok(found.then_some(url))
+        })
+    }
+}
+
+pub struct ITunesSearch {
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct ITunesResults {
+    results: Vec<ITunesAlbum>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ITunesAlbum {
+    artwork_url100: Option<String>,
+}
+
+impl ArtProvider for ITunesSearch {
+    fn name(&self) -> &'static str {
+        "itunes"
+    }
+
+    // apple documents roughly 20 requests a minute
+    fn min_interval(&self) -> Duration {
+        Duration::from_secs(3)
+    }
+
+    fn lookup<'a>(&'a self, artist: &'a str, album: &'a str) -> LookupFuture<'a> {
+        Box::pin(async move {
+            let term = format!("{} {}", artist, album);
+            let results = self
+                .client
+                .get("https://itunes.apple.com/search")
+                .query(&[("term", term.as_str()), ("entity", "album"), ("limit", "1")])
+                .send_retrying(Endpoint::Art)
+                .await?
+                .error_for_status()?
+                .json::<ITunesResults>()
+                .await?;
+            // the 100px thumbnail url serves any size asked for
+            Ok(results
+                .results
+                .into_iter()
+                .find_map(|album| album.artwork_url100)
+                .map(|url| url.replace("100x100bb", "600x600bb")))
+        })
+    }
+}
+
+pub fn provider(name: &str) -> Option<Box<dyn ArtProvider>> {
+    let client = musicbrainz::client();
+    match name {
+        "musicbrainz" => Some(Box::new(CoverArtArchive { client })),
+        "itunes" => Some(Box::new(ITunesSearch { client })),
+        _ => None,
+    }
+}
+
+// asks each provider in turn until one has art. answers are cached per album,
+// including "nobody has it", so replaying an album never repeats lookups
+pub struct ArtResolver {
+    // each provider with the time it may next be asked
+    providers: Vec<(Box<dyn ArtProvider>, Mutex<Instant>)>,
+    cache: Mutex<HashMap<(String, String), Option<String>>>,
+}
+
+impl ArtResolver {
+    pub fn new(providers: Vec<Box<dyn ArtProvider>>) -> Self {
+        ArtResolver {
+            providers: providers
+                .into_iter()
+                .map(|provider| (provider, Mutex::new(Instant::now())))
+                .collect(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn resolve(&self, track: &SpotifyData) -> Option<String> {
+        if !track.album_art_url.is_empty() {
+            return Some(track.album_art_url.clone());
+        }
+        if track.album_name.is_empty() {
+            return None;
+        }
+        let key = (
+            track.artist_name.to_lowercase(),
+            track.album_name.to_lowercase(),
+        );
+        if let Some(cached) = self.cache.lock().await.get(&key) {
+            return cached.clone();
+        }
+
+        let mut failed = false;
+        for (provider, next_request) in &self.providers {
+            {
+                let mut next_request = next_request.lock().await;
+                tokio::time::sleep_until(*next_request).await;
+                *next_request = Instant::now() + provider.min_interval();
+            }
+            match provider.lookup(&track.artist_name, &track.album_name).await {
+                Ok(Some(url)) => {
+                    self.cache.lock().await.insert(key, Some(url.clone()));
+                    return Some(url);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{} art lookup failed: {:?}", provider.name(), e);
+                    failed = true;
+                }
+            }
+        }
+        // an error might not happen next time, so only a clean miss is cached
+        if !failed {
+            self.cache.lock().await.insert(key, None);
+        }
+        None
+    }
+}
+
+// the cover for a settled track, its own or one a provider found, with the
+// album it's for so it's never shown against another
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlbumCover {
+    pub artist: String,
+    pub album: String,
+    pub url: String,
+}
+
+impl AlbumCover {
+    pub fn is_for(&self, data: &SpotifyData) -> bool {
+        self.artist == data.artist_name && self.album == data.album_name
+    }
+}
+
+// the track's own art url, or else the cover found for it. only the now.json
+// server asks for it
+#[cfg_attr(not(feature = "now-json"), allow(dead_code))]
+pub fn art_url<'a>(data: &'a SpotifyData, cover: Option<&'a AlbumCover>) -> &'a str {
+    if !data.album_art_url.is_empty() {
+        return &data.album_art_url;
+    }
+    cover
+        .filter(|cover| cover.is_for(data))
+        .map_or("", |cover| cover.url.as_str())
+}
+
+// publishes the cover of each settled track, looking one up for those that
+// came without any
+pub fn spawn_art_resolver(
+    resolver: ArtResolver,
+    mut settled: broadcast::Receiver<SpotifyData>,
+) -> watch::Receiver<Option<AlbumCover>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        loop {
+            let track = match settled.recv().await {
+                Ok(track) => track,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let cover = resolver.resolve(&track).await.map(|url| AlbumCover {
+                artist: track.artist_name,
+                album: track.album_name,
+                url,
+            });
+            if tx.send(cover).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+struct CountingProvider(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+#[cfg(test)]
+impl ArtProvider for CountingProvider {
+    fn name(&self) -> &'static str {
+        "counting"
+    }
+
+    fn min_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn lookup<'a>(&'a self, _artist: &'a str, album: &'a str) -> LookupFuture<'a> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Box::pin(async move { Ok((album == "known").then(|| String::from("art.jpg"))) })
+    }
+}
+
+#[tokio::test]
+async fn test_lookups_are_cached_per_album() {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let resolver = ArtResolver::new(vec![Box::new(CountingProvider(calls.clone()))]);
+    let track = |album: &str| SpotifyData {
+        artist_name: String::from("artist"),
+        album_name: album.to_owned(),
+        ..Default::default()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            resolver.resolve(&track("known")).await.as_deref(),
+            Some("art.jpg")
+        );
+        assert_eq!(resolver.resolve(&track("unknown")).await, None);
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_found_covers_only_fill_in_for_their_album() {
+    let track = SpotifyData {
+        artist_name: String::from("artist"),
+        album_name: String::from("album"),
+        ..Default::default()
+    };
+    let cover = AlbumCover {
+        artist: String::from("artist"),
+        album: String::from("album"),
+        url: String::from("found.jpg"),
+    };
+    assert_eq!(art_url(&track, Some(&cover)), "found.jpg");
+    assert_eq!(art_url(&track, None), "");
+
+    let other = SpotifyData {
+        album_name: String::from("other"),
+        ..track.clone()
+    };
+    assert_eq!(art_url(&other, Some(&cover)), "");
+    let own = SpotifyData {
+        album_art_url: String::from("own.jpg"),
+        ..track
+    };
+    assert_eq!(art_url(&own, Some(&cover)), "own.jpg");
+}
@@ -0,0 +1,164 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+// rotated at this size, keeping this many old files
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+const KEPT_LOGS: usize = 3;
+// query parameters that carry credentials
+const REDACTED_PARAMS: &[&str] = &["code", "token", "access_token", "refresh_token"];
+const RATE_LIMIT_HEADERS: &[&str] = &[
+    "retry-after",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+];
+
+// set once at startup when audit_log is on. every request in the app goes
+// through send, so there's no handle to pass around
+static LOG: OnceLock<Mutex<PathBuf>> = OnceLock::new();
+
+pub fn log_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+        .join("audit.log")
+}
+
+pub fn enable() {
+    let _ = LOG.set(Mutex::new(log_path()));
+}
+
+fn redact_url(url: &reqwest::Url) -> String {
+    let mut url = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if REDACTED_PARAMS.contains(&name.as_ref()) {
+                String::from("REDACTED")
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    if !pairs.is_empty() {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+// reqwest puts the whole url in its errors, credentials and all
+fn describe_error(e: &reqwest::Error) -> String {
+    let described = e.to_string();
+    match e.url() {
+        Some(url) => described.replace(url.as_str(), &redact_url(url)),
+        None => described,
+    }
+}
+
+// audit.log becomes audit.log.1, .1 becomes .2 and so on
+fn rotate(path: &PathBuf) {
+    for n in (1..KEPT_LOGS).rev() {
+        let _ = std::fs::rename(
+            path.with_extension(format!("log.{}", n)),
+            path.with_extension(format!("log.{}", n + 1)),
+        );
+    }
+    let _ = std::fs::rename(path, path.with_extension("log.1"));
+}
+
+fn write(line: &serde_json::Value) {
+    let Some(path) = LOG.get() else {
+        return;
+    };
+    let path = path.lock().unwrap();
+    if std::fs::metadata(&*path).is_ok_and(|meta| meta.len() >= MAX_LOG_BYTES) {
+        rotate(&path);
+    }
+    let res = std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&*path)?;
+        writeln!(file, "{}", line)
+    });
+    if let Err(e) = res {
+        eprintln!("couldn't write {}: {}", path.display(), e);
+    }
+}
+
+// sends the request, noting it in the audit log if that's on: method, url
+// without credentials, status, latency and any rate limit headers. headers
+// and bodies aren't logged, so tokens never are
+async fn send(request: reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    if LOG.get().is_none() {
+        return request.send().await;
+    }
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().to_string();
+    let url = redact_url(request.url());
+    let started = Instant::now();
+    let res = client.execute(request).await;
+
+    let mut line = serde_json::json!({
+        "at": chrono::Local::now().to_rfc3339(),
+        "method": method,
+        "url": url,
+        "latency_ms": started.elapsed().as_millis() as u64,
+    });
+    match &res {
+        Ok(response) => {
+            line["status"] = response.status().as_u16().into();
+            for header in RATE_LIMIT_HEADERS {
+                if let Some(value) = response.headers().get(*header) {
+                    line[*header] = value.to_str().unwrap_or_default().into();
+                }
+            }
+        }
+        Err(e) => line["error"] = describe_error(e).into(),
+    }
+    write(&line);
+    res
+}
+
+// every attempt http::send makes goes through this
+pub trait SendAudited {
+    fn send_audited(
+        self,
+    ) -> impl std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send;
+}
+
+impl SendAudited for reqwest::RequestBuilder {
+    fn send_audited(
+        self,
+    ) -> impl std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send {
+        send(self)
+    }
+}
+
+#[test]
+fn test_credentials_are_redacted() {
+    let url = reqwest::Url::parse(
+        "https://accounts.spotify.com/api/token?code=secret&redirect_uri=http%3A%2F%2F127.0.0.1%3A8000",
+    )
+    .unwrap();
+    let redacted = redact_url(&url);
+    assert!(!redacted.contains("secret"));
+    assert!(redacted.contains("code=REDACTED"));
+    assert!(redacted.contains("redirect_uri="));
+}
+
+#[tokio::test]
+async fn test_credentials_are_redacted_from_errors() {
+    // nothing listens on port 1, so this fails without leaving the machine
+    let e = reqwest::get("http://127.0.0.1:1/api/token?code=secret")
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("secret"));
+    let described = describe_error(&e);
+    assert!(!described.contains("secret"));
+    assert!(described.contains("code=REDACTED"));
+}
@@ -0,0 +1,267 @@
+use chrono::{Local, NaiveDateTime, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::player::{self, Backend, PlayerCommand};
+use crate::playlist;
+use crate::toast::Toast;
+
+// how often the clock is checked for automations set to a time of day
+const TICK: Duration = Duration::from_secs(15);
+// how long a focus or break phase can be set to
+const MIN_PHASE: Duration = Duration::from_secs(60);
+const MAX_PHASE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// the two halves of a pomodoro
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+impl Phase {
+    fn next(self) -> Self {
+        match self {
+            Phase::Work => Phase::Break,
+            Phase::Break => Phase::Work,
+        }
+    }
+}
+
+// a pomodoro timer, started and stopped with `kyomi ctl focus start|stop`.
+// it goes from work to break and back until it's stopped
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FocusTimer {
+    // minutes
+    pub work: f64,
+    #[serde(rename = "break")]
+    pub rest: f64,
+}
+
+impl Default for FocusTimer {
+    fn default() -> Self {
+        FocusTimer {
+            work: 25.0,
+            rest: 5.0,
+        }
+    }
+}
+
+impl FocusTimer {
+    fn length(&self, phase: Phase) -> Duration {
+        let minutes = match phase {
+            Phase::Work => self.work,
+            Phase::Break => self.rest,
+        };
+        crate::config::secs(minutes * 60.0, MIN_PHASE, MAX_PHASE)
+    }
+}
+
+// something done at a time of day, or when the focus timer gets to work or
+// break, set in the config as e.g.
+//   [[automations]]
+//   on = "work"
+//   play = "Deep Focus"
+//
+//   [[automations]]
+//   on = "break"
+//   pause = true
+//
+//   [[automations]]
+//   at = "09:00"
+//   play = "spotify:album:4aawyAB9vmqN3uQ7FjRGTy"
+// play takes one of your playlists by name, id or uri, or any spotify uri
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Automation {
+    // "HH:MM", every day
+    pub at: Option<String>,
+    pub on: Option<Phase>,
+    pub play: Option<String>,
+    pub pause: bool,
+}
+
+impl Automation {
+    fn time(&self) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(self.at.as_deref()?.trim(), "%H:%M").ok()
+    }
+}
+
+// whether `at` came round after `since`, up to and including `now`
+fn due(at: NaiveTime, since: NaiveDateTime, now: NaiveDateTime) -> bool {
+    let mut last = now.date().and_time(at);
+    if last > now {
+        last -= TimeDelta::days(1);
+    }
+    last > since
+}
+
+// spawned, so a slow playlist lookup doesn't hold up the clock or the timer
+async fn run(automation: Automation, backend: Backend, controls: UnboundedSender<PlayerCommand>) {
+    if automation.pause {
+        let _ = controls.send(PlayerCommand::Pause);
+    }
+    let Some(wanted) = automation.play.as_deref() else {
+        return;
+    };
+    let Backend::Spotify(spotify) = &backend else {
+        eprintln!("automations can only play with the spotify source");
+        return;
+    };
+    let spotify = match player::spotify_ready(spotify).await {
+        Ok(spotify) => spotify,
+        Err(e) => {
+            eprintln!("automation couldn't play {}: {}", wanted, e);
+            return;
+        }
+    };
+    let res = if wanted.starts_with("spotify:") && !wanted.starts_with("spotify:playlist:") {
+        spotify
+            .play_uri(wanted)
+            .await
+            .map(|_| wanted.to_owned())
+            .map_err(anyhow::Error::from)
+    } else {
+        playlist::play_named(&spotify, wanted).await
+    };
+    match res {
+        Ok(playing) => eprintln!("automation playing {}", playing),
+        Err(e) => eprintln!("automation couldn't play {}: {:?}", wanted, e),
+    }
+}
+
+// runs the automations as their times come round and the focus timer moves
+// between work and break. the timer starts when true is sent on the returned
+// channel and stops on false, and each new phase is announced in a toast
+pub fn spawn_automations(
+    backend: Backend,
+    controls: UnboundedSender<PlayerCommand>,
+    automations: Vec<Automation>,
+    timer: FocusTimer,
+) -> (watch::Receiver<Option<Toast>>, UnboundedSender<bool>) {
+    let (toast_tx, toast_rx) = watch::channel(None);
+    let (focus_tx, mut focus_rx) = mpsc::unbounded_channel::<bool>();
+    for automation in &automations {
+        if automation.at.is_some() && automation.time().is_none() {
+            eprintln!(
+                "ignoring automation at {:?}, expected HH:MM",
+                automation.at.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    tokio::spawn(async move {
+        let start = |automation: &Automation| {
+            tokio::spawn(run(automation.clone(), backend.clone(), controls.clone()));
+        };
+        let mut checked = Local::now().naive_local();
+        let mut tick = tokio::time::interval(TICK);
+        // the phase the focus timer is in, and when it ends
+        let mut phase: Option<(Phase, Instant)> = None;
+        loop {
+            let phase_ends = phase.map(|(_, ends)| ends);
+            let started = tokio::select! {
+                _ = tick.tick() => {
+                    let now = Local::now().naive_local();
+                    for automation in &automations {
+                        if automation.time().is_some_and(|at| due(at, checked, now)) {
+                            start(automation);
+                        }
+                    }
+                    checked = now;
+                    None
+                }
+                focus = focus_rx.recv() => match focus {
+                    Some(true) => Some(Phase::Work),
+                    Some(false) => {
+                        phase = None;
+                        None
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(phase_ends.unwrap_or_else(Instant::now)), if phase_ends.is_some() => {
+                    phase.map(|(current, _)| current.next())
+                }
+            };
+            let Some(started) = started else {
+                continue;
+            };
+            let length = timer.length(started);
+            phase = Some((started, Instant::now() + length));
+            let name = match started {
+                Phase::Work => "focus",
+                Phase::Break => "break",
+            };
+            let minutes = (length.as_secs_f64() / 60.0).round();
+            toast_tx.send_replace(Some(Toast::new(vec![format!(
+                "{} for {} min",
+                name, minutes
+            )])));
+            for automation in automations.iter().filter(|a| a.on == Some(started)) {
+                start(automation);
+            }
+        }
+    });
+    (toast_rx, focus_tx)
+}
+
+#[test]
+fn test_automation_times_come_round_once() {
+    let at = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+    let when = |when: &str| NaiveDateTime::parse_from_str(when, "%Y-%m-%d %H:%M:%S").unwrap();
+
+    assert!(due(
+        at("09:00"),
+        when("2024-03-01 08:59:50"),
+        when("2024-03-01 09:00:05")
+    ));
+    // already done on the last check
+    assert!(!due(
+        at("09:00"),
+        when("2024-03-01 09:00:05"),
+        when("2024-03-01 09:00:20")
+    ));
+    assert!(!due(
+        at("09:00"),
+        when("2024-03-01 08:30:00"),
+        when("2024-03-01 08:59:59")
+    ));
+    // across midnight, and after the machine slept through it
+    assert!(due(
+        at("00:00"),
+        when("2024-03-01 23:59:50"),
+        when("2024-03-02 00:00:05")
+    ));
+    assert!(due(
+        at("09:00"),
+        when("2024-03-01 22:00:00"),
+        when("2024-03-02 10:00:00")
+    ));
+
+    let automation: Automation = toml::from_str("at = \"7:30\"\npause = true").unwrap();
+    assert_eq!(automation.time(), Some(at("07:30")));
+    let automation: Automation = toml::from_str("on = \"break\"\nat = \"noon\"").unwrap();
+    assert_eq!(automation.on, Some(Phase::Break));
+    assert_eq!(automation.time(), None);
+}
+
+#[test]
+fn test_focus_phase_lengths() {
+    let timer = |work| FocusTimer { work, rest: 5.0 };
+    assert_eq!(
+        timer(25.0).length(Phase::Work),
+        Duration::from_secs(25 * 60)
+    );
+    assert_eq!(
+        timer(25.0).length(Phase::Break),
+        Duration::from_secs(5 * 60)
+    );
+    assert_eq!(timer(0.0).length(Phase::Work), MIN_PHASE);
+    // toml takes inf and nan
+    assert_eq!(timer(f64::INFINITY).length(Phase::Work), MAX_PHASE);
+    assert_eq!(timer(f64::NAN).length(Phase::Work), MIN_PHASE);
+}
@@ -0,0 +1,234 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::watch;
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::musicbrainz;
+
+// how long the cursor has to rest on the artist before the card opens
+const HOVER_DELAY: Duration = Duration::from_secs(1);
+// what fits across the widget at the default text size
+const CARD_LINE_CHARS: usize = 26;
+const BIO_LINES: usize = 2;
+const TOP_SONGS: usize = 2;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArtistCard {
+    pub artist: String,
+    // first paragraph of the artist's wikipedia article
+    pub bio: String,
+    pub top_songs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct WikiSummary {
+    #[serde(rename = "type")]
+    page_type: String,
+    extract: String,
+}
+
+#[derive(Deserialize)]
+struct ITunesSongs {
+    results: Vec<ITunesSong>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ITunesSong {
+    track_name: Option<String>,
+}
+
+async fn wiki_summary(client: &reqwest::Client, title: &str) -> Option<String> {
+    let url = format!(
+        "https://en.wikipedia.org/api/rest_v1/page/summary/{}",
+        urlencoding::encode(&title.replace(' ', "_"))
+    );
+    let summary = client
+        .get(url)
+        .send_retrying(Endpoint::Art)
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json::<WikiSummary>()
+        .await
+        .ok()?;
+    // a name shared with something else lands on a disambiguation page
+    (summary.page_type == "standard").then_some(summary.extract)
+}
+
+async fn fetch_card(client: &reqwest::Client, artist: &str) -> ArtistCard {
+    let mut bio = None;
+    for title in [
+        artist.to_owned(),
+        format!("{} (band)", artist),
+        format!("{} (musician)", artist),
+    ] {
+        bio = wiki_summary(client, &title).await;
+        if bio.is_some() {
+            break;
+        }
+    }
+
+    let top_songs = match client
+        .get("https://itunes.apple.com/search")
+        .query(&[
+            ("term", artist),
+            ("entity", "song"),
+            ("attribute", "artistTerm"),
+            ("limit", "10"),
+        ])
+        .send_retrying(Endpoint::Art)
+        .await
+        .and_then(|res| res.error_for_status())
+    {
+        Ok(res) => res.json::<ITunesSongs>().await.map_or(Vec::new(), |songs| {
+            let mut names: Vec<String> = Vec::new();
+            for name in songs.results.into_iter().filter_map(|song| song.track_name) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            names
+        }),
+        Err(e) => {
+            eprintln!("couldn't get top songs for {}: {:?}", artist, e);
+            Vec::new()
+        }
+    };
+
+    ArtistCard {
+        artist: artist.to_owned(),
+        bio: bio.unwrap_or_default(),
+        top_songs: top_songs.into_iter().take(TOP_SONGS).collect(),
+    }
+}
+
+// fetches cards for artist names sent to it, one at a time, remembering every
+// card for the rest of the session
+fn spawn_card_fetcher() -> (UnboundedSender<String>, watch::Receiver<Option<ArtistCard>>) {
+    let (request_tx, mut request_rx) = mpsc::unbounded_channel::<String>();
+    let (card_tx, card_rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let client = musicbrainz::client();
+        let mut cache: HashMap<String, ArtistCard> = HashMap::new();
+        while let Some(artist) = request_rx.recv().await {
+            let card = match cache.get(&artist) {
+                Some(card) => card.clone(),
+                None => {
+                    let card = fetch_card(&client, &artist).await;
+                    cache.insert(artist, card.clone());
+                    card
+                }
+            };
+            if card_tx.send(Some(card)).is_err() {
+                break;
+            }
+        }
+    });
+    (request_tx, card_rx)
+}
+
+// the hover card over the artist name: opens after the cursor rests on it,
+// closes when the cursor leaves the widget
+pub struct HoverCard {
+    requests: UnboundedSender<String>,
+    cards: watch::Receiver<Option<ArtistCard>>,
+    hover_since: Option<Instant>,
+    showing: Option<String>,
+}
+
+impl HoverCard {
+    pub fn spawn() -> Self {
+        let (requests, cards) = spawn_card_fetcher();
+        HoverCard {
+            requests,
+            cards,
+            hover_since: None,
+            showing: None,
+        }
+    }
+
+    pub fn hover(&mut self, over_artist: bool, now: Instant) {
+        if !over_artist {
+            self.hover_since = None;
+        } else if self.hover_since.is_none() {
+            self.hover_since = Some(now);
+        }
+    }
+
+    pub fn leave(&mut self) {
+        self.hover_since = None;
+        self.showing = None;
+    }
+
+    // asks for the card once the cursor has rested long enough
+    pub fn poll(&mut self, artist: &str, now: Instant) {
+        let rested = self
+            .hover_since
+            .is_some_and(|since| now.duration_since(since) >= HOVER_DELAY);
+        if rested && self.showing.is_none() {
+            let _ = self.requests.send(artist.to_owned());
+            self.showing = Some(artist.to_owned());
+        }
+    }
+
+    // None until the card for the hovered artist has arrived
+    pub fn card(&self) -> Option<ArtistCard> {
+        let showing = self.showing.as_ref()?;
+        self.cards
+            .borrow()
+            .clone()
+            .filter(|card| &card.artist == showing)
+    }
+}
+
+// the card as widget lines: the artist, the start of the bio and a couple of
+// songs
+pub fn card_lines(card: &ArtistCard) -> Vec<String> {
+    let mut lines = vec![card.artist.clone()];
+    lines.extend(wrap(&card.bio, CARD_LINE_CHARS, BIO_LINES));
+    lines.extend(card.top_songs.iter().map(|song| format!("♪ {}", song)));
+    lines
+}
+
+// word wraps into at most max_lines, marking anything cut off with …
+fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let len = current.chars().count();
+        if len > 0 && len + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = &mut lines[max_lines - 1];
+        if last.chars().count() >= width {
+            *last = last.chars().take(width - 1).collect();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+#[test]
+fn test_bio_wraps_and_truncates() {
+    let bio = "Radiohead are an English rock band formed in Abingdon, Oxfordshire, in 1985.";
+    assert_eq!(
+        wrap(bio, 26, 2),
+        vec!["Radiohead are an English", "rock band formed in…"]
+    );
+    assert_eq!(wrap("short", 26, 2), vec!["short"]);
+}
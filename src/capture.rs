@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+
+// plenty for text that changes a few times a second
+const FRAME_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+
+// streams rendered frames to a command as raw rgba on its stdin, for feeding
+// a virtual camera or an NDI sender, e.g. with v4l2loopback
+//   ffmpeg -f rawvideo -pix_fmt rgba -video_size {width}x{height} -framerate 30
+//     -i - -f v4l2 -pix_fmt yuv420p /dev/video10
+// the command is restarted whenever the widget changes size
+pub struct FrameOutput {
+    command: String,
+    frames: Option<SyncSender<Vec<u8>>>,
+    // what the running command was told to expect
+    size: (u32, u32),
+    last_frame: Option<Instant>,
+    // gave up after the command failed to start or went away
+    stopped: bool,
+}
+
+impl FrameOutput {
+    pub fn new(command: String) -> Self {
+        FrameOutput {
+            command,
+            frames: None,
+            size: (0, 0),
+            last_frame: None,
+            stopped: false,
+        }
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        !self.stopped
+            && self
+                .last_frame
+                .is_none_or(|last| now.duration_since(last) >= FRAME_INTERVAL)
+    }
+
+    // frames are dropped while the command is still busy with the last one
+    pub fn send(&mut self, size: (u32, u32), rgba: Vec<u8>, now: Instant) {
+        self.last_frame = Some(now);
+        if self.frames.is_none() || self.size != size {
+            match spawn(&expand(&self.command, size)) {
+                Ok(frames) => {
+                    self.frames = Some(frames);
+                    self.size = size;
+                }
+                Err(e) => {
+                    eprintln!("couldn't start frame output: {:?}", e);
+                    self.stopped = true;
+                    return;
+                }
+            }
+        }
+        let frames = self.frames.as_ref().unwrap();
+        if let Err(TrySendError::Disconnected(_)) = frames.try_send(rgba) {
+            eprintln!("frame output stopped");
+            self.frames = None;
+            self.stopped = true;
+        }
+    }
+}
+
+fn expand(command: &str, (width, height): (u32, u32)) -> String {
+    command
+        .replace("{width}", &width.to_string())
+        .replace("{height}", &height.to_string())
+}
+
+// arguments are split on whitespace, there's no quoting
+fn spawn(command: &str) -> Result<SyncSender<Vec<u8>>, anyhow::Error> {
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("frame_output is empty"))?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+
+    // writes block on the command, so they happen off the winit thread
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(1);
+    std::thread::spawn(move || {
+        for frame in rx {
+            if let Err(e) = stdin.write_all(&frame) {
+                eprintln!("frame output failed: {}", e);
+                break;
+            }
+        }
+        drop(stdin);
+        let _ = child.wait();
+    });
+    Ok(tx)
+}
+
+#[test]
+fn test_expands_frame_size() {
+    assert_eq!(
+        expand("ffmpeg -video_size {width}x{height} -i -", (512, 256)),
+        "ffmpeg -video_size 512x256 -i -"
+    );
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+// longest step a single frame can advance animations by, so a stall (dragging
+// the window, a slow frame) doesn't make everything jump
+const MAX_DELTA: Duration = Duration::from_millis(100);
+
+// drives animations only. it's monotonic and stops while the widget is hidden,
+// so nothing animates off screen and nothing lurches on return. anything that
+// has to track real time (playback position, schedules, polling) keeps using
+// its own Instant/SystemTime and is unaffected by pausing.
+pub struct AnimationClock {
+    last_frame: Instant,
+    paused: bool,
+}
+
+impl AnimationClock {
+    pub fn new(now: Instant) -> Self {
+        AnimationClock {
+            last_frame: now,
+            paused: false,
+        }
+    }
+
+    // advances to `now` and returns the step for this frame
+    pub fn tick(&mut self, now: Instant) -> Duration {
+        let delta = if self.paused {
+            Duration::ZERO
+        } else {
+            now.saturating_duration_since(self.last_frame)
+                .min(MAX_DELTA)
+        };
+        self.last_frame = now;
+        delta
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self, now: Instant) {
+        if self.paused {
+            self.paused = false;
+            self.last_frame = now;
+        }
+    }
+}
+
+#[test]
+fn test_paused_time_is_skipped() {
+    let start = Instant::now();
+    let mut clock = AnimationClock::new(start);
+
+    assert_eq!(
+        clock.tick(start + Duration::from_millis(16)),
+        Duration::from_millis(16)
+    );
+    clock.pause();
+    assert_eq!(clock.tick(start + Duration::from_secs(60)), Duration::ZERO);
+
+    clock.resume(start + Duration::from_secs(120));
+    assert_eq!(
+        clock.tick(start + Duration::from_millis(120_016)),
+        Duration::from_millis(16)
+    );
+}
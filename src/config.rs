@@ -0,0 +1,468 @@
+use serde::{Deserialize, Serialize};
+
+use crate::automation::{Automation, FocusTimer};
+use crate::gesture::Gestures;
+use crate::history_store::HistoryBackend;
+use crate::http::Timeouts;
+use crate::input::HoldModifier;
+use crate::layout::WidgetShape;
+use crate::now_json::NowJson;
+use crate::rating::RatingAction;
+use crate::sleep::FadeCurve;
+use crate::theme::{AccentEffect, ContextTheme};
+use crate::webhook::Webhook;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// user-edited settings, read once at startup from config.toml in the
+// platform config directory (~/.config/kyomi on linux). anything missing from
+// the file falls back to the defaults below.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    // gap between the widget and the screen edges, in logical pixels
+    pub margin_x: u32,
+    pub margin_y: u32,
+    // space around and between elements inside the widget, in logical pixels
+    pub padding: f32,
+    // start in privacy mode, and/or enter it daily between two times, e.g.
+    // privacy_schedule = "22:00-07:00"
+    pub private: bool,
+    pub privacy_schedule: Option<String>,
+    // shown instead of the track while streamer mode is on
+    pub redact_message: String,
+    // a small E after the title of explicit tracks, or for family and
+    // streaming setups, their titles left out of everything kyomi shows or
+    // sends, and the tracks left out of scrobbles, history and webhooks
+    pub explicit_badge: bool,
+    pub hide_explicit: bool,
+    // colored dot showing which source the track came from
+    // (spotify green, MPRIS gray), handy when checking source priority
+    pub source_badge: bool,
+    // window classes or process names, e.g. ["obs", "steam_app_570"]. the
+    // widget hides itself while any of them has focus
+    pub hide_when_focused: Vec<String>,
+    // x11 only. "utility" floats in most tiling window managers, "dock" also
+    // keeps it out of the way of focus, "normal" leaves it to the window
+    // manager. override redirect takes the window manager out of it entirely
+    pub x11_window_type: X11WindowType,
+    pub x11_override_redirect: bool,
+    // show the widget on every workspace. on x11 this also keeps it out of the
+    // taskbar and pager and needs wmctrl, on wayland it works on sway and
+    // hyprland (floating windows only). not available on windows
+    pub sticky: bool,
+    // clicks go through the widget to whatever is underneath, except while
+    // this key is held, e.g. interact_modifier = "alt". one of alt, ctrl,
+    // shift, super. unset keeps the widget interactive all the time
+    pub interact_modifier: Option<HoldModifier>,
+    // a command to stream the widget's frames to as raw rgba, e.g. ffmpeg
+    // writing to a v4l2loopback camera or an NDI sender. {width} and {height}
+    // are replaced with the frame size, see capture.rs
+    pub frame_output: Option<String>,
+    // name of the spotify connect device that's playing, e.g. "phone · Pixel"
+    pub device_indicator: bool,
+    // small shuffle and repeat marks next to it, when they're on
+    pub playback_mode_indicator: bool,
+    // a heart when the track is in the spotify library. l likes or unlikes
+    // it, as can a gesture set to "toggle_like"
+    pub like_indicator: bool,
+    // picking the connect device with this name in spotify opens the expanded
+    // view, and picking another closes it. kyomi can't be a connect device
+    // itself, that takes librespot's protocol and somewhere to play audio
+    // (the web playback sdk only runs in a browser), so run librespot or
+    // spotifyd under the name "kyomi" alongside it
+    pub connect_target: Option<String>,
+    // the next few tracks in the queue under the current one
+    pub queue_preview: bool,
+    // seconds before a track ends to show what's next in a toast, e.g. 10
+    pub up_next_toast: Option<f64>,
+    // a loudness target in dB, e.g. -14. the expanded details page then
+    // shows how loud the track is mastered against it, going by spotify's
+    // loudness figure, which is an average in dB and not LUFS
+    pub loudness_target: Option<f32>,
+    // tap b along with the music to count its bpm, shown against the tempo
+    // spotify has for the track
+    pub tap_tempo: bool,
+    // what the now playing page shows, one template per line, e.g.
+    // "{{track | truncate:24}}". the expanded lines are added below when
+    // expanded. fields: track, artist, album, status, progress, duration,
+    // progress_ms, duration_ms, source, context (the playlist or album playing
+    // from). filters: upper, lower, truncate:N, duration
+    pub lines: Vec<String>,
+    pub expanded_lines: Vec<String>,
+    // rgb, 0-1. used for the progress bar
+    pub accent: [f32; 3],
+    // animates the accent: "breathe" pulses it, "cycle" fades through
+    // accent_palette (up to 4 colors, or around the color wheel if empty),
+    // "pulse" flashes it in time with the track's tempo from spotify.
+    // period is the seconds per breath or trip through the palette
+    pub accent_effect: AccentEffect,
+    pub accent_period: f32,
+    pub accent_palette: Vec<[f32; 3]>,
+    // accents and icons for particular playlists, albums and artists, see
+    // theme::ContextTheme
+    pub context_themes: Vec<ContextTheme>,
+    // keeps the accent still whatever the theme asks for
+    pub reduce_motion: bool,
+    // rgb, 0-1
+    pub text_color: [f32; 3],
+    // rounding of the widget's corners, in logical pixels
+    pub corner_radius: f32,
+    // "rounded" uses corner_radius, "pill" rounds the ends all the way.
+    // clicks in the transparent corners outside the outline do nothing, though
+    // they still land on the widget's window rather than what's under it
+    pub shape: WidgetShape,
+    // of the whole widget, 0-1
+    pub opacity: f32,
+    pub text_hinting: TextHinting,
+    // a .ttf or .otf file to draw text with. unset uses the bundled Fira Code,
+    // or a system monospace font in lite builds, which leave it out
+    pub font: Option<PathBuf>,
+    // a second font for characters the first can't draw, e.g. a Noto CJK
+    // .otf. unset asks fontconfig for one that covers metadata_language
+    pub fallback_font: Option<PathBuf>,
+    // blend text in linear light. off gives heavier, more traditional looking
+    // text, which can read better at small sizes on standard-DPI screens
+    pub gamma_correct_text: bool,
+    // where to look for cover art when the source has none, tried in order.
+    // "musicbrainz" (cover art archive) and "itunes"
+    pub art_providers: Vec<String>,
+    // fill in year, label and properly spelled artists for MPRIS tracks from
+    // musicbrainz. answers are cached on disk
+    pub musicbrainz_enrichment: bool,
+    // fetch the full album of each spotify track, for its label, genres and
+    // copyright on the expanded details page and in expanded_lines
+    pub album_labels: bool,
+    // rest the cursor on the artist for a short bio and a few songs, from
+    // wikipedia and itunes
+    pub artist_cards: bool,
+    // the album cover to the left of the text, for tracks from spotify
+    pub album_art: bool,
+    // your own spotify app, written by `kyomi setup spotify`. an unset id uses
+    // the app kyomi was built with. without a secret, login uses PKCE. setup
+    // seals the secret to this machine, one typed in by hand works too
+    pub spotify_client_id: Option<String>,
+    pub spotify_client_secret: Option<String>,
+    pub spotify_redirect_uri: Option<String>,
+    // use a web api token something else keeps fresh instead of logging in,
+    // see Spotify::with_external_token. KYOMI_SPOTIFY_TOKEN does the same
+    // with a token from the environment
+    pub spotify_token_file: Option<PathBuf>,
+    // seconds before the token runs out that it's refreshed, ahead of the
+    // next call
+    pub token_refresh_margin: u64,
+    // submit what's playing and finished listens to listenbrainz. the user
+    // token from listenbrainz.org/settings, leave unset to not scrobble there
+    pub listenbrainz_token: Option<String>,
+    // seconds of crossfade set in the player. each track then gives way that
+    // much early, which listens and the history make up for
+    pub crossfade: f64,
+    // seconds between polls of the player. adaptive polling goes quicker just
+    // after a command, and slower while paused
+    pub poll_interval: f64,
+    pub adaptive_polling: bool,
+    // with adaptive polling, a playing spotify track is polled again just
+    // after it should end, and every this many seconds until then to catch
+    // changes made on other devices. paused ones no more often than that
+    pub poll_heartbeat: f64,
+    // warn when spotify connect playback keeps stopping mid-track by itself,
+    // and optionally start it again
+    pub playback_watchdog: bool,
+    pub watchdog_auto_resume: bool,
+    // keep a local log of what was played and for how long, for
+    // `kyomi history`
+    pub history: bool,
+    // "sqlite", or "jsonl" for a plain file. builds without the sqlite
+    // feature always use jsonl
+    pub history_backend: HistoryBackend,
+    // after a run of skips, suggest switching to a radio or playlist
+    pub skip_streak_hint: bool,
+    // a page with last week's top artists, tracks, minutes and most skipped,
+    // and/or the same as a desktop notification on monday morning
+    pub weekly_recap: bool,
+    pub recap_notification: bool,
+    // how numbers and dates are written on stats pages, e.g. "de_DE". unset
+    // follows LC_ALL, LC_NUMERIC, LC_TIME and LANG
+    pub locale: Option<String>,
+    // the language spotify names albums, artists and tracks in where it has
+    // them translated, e.g. "ja" or "pt-BR", sent as Accept-Language. unset
+    // leaves it to spotify, which goes by the account's country
+    pub metadata_language: Option<String>,
+    // a line under titles in other scripts reading them in latin letters:
+    // romaji, pinyin, korean romanization and so on. kanji on their own are
+    // read as japanese when metadata_language is, otherwise as chinese. needs
+    // the romanize feature
+    pub romanize_titles: bool,
+    // what clicking, long pressing and dragging on the widget does
+    pub gestures: Gestures,
+    // for networks that need them, e.g. proxy = "socks5h://127.0.0.1:1080"
+    // and ca_bundle = "/etc/corp/root-ca.pem". see http.rs
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<PathBuf>,
+    // connect and read timeouts, retries and a total time budget for auth,
+    // polling and art requests, see http.rs
+    pub timeouts: Timeouts,
+    // serves the track as /now.json, for browser sources in OBS and the like.
+    // not in builds without the now-json feature, like the lite one
+    pub now_json: Option<NowJson>,
+    // posts track changes, pauses and resumes to a url of your own
+    pub webhook: Option<Webhook>,
+    // log every request kyomi makes, without credentials, to audit.log in
+    // the local data directory (~/.local/share/kyomi on linux)
+    pub audit_log: bool,
+    // bound to + and -
+    pub rate_up: RatingAction,
+    pub rate_down: RatingAction,
+    // the playlist f adds the playing track to, by name, id or uri
+    pub favorites_playlist: Option<String>,
+    // a pomodoro timer under [focus_timer], minutes of work and of break
+    pub focus_timer: FocusTimer,
+    // playlists to start or playback to pause at a time of day or as the
+    // focus timer moves between work and break, see automation::Automation
+    pub automations: Vec<Automation>,
+    // seconds over which `kyomi ctl sleep` turns the volume down before it
+    // pauses, and how: "linear", "ease_in" or "ease_out"
+    pub sleep_fade: f64,
+    pub sleep_fade_curve: FadeCurve,
+}
+
+pub const DEFAULT_LINES: &[&str] = &["{{track}}", "{{artist}}"];
+pub const DEFAULT_EXPANDED_LINES: &[&str] = &["{{album_year}}", "{{position}}"];
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            margin_x: 16,
+            margin_y: 16,
+            padding: 10.0,
+            private: false,
+            privacy_schedule: None,
+            redact_message: String::from("♪ music playing"),
+            explicit_badge: true,
+            hide_explicit: false,
+            source_badge: false,
+            hide_when_focused: Vec::new(),
+            x11_window_type: X11WindowType::Utility,
+            x11_override_redirect: false,
+            sticky: true,
+            interact_modifier: None,
+            frame_output: None,
+            device_indicator: true,
+            playback_mode_indicator: true,
+            like_indicator: true,
+            connect_target: None,
+            queue_preview: false,
+            up_next_toast: None,
+            loudness_target: None,
+            tap_tempo: false,
+            lines: DEFAULT_LINES.iter().map(|line| line.to_string()).collect(),
+            expanded_lines: DEFAULT_EXPANDED_LINES
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+            accent: [0.9, 1.0, 1.0],
+            accent_effect: AccentEffect::Still,
+            accent_period: 8.0,
+            accent_palette: Vec::new(),
+            context_themes: Vec::new(),
+            reduce_motion: false,
+            text_color: [0.9, 1.0, 1.0],
+            corner_radius: 0.0,
+            shape: WidgetShape::Rounded,
+            opacity: 1.0,
+            text_hinting: TextHinting::Subpixel,
+            font: None,
+            fallback_font: None,
+            gamma_correct_text: true,
+            art_providers: vec![String::from("musicbrainz"), String::from("itunes")],
+            musicbrainz_enrichment: false,
+            album_labels: false,
+            artist_cards: false,
+            album_art: false,
+            spotify_client_id: None,
+            spotify_client_secret: None,
+            spotify_redirect_uri: None,
+            spotify_token_file: None,
+            token_refresh_margin: 60,
+            listenbrainz_token: None,
+            crossfade: 0.0,
+            poll_interval: 2.0,
+            adaptive_polling: true,
+            poll_heartbeat: 15.0,
+            playback_watchdog: true,
+            watchdog_auto_resume: false,
+            history: true,
+            history_backend: HistoryBackend::Sqlite,
+            skip_streak_hint: false,
+            weekly_recap: false,
+            recap_notification: false,
+            locale: None,
+            metadata_language: None,
+            romanize_titles: false,
+            gestures: Gestures::default(),
+            proxy: None,
+            ca_bundle: None,
+            timeouts: Timeouts::default(),
+            now_json: None,
+            webhook: None,
+            audit_log: false,
+            rate_up: RatingAction::SaveTrack,
+            rate_down: RatingAction::RemoveTrack,
+            favorites_playlist: None,
+            focus_timer: FocusTimer::default(),
+            automations: Vec::new(),
+            sleep_fade: 60.0,
+            sleep_fade_curve: FadeCurve::Linear,
+        }
+    }
+}
+
+// glyphs are always rasterized as grayscale coverage, this picks how they're
+// positioned. subpixel keeps the font's exact spacing by rasterizing glyphs
+// separately for fractional pixel offsets, grayscale snaps them to whole
+// pixels for crisper stems
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextHinting {
+    Grayscale,
+    Subpixel,
+}
+
+impl TextHinting {
+    // how far apart two positions of a glyph can be and still share a raster
+    pub fn position_tolerance(&self) -> f32 {
+        match self {
+            TextHinting::Grayscale => 1.0,
+            TextHinting::Subpixel => 0.1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum X11WindowType {
+    Normal,
+    Utility,
+    Dock,
+}
+
+// seconds from the config as a Duration held between min and max.
+// Duration::from_secs_f64 panics on infinities and NaN, which toml allows
+pub fn secs(value: f64, min: Duration, max: Duration) -> Duration {
+    Duration::try_from_secs_f64(value)
+        .unwrap_or(if value > 0.0 { max } else { min })
+        .clamp(min, max)
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("config.toml")
+    }
+
+    // a broken config shouldn't stop the widget from starting
+    pub fn load() -> Self {
+        let path = Config::path();
+        match std::fs::read_to_string(&path) {
+            Ok(raw) => toml::from_str(&raw).unwrap_or_else(|e| {
+                eprintln!("couldn't parse {}: {}", path.display(), e);
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        }
+    }
+}
+
+// packs config.toml and themes/ into a single tar so a setup can be copied to
+// another machine. tokens and ui state live elsewhere and never end up in it.
+pub fn export_bundle(path: &Path) -> Result<(), anyhow::Error> {
+    export_bundle_from(&config_dir(), path)
+}
+
+pub fn import_bundle(path: &Path) -> Result<(), anyhow::Error> {
+    import_bundle_into(&config_dir(), path)
+}
+
+fn export_bundle_from(dir: &Path, path: &Path) -> Result<(), anyhow::Error> {
+    let mut archive = tar::Builder::new(File::create(path)?);
+    let config_path = dir.join("config.toml");
+    if config_path.is_file() {
+        archive.append_path_with_name(&config_path, "config.toml")?;
+    }
+    let themes = dir.join("themes");
+    if themes.is_dir() {
+        archive.append_dir_all("themes", &themes)?;
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+// only config.toml and themes/ are unpacked, whatever else the archive holds.
+// the current config is kept as config.toml.bak
+fn import_bundle_into(dir: &Path, path: &Path) -> Result<(), anyhow::Error> {
+    let mut archive = tar::Archive::new(File::open(path)?);
+    std::fs::create_dir_all(dir)?;
+
+    let config_path = dir.join("config.toml");
+    if config_path.is_file() {
+        std::fs::copy(&config_path, dir.join("config.toml.bak"))?;
+    }
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if entry_path == Path::new("config.toml") || entry_path.starts_with("themes") {
+            entry.unpack_in(dir)?;
+        } else {
+            eprintln!("skipping {}", entry_path.display());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bundle_round_trip_skips_tokens() {
+    let root = std::env::temp_dir().join(format!("kyomi-bundle-{}", std::process::id()));
+    let from = root.join("from");
+    let to = root.join("to");
+    std::fs::create_dir_all(from.join("themes")).unwrap();
+    std::fs::write(from.join("config.toml"), "padding = 4.0").unwrap();
+    std::fs::write(from.join("themes").join("dark.toml"), "").unwrap();
+    std::fs::write(from.join("token"), "secret").unwrap();
+
+    let bundle = root.join("bundle.tar");
+    export_bundle_from(&from, &bundle).unwrap();
+    import_bundle_into(&to, &bundle).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(to.join("config.toml")).unwrap(),
+        "padding = 4.0"
+    );
+    assert!(to.join("themes").join("dark.toml").is_file());
+    assert!(!to.join("token").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_secs_stay_in_range() {
+    let (min, max) = (Duration::from_millis(100), Duration::from_secs(60));
+    assert_eq!(secs(1.5, min, max), Duration::from_millis(1500));
+    assert_eq!(secs(0.0, min, max), min);
+    assert_eq!(secs(-3.0, min, max), min);
+    assert_eq!(secs(1e300, min, max), max);
+    assert_eq!(secs(f64::INFINITY, min, max), max);
+    assert_eq!(secs(f64::NAN, min, max), min);
+}
+
+#[test]
+fn test_partial_config_keeps_defaults() {
+    let config: Config = toml::from_str("margin_x = 40").unwrap();
+    assert_eq!(config.margin_x, 40);
+    assert_eq!(config.margin_y, Config::default().margin_y);
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::art::AlbumCover;
+use crate::player::Backend;
+use crate::spotify::{AlbumArt, AlbumArtFetcher};
+
+// the settled track's cover, with the album it's for so it's never drawn
+// against a different track
+pub type TrackArt = Option<(AlbumCover, Arc<AlbumArt>)>;
+
+// fetches each cover the art resolver publishes. the spotify backend's art
+// client and cache are used when there is one, without holding on to the
+// client itself
+pub fn spawn_album_art(
+    backend: Backend,
+    mut covers: watch::Receiver<Option<AlbumCover>>,
+) -> watch::Receiver<TrackArt> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let fetcher = match backend {
+            Backend::Spotify(spotify) => spotify.lock().await.album_art_fetcher(),
+            Backend::Mpris(_) => AlbumArtFetcher::default(),
+        };
+        while covers.changed().await.is_ok() {
+            let Some(cover) = covers.borrow_and_update().clone() else {
+                continue;
+            };
+            // covers are on a cdn and need no token, so no spotify_ready
+            let art = match fetcher.fetch(&cover.url).await {
+                Ok(art) => art,
+                Err(e) => {
+                    eprintln!("couldn't get the album art: {:?}", e);
+                    continue;
+                }
+            };
+            if tx.send(Some((cover, art))).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// the cover as a texture for the quad shader, bound as its own group. starts
+// out as one transparent pixel so the pipeline always has something bound
+pub struct CoverTexture {
+    // what the uploaded cover is for, None until one is
+    pub cover: Option<AlbumCover>,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl CoverTexture {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_for_cover"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("cover sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let placeholder = texture(device, queue, 1, 1, &[0; 4]);
+        let bind_group = bind_group(device, &bind_group_layout, &placeholder, &sampler);
+        CoverTexture {
+            cover: None,
+            sampler,
+            bind_group,
+            bind_group_layout,
+        }
+    }
+
+    // a new texture the size of the art, replacing the last one
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cover: &AlbumCover,
+        art: &AlbumArt,
+    ) {
+        let max = device.limits().max_texture_dimension_2d;
+        if art.width == 0 || art.height == 0 || art.width > max || art.height > max {
+            eprintln!("album art is {}x{}, can't draw it", art.width, art.height);
+            return;
+        }
+        let texture = texture(device, queue, art.width, art.height, &art.rgba);
+        self.bind_group = bind_group(device, &self.bind_group_layout, &texture, &self.sampler);
+        self.cover = Some(cover.clone());
+    }
+
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'_>, group: u32) {
+        render_pass.set_bind_group(group, &self.bind_group, &[]);
+    }
+}
+
+// covers are srgb, so they're sampled back to linear like every other color
+fn texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("cover texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
+
+fn bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("cover bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
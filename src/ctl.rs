@@ -0,0 +1,240 @@
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+#[cfg(unix)]
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::oneshot;
+
+use crate::sleep;
+use crate::theme::ThemeSelection;
+
+// what `kyomi ctl` can ask a running widget to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum CtlCommand {
+    Theme(ThemeSelection),
+    // a sentence about what's playing
+    Status,
+    // a spotify track or episode uri to play next
+    Queue(String),
+    // opens the quick search launcher, for binding to a desktop shortcut
+    Search,
+    // plays the playlist whose name is closest
+    PlayPlaylist(String),
+    // starts or stops the focus timer
+    Focus(bool),
+    // pauses after this long, or never with None
+    Sleep(Option<Duration>),
+}
+
+impl CtlCommand {
+    pub fn parse(words: &[&str]) -> Result<Self, anyhow::Error> {
+        match words {
+            ["theme", selection] => Ok(CtlCommand::Theme(ThemeSelection::parse(selection))),
+            ["status"] => Ok(CtlCommand::Status),
+            ["queue", uri] => Ok(CtlCommand::Queue((*uri).to_owned())),
+            ["search"] => Ok(CtlCommand::Search),
+            ["focus", "start"] => Ok(CtlCommand::Focus(true)),
+            ["focus", "stop"] => Ok(CtlCommand::Focus(false)),
+            ["sleep", "off"] => Ok(CtlCommand::Sleep(None)),
+            ["sleep", minutes] => match minutes
+                .parse::<f64>()
+                .ok()
+                .and_then(|minutes| Duration::try_from_secs_f64(minutes * 60.0).ok())
+            {
+                Some(after) if !after.is_zero() && after <= sleep::MAX_SLEEP => {
+                    Ok(CtlCommand::Sleep(Some(after)))
+                }
+                _ => Err(anyhow::anyhow!(
+                    "expected minutes, up to a day, got {:?}",
+                    minutes
+                )),
+            },
+            ["play-playlist", name @ ..] if !name.is_empty() => {
+                Ok(CtlCommand::PlayPlaylist(name.join(" ")))
+            }
+            _ => Err(anyhow::anyhow!("unknown command {:?}", words.join(" "))),
+        }
+    }
+}
+
+// a command with somewhere to send the one line answer
+pub type CtlRequest = (CtlCommand, oneshot::Sender<Result<String, String>>);
+
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("kyomi.sock")
+}
+
+// listens on a unix socket for one command per connection, as a line of
+// words, and answers with "ok ..." or "error ..."
+#[cfg(unix)]
+pub fn spawn_server() -> Result<UnboundedReceiver<CtlRequest>, anyhow::Error> {
+    let path = socket_path();
+    if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+        return Err(anyhow::anyhow!(
+            "another kyomi is already listening on {}",
+            path.display()
+        ));
+    }
+    // left behind by an instance that didn't shut down cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut line = String::new();
+                if tokio::io::BufReader::new(read)
+                    .read_line(&mut line)
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let answer = match CtlCommand::parse(&words) {
+                    Ok(command) => {
+                        let (answer_tx, answer_rx) = oneshot::channel();
+                        let _ = tx.send((command, answer_tx));
+                        answer_rx
+                            .await
+                            .unwrap_or_else(|_| Err(String::from("widget went away")))
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                let answer = match answer {
+                    Ok(answer) => format!("ok {}\n", answer),
+                    Err(e) => format!("error {}\n", e),
+                };
+                let _ = write.write_all(answer.as_bytes()).await;
+            });
+        }
+    });
+    Ok(rx)
+}
+
+// there's no unix socket to listen on
+#[cfg(not(unix))]
+pub fn spawn_server() -> Result<UnboundedReceiver<CtlRequest>, anyhow::Error> {
+    Err(anyhow::anyhow!("kyomi ctl only works on unix for now"))
+}
+
+#[cfg(unix)]
+fn send(line: &str) -> std::io::Result<String> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket_path())?;
+    writeln!(stream, "{}", line)?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer)
+}
+
+#[cfg(not(unix))]
+fn send(_line: &str) -> std::io::Result<String> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "kyomi ctl only works on unix for now",
+    ))
+}
+
+// `kyomi ctl theme next|<name>`, `kyomi ctl status`, `kyomi ctl queue <uri>`,
+// `kyomi ctl search`, `kyomi ctl play-playlist <name>`,
+// `kyomi ctl focus start|stop` or `kyomi ctl sleep <minutes>|off`, sent to
+// the running widget
+pub fn ctl_command(args: &[String]) {
+    if args.is_empty() {
+        println!("usage: kyomi ctl theme next|<name>");
+        println!("       kyomi ctl status");
+        println!("       kyomi ctl queue <spotify uri>");
+        println!("       kyomi ctl search");
+        println!("       kyomi ctl play-playlist <name>");
+        println!("       kyomi ctl focus start|stop");
+        println!("       kyomi ctl sleep <minutes>|off");
+        return;
+    }
+    match send(&args.join(" ")) {
+        Ok(answer) if answer.starts_with("ok") => print!("{}", answer),
+        Ok(answer) => {
+            print!("{}", answer);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("couldn't reach kyomi, is it running? {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// `kyomi status`, just the sentence so it reads well aloud and in scripts
+pub fn status_command() {
+    match send("status") {
+        Ok(answer) => match answer.strip_prefix("ok ") {
+            Some(sentence) => print!("{}", sentence),
+            None => {
+                print!("{}", answer);
+                std::process::exit(1);
+            }
+        },
+        Err(_) => {
+            eprintln!("kyomi isn't running");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[test]
+fn test_ctl_commands_parse() {
+    let parse = |line: &str| CtlCommand::parse(&line.split_whitespace().collect::<Vec<_>>());
+    assert_eq!(parse("status").unwrap(), CtlCommand::Status);
+    assert_eq!(parse("search").unwrap(), CtlCommand::Search);
+    assert_eq!(
+        parse("theme next").unwrap(),
+        CtlCommand::Theme(ThemeSelection::parse("next"))
+    );
+    assert_eq!(
+        parse("queue spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap(),
+        CtlCommand::Queue(String::from("spotify:track:4uLU6hMCjMI75M1A2tKUQC"))
+    );
+    assert_eq!(
+        parse("play-playlist Deep Focus").unwrap(),
+        CtlCommand::PlayPlaylist(String::from("Deep Focus"))
+    );
+    assert_eq!(parse("focus start").unwrap(), CtlCommand::Focus(true));
+    assert_eq!(parse("focus stop").unwrap(), CtlCommand::Focus(false));
+    assert_eq!(parse("sleep off").unwrap(), CtlCommand::Sleep(None));
+    assert_eq!(
+        parse("sleep 1.5").unwrap(),
+        CtlCommand::Sleep(Some(Duration::from_secs(90)))
+    );
+
+    assert_eq!(
+        parse("sleep 1440").unwrap(),
+        CtlCommand::Sleep(Some(sleep::MAX_SLEEP))
+    );
+
+    for bad in [
+        "",
+        "play-playlist",
+        "focus",
+        "sleep 0",
+        "sleep -5",
+        "sleep soon",
+        "sleep 1441",
+        "sleep inf",
+        "sleep 1e300",
+        "sleep NaN",
+        "theme",
+        "dance",
+    ] {
+        assert!(parse(bad).is_err(), "{:?} parsed", bad);
+    }
+}
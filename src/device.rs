@@ -0,0 +1,167 @@
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::player::{self, Backend};
+use crate::spotify::Device;
+
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_NAME_CHARS: usize = 12;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Devices {
+    // where playback is right now, None while nothing is active
+    pub active: Option<Device>,
+    // kept after playback stops, so it can be resumed there
+    pub last_seen: Option<Device>,
+}
+
+impl Devices {
+    // nothing is playing, but there's somewhere to resume
+    pub fn idle_device(&self) -> Option<&Device> {
+        match self.active {
+            Some(_) => None,
+            None => self.last_seen.as_ref(),
+        }
+    }
+
+    fn update(&mut self, active: Option<Device>) -> bool {
+        let before = self.clone();
+        if active.is_some() {
+            self.last_seen = active.clone();
+        }
+        self.active = active;
+        *self != before
+    }
+}
+
+// keeps track of which connect device spotify is actually playing on. only the
+// web api knows about devices, with MPRIS the channel just stays empty
+pub fn spawn_device_watcher(backend: Backend) -> watch::Receiver<Devices> {
+    let (tx, rx) = watch::channel(Devices::default());
+    if let Backend::Spotify(spotify) = backend {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DEVICE_POLL_INTERVAL);
+            while !tx.is_closed() {
+                interval.tick().await;
+                let res = async {
+                    player::spotify_ready(&spotify)
+                        .await?
+                        .get_player_state()
+                        .await
+                }
+                .await;
+                let active = match res {
+                    Ok(state) => state.and_then(|state| state.device),
+                    Err(e) => {
+                        eprintln!("couldn't get playback device: {:?}", e);
+                        continue;
+                    }
+                };
+                tx.send_if_modified(|devices| devices.update(active));
+            }
+        });
+    }
+    rx
+}
+
+// the one after the active device, round to the first again. restricted
+// devices without an id can't be moved to and are skipped. which is active
+// comes from the same response, the watcher's idea of it can be stale
+pub fn next_device(devices: &[Device]) -> Option<&Device> {
+    let usable: Vec<&Device> = devices
+        .iter()
+        .filter(|device| device.id.is_some())
+        .collect();
+    let active_id = devices
+        .iter()
+        .find(|device| device.is_active)
+        .and_then(|active| active.id.as_ref());
+    let next = match usable
+        .iter()
+        .position(|device| device.id.as_ref() == active_id)
+    {
+        Some(i) => usable[(i + 1) % usable.len()],
+        None => *usable.first()?,
+    };
+    // the active one is the only one
+    (next.id.as_ref() != active_id).then_some(next)
+}
+
+// short enough for the corner of the widget, e.g. "phone · Pixel 7"
+pub fn label(device: &Device) -> String {
+    let kind = match device.device_type.as_str() {
+        "Computer" => "pc",
+        "Smartphone" => "phone",
+        "Tablet" => "tablet",
+        "Speaker" | "AVR" | "CastAudio" | "AudioDongle" => "speaker",
+        "TV" | "STB" | "CastVideo" => "tv",
+        "Automobile" => "car",
+        "GameConsole" => "console",
+        _ => "device",
+    };
+    let mut name: String = device.name.chars().take(MAX_NAME_CHARS).collect();
+    if device.name.chars().count() > MAX_NAME_CHARS {
+        name.push('…');
+    }
+    format!("{} · {}", kind, name)
+}
+
+#[test]
+fn test_label_shortens_name() {
+    let device = Device {
+        id: None,
+        name: String::from("Living Room Speaker"),
+        device_type: String::from("CastAudio"),
+        volume_percent: None,
+        is_private_session: false,
+        is_active: false,
+    };
+    assert_eq!(label(&device), "speaker · Living Room …");
+}
+
+#[test]
+fn test_last_device_survives_stopping() {
+    let device = Device {
+        id: Some(String::from("abc")),
+        name: String::from("Desk"),
+        device_type: String::from("Computer"),
+        volume_percent: Some(50),
+        is_private_session: false,
+        is_active: true,
+    };
+    let mut devices = Devices::default();
+    assert!(devices.update(Some(device.clone())));
+    assert!(devices.idle_device().is_none());
+
+    assert!(devices.update(None));
+    assert_eq!(devices.idle_device(), Some(&device));
+    assert!(!devices.update(None));
+}
+
+#[test]
+fn test_next_device_wraps_around() {
+    let device = |id: Option<&str>, name: &str, is_active| Device {
+        id: id.map(String::from),
+        name: String::from(name),
+        device_type: String::from("Computer"),
+        volume_percent: None,
+        is_private_session: false,
+        is_active,
+    };
+    let active_at = |i| {
+        vec![
+            device(Some("a"), "Desk", i == 0),
+            device(None, "Restricted", false),
+            device(Some("b"), "Phone", i == 2),
+        ]
+    };
+    let devices = active_at(0);
+    assert_eq!(next_device(&devices), Some(&devices[2]));
+    let devices = active_at(2);
+    assert_eq!(next_device(&devices), Some(&devices[0]));
+    let devices = active_at(1);
+    assert_eq!(next_device(&devices), Some(&devices[0]));
+    // nowhere else to go
+    let devices = active_at(0);
+    assert_eq!(next_device(&devices[..1]), None);
+}
@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, Mutex};
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::musicbrainz;
+use crate::player;
+use crate::spotify::Spotify;
+use crate::{Source, SpotifyData};
+
+// what musicbrainz adds to a track from a source that sends little more than
+// titles, or what the full spotify album has over the one tracks come with.
+// everything is optional, neither always knows
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Enrichment {
+    // the credited artist as musicbrainz spells it
+    pub artist: Option<String>,
+    pub year: Option<String>,
+    pub label: Option<String>,
+    // only from spotify
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub copyright: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RecordingSearch {
+    recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize)]
+struct Recording {
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    releases: Vec<ReleaseSummary>,
+}
+
+#[derive(Deserialize)]
+struct ArtistCredit {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+}
+
+#[derive(Deserialize)]
+struct ReleaseSummary {
+    id: String,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    date: Option<String>,
+    #[serde(rename = "label-info", default)]
+    label_info: Vec<LabelInfo>,
+}
+
+#[derive(Deserialize)]
+struct LabelInfo {
+    label: Option<Label>,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+fn cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+        .join("musicbrainz.json")
+}
+
+fn cache_key(track: &SpotifyData) -> String {
+    format!(
+        "{}\t{}\t{}",
+        track.artist_name, track.track_name, track.album_name
+    )
+    .to_lowercase()
+}
+
+// answers survive restarts, musicbrainz data for a track hardly ever changes
+struct DiskCache {
+    entries: HashMap<String, Enrichment>,
+}
+
+impl DiskCache {
+    fn load() -> Self {
+        let entries = std::fs::read_to_string(cache_path())
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        DiskCache { entries }
+    }
+
+    fn insert(&mut self, key: String, enrichment: Enrichment) {
+        self.entries.insert(key, enrichment);
+        let path = cache_path();
+        let res = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, serde_json::to_string(&self.entries).unwrap()));
+        if let Err(e) = res {
+            eprintln!("couldn't save {}: {}", path.display(), e);
+        }
+    }
+}
+
+struct MusicBrainz {
+    client: reqwest::Client,
+}
+
+impl MusicBrainz {
+    async fn get<T: serde::de::DeserializeOwned>(
+        &mut self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, anyhow::Error> {
+        musicbrainz::wait_turn().await;
+        Ok(self
+            .client
+            .get(url)
+            .query(query)
+            .send_retrying(Endpoint::Art)
+            .await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+
+    // finds the recording, then the release it's on for the year and label
+    async fn lookup(&mut self, track: &SpotifyData) -> Result<Enrichment, anyhow::Error> {
+        let query = format!(
+            "recording:\"{}\" AND artist:\"{}\"",
+            musicbrainz::escape(&track.track_name),
+            musicbrainz::escape(&track.artist_name)
+        );
+        let search: RecordingSearch = self
+            .get(
+                "https://musicbrainz.org/ws/2/recording/",
+                &[("query", query.as_str()), ("fmt", "json"), ("limit", "1")],
+            )
+            .await?;
+        let recording = match search.recordings.into_iter().next() {
+            Some(recording) => recording,
+            None => return Ok(Enrichment::default()),
+        };
+
+        let artist = credited_artist(&recording.artist_credit);
+
+        // prefer the release the player says it's from
+        let album = track.album_name.to_lowercase();
+        let release = recording
+            .releases
+            .iter()
+            .find(|release| release.title.to_lowercase() == album)
+            .or(recording.releases.first());
+        let release: Option<Release> = match release {
+            Some(release) => Some(
+                self.get(
+                    &format!("https://musicbrainz.org/ws/2/release/{}", release.id),
+                    &[("inc", "labels"), ("fmt", "json")],
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        Ok(Enrichment {
+            artist,
+            year: release
+                .as_ref()
+                .and_then(|release| release.date.as_deref())
+                .and_then(|date| date.get(..4))
+                .map(String::from),
+            label: release.and_then(|release| {
+                release
+                    .label_info
+                    .into_iter()
+                    .find_map(|info| info.label)
+                    .map(|label| label.name)
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+// "Simon & Garfunkel", from each credited artist and what joins them
+fn credited_artist(credits: &[ArtistCredit]) -> Option<String> {
+    (!credits.is_empty()).then(|| {
+        credits
+            .iter()
+            .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+            .collect()
+    })
+}
+
+// the label, genres and copyright of a spotify album, which only the full
+// album object has
+async fn album_details(
+    spotify: &Arc<Mutex<Spotify>>,
+    album_id: &str,
+) -> Result<Enrichment, anyhow::Error> {
+    let album = player::spotify_ready(spotify)
+        .await?
+        .get_album(album_id)
+        .await?;
+    Ok(Enrichment {
+        copyright: album.copyright().map(String::from),
+        label: album.label.filter(|label| !label.is_empty()),
+        genres: album.genres,
+        ..Default::default()
+    })
+}
+
+// looks up each settled track and publishes what it found, alongside the
+// track it belongs to. tracks from a sparse source go to musicbrainz when
+// that's enabled, spotify tracks get their full album when given spotify
+pub fn spawn_enricher(
+    mut settled: broadcast::Receiver<SpotifyData>,
+    musicbrainz_enabled: bool,
+    spotify: Option<Arc<Mutex<Spotify>>>,
+) -> watch::Receiver<Option<(SpotifyData, Enrichment)>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let mut cache = DiskCache::load();
+        let mut musicbrainz = MusicBrainz {
+            client: musicbrainz::client(),
+        };
+        loop {
+            let track = match settled.recv().await {
+                Ok(track) => track,
+                // the newest track is still to come, that's the one to look up
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if track.track_name.is_empty() {
+                continue;
+            }
+            let enrichment = match (track.source, &spotify, &track.album_id) {
+                // the web api already has the rest
+                (Source::Spotify, Some(spotify), Some(album_id)) => {
+                    match album_details(spotify, album_id).await {
+                        Ok(enrichment) => enrichment,
+                        Err(e) => {
+                            eprintln!("album lookup failed: {:?}", e);
+                            continue;
+                        }
+                    }
+                }
+                (Source::Mpris, _, _) if musicbrainz_enabled => {
+                    let key = cache_key(&track);
+                    match cache.entries.get(&key) {
+                        Some(enrichment) => enrichment.clone(),
+                        None => match musicbrainz.lookup(&track).await {
+                            Ok(enrichment) => {
+                                cache.insert(key, enrichment.clone());
+                                enrichment
+                            }
+                            Err(e) => {
+                                eprintln!("musicbrainz lookup failed: {:?}", e);
+                                continue;
+                            }
+                        },
+                    }
+                }
+                _ => continue,
+            };
+            if tx.send(Some((track, enrichment))).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_recording_search_parsing() {
+    let raw = r#"{"recordings": [{
+        "artist-credit": [
+            {"name": "Simon", "joinphrase": " & "},
+            {"name": "Garfunkel"}
+        ],
+        "releases": [{"id": "r1", "title": "Bookends"}]
+    }]}"#;
+    let search: RecordingSearch = serde_json::from_str(raw).unwrap();
+    let recording = &search.recordings[0];
+    assert_eq!(
+        credited_artist(&recording.artist_credit).as_deref(),
+        Some("Simon & Garfunkel")
+    );
+    assert_eq!(recording.releases[0].id, "r1");
+}
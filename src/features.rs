@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, Mutex};
+
+use crate::player::{self, Backend};
+use crate::spotify::{AudioFeatures, Spotify, SpotifyError};
+use crate::SpotifyData;
+
+// the features of the settled track, with its id so they're never shown
+// against a different one. only spotify tracks have them
+pub type TrackFeatures = Option<(String, AudioFeatures)>;
+
+// looks up each settled track's audio features, once per track per run.
+// spotify answers 403 to apps that aren't allowed the endpoint, and then
+// it stops asking for the rest of the run
+pub fn spawn_audio_features(
+    backend: Backend,
+    mut settled: broadcast::Receiver<SpotifyData>,
+) -> watch::Receiver<TrackFeatures> {
+    let (tx, rx) = watch::channel(None);
+    let Backend::Spotify(spotify) = backend else {
+        return rx;
+    };
+    tokio::spawn(async move {
+        let mut cache: HashMap<String, AudioFeatures> = HashMap::new();
+        loop {
+            let track = match settled.recv().await {
+                Ok(track) => track,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Some(id) = track.track_id else {
+                continue;
+            };
+            let features = match cache.get(&id) {
+                Some(features) => *features,
+                None => match fetch_features(&spotify, &id).await {
+                    Ok(features) => {
+                        cache.insert(id.clone(), features);
+                        features
+                    }
+                    Err(SpotifyError::Network(e))
+                        if e.status() == Some(reqwest::StatusCode::FORBIDDEN) =>
+                    {
+                        eprintln!("spotify won't give this app audio features, turning them off");
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("couldn't get audio features: {:?}", e);
+                        continue;
+                    }
+                },
+            };
+            if tx.send(Some((id, features))).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+async fn fetch_features(
+    spotify: &Arc<Mutex<Spotify>>,
+    id: &str,
+) -> Result<AudioFeatures, SpotifyError> {
+    player::spotify_ready(spotify)
+        .await?
+        .get_audio_features(id)
+        .await
+}
+
+// how loud the track is mastered against a target, e.g.
+// "loudness -7.2 dB, +6.8 over -14 dB". spotify's loudness is its own
+// average in dB rather than LUFS, so both are shown as dB
+pub fn loudness_line(loudness: f32, target: f32) -> String {
+    let difference = loudness - target;
+    let relation = if difference >= 0.0 { "over" } else { "under" };
+    format!(
+        "loudness {:.1} dB, {:+.1} {} {} dB",
+        loudness, difference, relation, target
+    )
+}
+
+#[test]
+fn test_loudness_line() {
+    assert_eq!(
+        loudness_line(-7.25, -14.0),
+        "loudness -7.2 dB, +6.8 over -14 dB"
+    );
+    assert_eq!(
+        loudness_line(-16.0, -14.0),
+        "loudness -16.0 dB, -2.0 under -14 dB"
+    );
+}
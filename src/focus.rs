@@ -0,0 +1,92 @@
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct HyprlandWindow {
+    class: String,
+}
+
+async fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"
+fn parse_active_window(xprop: &str) -> Option<&str> {
+    let id = xprop.split('#').nth(1)?.trim();
+    (id != "0x0").then_some(id)
+}
+
+// "WM_CLASS(STRING) = "obs", "obs"", instance then class
+fn parse_wm_class(xprop: &str) -> Option<String> {
+    let (_, values) = xprop.split_once('=')?;
+    values
+        .split(',')
+        .next_back()
+        .map(|class| class.trim().trim_matches('"').to_owned())
+        .filter(|class| !class.is_empty())
+}
+
+// the class or process name of whatever window has focus, from whichever of
+// these the desktop answers to. None when there's no way to tell
+pub async fn active_app() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        let script = "tell application \"System Events\" to get name of first application process whose frontmost is true";
+        return output("osascript", &["-e", script])
+            .await
+            .map(|name| name.trim().to_owned());
+    }
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        let window = output("hyprctl", &["activewindow", "-j"]).await?;
+        return serde_json::from_str::<HyprlandWindow>(&window)
+            .ok()
+            .map(|window| window.class);
+    }
+    let root = output("xprop", &["-root", "_NET_ACTIVE_WINDOW"]).await?;
+    let id = parse_active_window(&root)?;
+    parse_wm_class(&output("xprop", &["-id", id, "WM_CLASS"]).await?)
+}
+
+fn should_hide(active: &str, apps: &[String]) -> bool {
+    apps.iter().any(|app| app.eq_ignore_ascii_case(active))
+}
+
+// whether one of the listed apps has focus, checked every second
+pub fn spawn_focus_watcher(apps: Vec<String>) -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let hide = active_app()
+                .await
+                .is_some_and(|active| should_hide(&active, &apps));
+            tx.send_if_modified(|hidden| std::mem::replace(hidden, hide) != hide);
+            if tx.is_closed() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_xprop_parsing() {
+    let root = "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n";
+    assert_eq!(parse_active_window(root), Some("0x3a00007"));
+    assert_eq!(
+        parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0"),
+        None
+    );
+
+    let class = parse_wm_class("WM_CLASS(STRING) = \"obs\", \"OBS\"\n").unwrap();
+    assert_eq!(class, "OBS");
+    assert!(should_hide(&class, &[String::from("obs")]));
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[cfg(feature = "bundled-font")]
+const BUNDLED: &[u8] = include_bytes!("../fonts/Fira_Code_v6.2/ttf/FiraCode-Light.ttf");
+
+// looked for when there's no bundled font and none configured
+#[cfg(not(feature = "bundled-font"))]
+const SYSTEM_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/liberation/LiberationMono-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Courier New.ttf",
+    r"C:\Windows\Fonts\consola.ttf",
+];
+
+// read once, the brush borrows it for as long as the app runs
+static LOADED: OnceLock<Vec<u8>> = OnceLock::new();
+// the same for the fallback font, None when there isn't one
+static FALLBACK: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+
+// fontconfig's pick for a pattern, where there's fontconfig
+fn fc_match(pattern: &str) -> Option<PathBuf> {
+    let output = std::process::Command::new("fc-match")
+        .args(["--format=%{file}", pattern])
+        .output()
+        .ok()?;
+    let path = PathBuf::from(String::from_utf8(output.stdout).ok()?);
+    path.is_file().then_some(path)
+}
+
+#[cfg(not(feature = "bundled-font"))]
+fn system_font() -> Option<PathBuf> {
+    fc_match("monospace").or_else(|| {
+        SYSTEM_FONTS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file())
+    })
+}
+
+#[cfg(feature = "bundled-font")]
+fn fallback() -> Result<&'static [u8], anyhow::Error> {
+    Ok(BUNDLED)
+}
+
+// lite builds leave the font out to stay small, so use the system's
+#[cfg(not(feature = "bundled-font"))]
+fn fallback() -> Result<&'static [u8], anyhow::Error> {
+    let path = system_font().ok_or_else(|| {
+        anyhow::anyhow!("no font found, set font in the config to a .ttf or .otf file")
+    })?;
+    read(&path)
+}
+
+fn read(path: &Path) -> Result<&'static [u8], anyhow::Error> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("couldn't read font {}: {}", path.display(), e))?;
+    Ok(LOADED.get_or_init(|| bytes))
+}
+
+// the configured font, otherwise the bundled Fira Code, otherwise a system
+// monospace font
+pub fn load(configured: Option<&Path>) -> Result<&'static [u8], anyhow::Error> {
+    if let Some(loaded) = LOADED.get() {
+        return Ok(loaded);
+    }
+    match configured {
+        Some(path) => read(path),
+        None => fallback(),
+    }
+}
+
+// a font for what the main one can't draw: the configured fallback,
+// otherwise fontconfig's pick for the metadata language, e.g. a CJK font for
+// "ja". None without either, or if it couldn't be read
+pub fn load_fallback(configured: Option<&Path>, language: Option<&str>) -> Option<&'static [u8]> {
+    FALLBACK
+        .get_or_init(|| {
+            let path = match configured {
+                Some(path) => path.to_path_buf(),
+                None => fc_match(&format!(":lang={}", language?.to_lowercase()))?,
+            };
+            std::fs::read(&path)
+                .map_err(|e| eprintln!("couldn't read font {}: {}", path.display(), e))
+                .ok()
+        })
+        .as_deref()
+}
+
+// splits text into runs drawn with the same font, by the index of the first
+// font that has each character, 0 when none do. spaces go with the run
+// they're in, so words in one script aren't broken up
+pub fn runs(text: &str, has: impl Fn(usize, char) -> bool, fonts: usize) -> Vec<(&str, usize)> {
+    let mut runs: Vec<(&str, usize)> = Vec::new();
+    let mut start = 0;
+    let mut current: Option<usize> = None;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let font = (0..fonts).find(|&font| has(font, c)).unwrap_or(0);
+        if let Some(current) = current.filter(|&current| current != font) {
+            runs.push((&text[start..i], current));
+            start = i;
+        }
+        current = Some(font);
+    }
+    if start < text.len() {
+        runs.push((&text[start..], current.unwrap_or(0)));
+    }
+    runs
+}
+
+#[test]
+fn test_configured_font_must_exist() {
+    assert!(load(Some(Path::new("/nonexistent/font.ttf"))).is_err());
+    #[cfg(feature = "bundled-font")]
+    assert_eq!(load(None).unwrap(), BUNDLED);
+}
+
+#[test]
+fn test_runs_split_by_font() {
+    // the first font only has ascii
+    let has = |font: usize, c: char| font == 1 || c.is_ascii();
+    assert_eq!(runs("Hello", has, 2), [("Hello", 0)]);
+    assert_eq!(
+        runs("宇多田ヒカル - First Love", has, 2),
+        [("宇多田ヒカル ", 1), ("- First Love", 0)]
+    );
+    assert_eq!(
+        runs("a 日本 b", has, 2),
+        [("a ", 0), ("日本 ", 1), ("b", 0)]
+    );
+    // with nothing to fall back to it's all the first font
+    assert_eq!(runs("a 日本", has, 1), [("a 日本", 0)]);
+    assert_eq!(runs("", has, 2), []);
+}
@@ -0,0 +1,101 @@
+// skim style scoring: every character of the pattern has to turn up in the
+// candidate in order, ignoring case. matches earn more at the start of a word
+// and right after the previous match, and lose a little for each character
+// skipped over
+const MATCH: i64 = 16;
+const WORD_START: i64 = 8;
+const CONSECUTIVE: i64 = 12;
+const FIRST_CHAR: i64 = 8;
+const GAP: i64 = 1;
+
+fn is_word_start(previous: Option<char>, c: char) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            !previous.is_alphanumeric() && c.is_alphanumeric()
+                || previous.is_lowercase() && c.is_uppercase()
+        }
+    }
+}
+
+// None when the pattern doesn't match at all. spaces in the pattern are
+// ignored, so words can be typed in any spacing
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let mut pattern = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+    for (i, c) in candidate.chars().enumerate() {
+        let Some(wanted) = pattern.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(*wanted)) {
+            score += MATCH;
+            if is_word_start(previous, c) {
+                score += WORD_START;
+            }
+            match last_match {
+                Some(last) if last + 1 == i => score += CONSECUTIVE,
+                Some(last) => score -= GAP * (i - last - 1) as i64,
+                None if i == 0 => score += FIRST_CHAR,
+                None => score -= GAP * i as i64,
+            }
+            last_match = Some(i);
+            pattern.next();
+        }
+        previous = Some(c);
+    }
+    pattern.peek().is_none().then_some(score)
+}
+
+// the best match, the earliest of any tied
+pub fn best<'a, T>(pattern: &str, items: &'a [T], key: impl Fn(&T) -> &str) -> Option<&'a T> {
+    items
+        .iter()
+        .filter_map(|item| Some((score(pattern, key(item))?, item)))
+        .fold(None, |best: Option<(i64, &T)>, (score, item)| match best {
+            Some((best_score, _)) if best_score >= score => best,
+            _ => Some((score, item)),
+        })
+        .map(|(_, item)| item)
+}
+
+// best first. ties keep their order, and anything that doesn't match goes
+// to the end
+pub fn rank<T>(pattern: &str, items: &mut [T], key: impl Fn(&T) -> &str) {
+    items.sort_by_cached_key(|item| std::cmp::Reverse(score(pattern, key(item))));
+}
+
+#[test]
+fn test_fuzzy_ranking() {
+    assert_eq!(score("xyz", "Favorites"), None);
+    assert_eq!(score("", "anything"), Some(0));
+    assert!(score("fav", "Favorites").is_some());
+    assert!(score("FAV", "favorites").is_some());
+
+    // prefixes beat matches further in, word starts beat the middle of words,
+    // runs beat scattered letters
+    assert!(score("fav", "Favorites") > score("fav", "My Favorites"));
+    assert!(score("mf", "My Favorites") > score("mf", "Mellow Coffee"));
+    assert!(score("chill", "Chill Vibes") > score("chill", "Coach Hill"));
+    assert!(score("dw", "Discover Weekly") > score("dw", "Dawn"));
+
+    let playlists = ["Release Radar", "Discover Weekly", "Daily Mix 1", "Dance"];
+    assert_eq!(
+        best("discover", &playlists, |name| name),
+        Some(&"Discover Weekly")
+    );
+    assert_eq!(best("dm1", &playlists, |name| name), Some(&"Daily Mix 1"));
+    assert_eq!(best("zzz", &playlists, |name| name), None);
+
+    let mut ranked = playlists;
+    rank("da", &mut ranked, |name| name);
+    assert_eq!(
+        ranked,
+        ["Daily Mix 1", "Dance", "Release Radar", "Discover Weekly"]
+    );
+}
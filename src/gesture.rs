@@ -0,0 +1,417 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use winit::event::MouseScrollDelta;
+
+use crate::input::UiAction;
+use crate::player::PlayerCommand;
+
+const DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(300);
+const LONG_PRESS: Duration = Duration::from_millis(600);
+// how far the cursor can wander, in logical pixels, before a press is a drag
+const DRAG_THRESHOLD: f32 = 8.0;
+// the widget's width is about 50 seconds
+const SEEK_MS_PER_PIXEL: f32 = 200.0;
+// dragging up is louder
+const VOLUME_PIXELS_PER_PERCENT: f32 = 2.0;
+// fingers are less precise than a cursor, so a tap can wander further
+const SWIPE_THRESHOLD: f32 = 24.0;
+// each notch of a mouse wheel. touchpads scroll in pixels, this many to a
+// notch
+const WHEEL_STEP_PERCENT: f32 = 5.0;
+const WHEEL_PIXELS_PER_STEP: f32 = 40.0;
+// how long the wheel rests before the volume it came to is sent
+const WHEEL_SETTLE: Duration = Duration::from_millis(250);
+// the volume last sent is trusted over the device's for this long, since the
+// device watcher only catches up every so often
+const WHEEL_REMEMBER: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    Click,
+    DoubleClick,
+    LongPress,
+    // how far the drag went along its main axis, in logical pixels
+    HorizontalDrag(f32),
+    VerticalDrag(f32),
+}
+
+// what a click, double click or long press does
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapAction {
+    Nothing,
+    PlayPause,
+    Next,
+    Previous,
+    ToggleShuffle,
+    CycleRepeat,
+    ToggleExpanded,
+    NextPage,
+    Hide,
+    ToggleLike,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DragAction {
+    Nothing,
+    Seek,
+    Volume,
+}
+
+// set in the config under [gestures], e.g.
+//   click = "play_pause"
+//   long_press = "toggle_expanded"
+//   vertical_drag = "volume"
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Gestures {
+    pub click: TapAction,
+    pub double_click: TapAction,
+    pub long_press: TapAction,
+    pub horizontal_drag: DragAction,
+    pub vertical_drag: DragAction,
+}
+
+impl Default for Gestures {
+    fn default() -> Self {
+        Gestures {
+            click: TapAction::PlayPause,
+            double_click: TapAction::Next,
+            long_press: TapAction::ToggleExpanded,
+            horizontal_drag: DragAction::Seek,
+            vertical_drag: DragAction::Volume,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Command(PlayerCommand),
+    Ui(UiAction),
+}
+
+impl TapAction {
+    fn outcome(self) -> Option<Outcome> {
+        let command = |command| Some(Outcome::Command(command));
+        let ui = |action| Some(Outcome::Ui(action));
+        match self {
+            TapAction::Nothing => None,
+            TapAction::PlayPause => command(PlayerCommand::PlayPause),
+            TapAction::Next => command(PlayerCommand::Next),
+            TapAction::Previous => command(PlayerCommand::Previous),
+            TapAction::ToggleShuffle => command(PlayerCommand::ToggleShuffle),
+            TapAction::CycleRepeat => command(PlayerCommand::CycleRepeat),
+            TapAction::ToggleExpanded => ui(UiAction::ToggleExpanded),
+            TapAction::NextPage => ui(UiAction::NextPage),
+            TapAction::Hide => ui(UiAction::Hide),
+            TapAction::ToggleLike => ui(UiAction::ToggleLike),
+        }
+    }
+}
+
+impl DragAction {
+    fn outcome(self, distance: f32) -> Option<Outcome> {
+        match self {
+            DragAction::Nothing => None,
+            DragAction::Seek => Some(Outcome::Command(PlayerCommand::SeekBy(
+                (distance * SEEK_MS_PER_PIXEL) as i64,
+            ))),
+            DragAction::Volume => {
+                let percent = (-distance / VOLUME_PIXELS_PER_PERCENT) as i8;
+                (percent != 0).then_some(Outcome::Command(PlayerCommand::VolumeBy(percent)))
+            }
+        }
+    }
+}
+
+impl Gestures {
+    pub fn outcome(&self, gesture: Gesture) -> Option<Outcome> {
+        match gesture {
+            Gesture::Click => self.click.outcome(),
+            Gesture::DoubleClick => self.double_click.outcome(),
+            Gesture::LongPress => self.long_press.outcome(),
+            Gesture::HorizontalDrag(distance) => self.horizontal_drag.outcome(distance),
+            Gesture::VerticalDrag(distance) => self.vertical_drag.outcome(distance),
+        }
+    }
+}
+
+// a click on the progress bar, at a fraction of its width, moves to that
+// point in the track
+pub fn seek_to(fraction: f32, played_ms: u64, duration_ms: u64) -> PlayerCommand {
+    let target_ms = (fraction.clamp(0.0, 1.0) * duration_ms as f32) as i64;
+    PlayerCommand::SeekBy(target_ms - played_ms as i64)
+}
+
+struct Press {
+    at: Instant,
+    from: (f32, f32),
+    dragged: bool,
+    // the long press already fired, releasing does nothing
+    long_pressed: bool,
+}
+
+// turns left button presses and cursor movement into gestures. a click waits
+// out the double click window before it counts, so double clicking never
+// also clicks
+#[derive(Default)]
+pub struct Recognizer {
+    press: Option<Press>,
+    pending_click: Option<Instant>,
+}
+
+impl Recognizer {
+    pub fn press(&mut self, position: (f32, f32), now: Instant) {
+        self.press = Some(Press {
+            at: now,
+            from: position,
+            dragged: false,
+            long_pressed: false,
+        });
+    }
+
+    pub fn moved(&mut self, position: (f32, f32)) {
+        if let Some(press) = self.press.as_mut() {
+            let (dx, dy) = (position.0 - press.from.0, position.1 - press.from.1);
+            if dx.hypot(dy) > DRAG_THRESHOLD {
+                press.dragged = true;
+            }
+        }
+    }
+
+    pub fn release(&mut self, position: (f32, f32), now: Instant) -> Option<Gesture> {
+        let press = self.press.take()?;
+        if press.long_pressed {
+            return None;
+        }
+        if press.dragged {
+            let (dx, dy) = (position.0 - press.from.0, position.1 - press.from.1);
+            return Some(if dx.abs() >= dy.abs() {
+                Gesture::HorizontalDrag(dx)
+            } else {
+                Gesture::VerticalDrag(dy)
+            });
+        }
+        match self.pending_click.take() {
+            Some(clicked) if now.duration_since(clicked) < DOUBLE_CLICK_WINDOW => {
+                Some(Gesture::DoubleClick)
+            }
+            _ => {
+                self.pending_click = Some(now);
+                None
+            }
+        }
+    }
+
+    // the gestures that fire on time rather than on input: a long press while
+    // still held, and a click once no second one came
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        if let Some(press) = self.press.as_mut() {
+            if !press.dragged && !press.long_pressed && now.duration_since(press.at) >= LONG_PRESS {
+                press.long_pressed = true;
+                self.pending_click = None;
+                return Some(Gesture::LongPress);
+            }
+        }
+        match self.pending_click {
+            Some(clicked)
+                if self.press.is_none() && now.duration_since(clicked) >= DOUBLE_CLICK_WINDOW =>
+            {
+                self.pending_click = None;
+                Some(Gesture::Click)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Touch {
+    Tap,
+    // the direction the finger moved
+    SwipeLeft,
+    SwipeRight,
+    SwipeUp,
+    SwipeDown,
+}
+
+// follows the first finger down, any others are ignored
+#[derive(Default)]
+pub struct TouchTracker {
+    finger: Option<(u64, (f32, f32))>,
+}
+
+impl TouchTracker {
+    pub fn start(&mut self, id: u64, position: (f32, f32)) {
+        if self.finger.is_none() {
+            self.finger = Some((id, position));
+        }
+    }
+
+    pub fn end(&mut self, id: u64, position: (f32, f32)) -> Option<Touch> {
+        let (_, from) = self.finger.take_if(|(finger, _)| *finger == id)?;
+        let (dx, dy) = (position.0 - from.0, position.1 - from.1);
+        Some(if dx.hypot(dy) <= SWIPE_THRESHOLD {
+            Touch::Tap
+        } else if dx.abs() >= dy.abs() {
+            if dx < 0.0 {
+                Touch::SwipeLeft
+            } else {
+                Touch::SwipeRight
+            }
+        } else if dy < 0.0 {
+            Touch::SwipeUp
+        } else {
+            Touch::SwipeDown
+        })
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        self.finger.take_if(|(finger, _)| *finger == id);
+    }
+}
+
+// scrolling up is louder. a whole scroll, touchpad deltas and all, is
+// added up and sent as the one volume once the wheel rests, rather than a
+// request per notch. without a volume to start from it goes as a change
+#[derive(Default)]
+pub struct WheelVolume {
+    pending: f32,
+    // the volume when this scroll started, if it's known
+    start: Option<u8>,
+    last_scroll: Option<Instant>,
+    last_sent: Option<(u8, Instant)>,
+}
+
+impl WheelVolume {
+    // `volume` is the device's as last heard
+    pub fn scrolled(&mut self, delta: MouseScrollDelta, volume: Option<u8>, now: Instant) {
+        let steps = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / WHEEL_PIXELS_PER_STEP,
+        };
+        if self.last_scroll.is_none() {
+            let sent = self
+                .last_sent
+                .filter(|(_, at)| now.duration_since(*at) < WHEEL_REMEMBER)
+                .map(|(sent, _)| sent);
+            self.start = sent.or(volume);
+            self.pending = 0.0;
+        }
+        self.pending += steps * WHEEL_STEP_PERCENT;
+        self.last_scroll = Some(now);
+    }
+
+    // the one command for the whole scroll, once the wheel has rested
+    pub fn poll(&mut self, now: Instant) -> Option<PlayerCommand> {
+        let last_scroll = self.last_scroll?;
+        if now.duration_since(last_scroll) < WHEEL_SETTLE {
+            return None;
+        }
+        self.last_scroll = None;
+        let percent = std::mem::take(&mut self.pending)
+            .trunc()
+            .clamp(-100.0, 100.0);
+        if percent == 0.0 {
+            return None;
+        }
+        match self.start.take() {
+            Some(start) => {
+                let volume = (start as f32 + percent).clamp(0.0, 100.0) as u8;
+                self.last_sent = Some((volume, now));
+                Some(PlayerCommand::SetVolume(volume))
+            }
+            None => Some(PlayerCommand::VolumeBy(percent as i8)),
+        }
+    }
+}
+
+#[test]
+fn test_recognizes_clicks_and_drags() {
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+    let mut recognizer = Recognizer::default();
+
+    recognizer.press((10.0, 10.0), at(0));
+    assert_eq!(recognizer.release((10.0, 10.0), at(50)), None);
+    assert_eq!(recognizer.poll(at(100)), None);
+    assert_eq!(recognizer.poll(at(400)), Some(Gesture::Click));
+
+    recognizer.press((10.0, 10.0), at(1000));
+    recognizer.release((10.0, 10.0), at(1050));
+    recognizer.press((10.0, 10.0), at(1100));
+    assert_eq!(
+        recognizer.release((10.0, 10.0), at(1150)),
+        Some(Gesture::DoubleClick)
+    );
+    assert_eq!(recognizer.poll(at(2000)), None);
+
+    recognizer.press((10.0, 10.0), at(3000));
+    assert_eq!(recognizer.poll(at(3700)), Some(Gesture::LongPress));
+    assert_eq!(recognizer.release((10.0, 10.0), at(3800)), None);
+
+    recognizer.press((10.0, 10.0), at(4000));
+    recognizer.moved((60.0, 14.0));
+    assert_eq!(recognizer.poll(at(5000)), None);
+    assert_eq!(
+        recognizer.release((60.0, 14.0), at(5000)),
+        Some(Gesture::HorizontalDrag(50.0))
+    );
+}
+
+#[test]
+fn test_touch_swipes() {
+    let mut touches = TouchTracker::default();
+    touches.start(1, (100.0, 50.0));
+    // a second finger doesn't take over
+    touches.start(2, (10.0, 10.0));
+    assert_eq!(touches.end(2, (200.0, 10.0)), None);
+    assert_eq!(touches.end(1, (40.0, 60.0)), Some(Touch::SwipeLeft));
+
+    touches.start(3, (100.0, 50.0));
+    assert_eq!(touches.end(3, (105.0, 45.0)), Some(Touch::Tap));
+    touches.start(4, (100.0, 80.0));
+    assert_eq!(touches.end(4, (100.0, 10.0)), Some(Touch::SwipeUp));
+}
+
+#[test]
+fn test_wheel_volume() {
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+    let notches = |y| MouseScrollDelta::LineDelta(0.0, y);
+    let mut wheel = WheelVolume::default();
+
+    // a burst of notches is one volume, sent once the wheel rests
+    wheel.scrolled(notches(-1.0), Some(50), at(0));
+    wheel.scrolled(notches(-1.0), Some(50), at(100));
+    assert_eq!(wheel.poll(at(200)), None);
+    assert_eq!(wheel.poll(at(400)), Some(PlayerCommand::SetVolume(40)));
+    assert_eq!(wheel.poll(at(500)), None);
+
+    // the next scroll goes on from there, not the device's stale volume
+    wheel.scrolled(notches(3.0), Some(50), at(1000));
+    assert_eq!(wheel.poll(at(1300)), Some(PlayerCommand::SetVolume(55)));
+    wheel.scrolled(notches(30.0), Some(50), at(2000));
+    assert_eq!(wheel.poll(at(2300)), Some(PlayerCommand::SetVolume(100)));
+
+    // touchpad pixels add up to whole percents, and without a volume to
+    // start from it's sent as a change
+    let pixels = |y| MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(0.0, y));
+    let mut wheel = WheelVolume::default();
+    wheel.scrolled(pixels(4.0), None, at(0));
+    assert_eq!(wheel.poll(at(300)), None);
+    wheel.scrolled(pixels(4.0), None, at(400));
+    wheel.scrolled(pixels(6.0), None, at(450));
+    assert_eq!(wheel.poll(at(700)), Some(PlayerCommand::VolumeBy(1)));
+}
+
+#[test]
+fn test_seek_on_progress_bar() {
+    assert_eq!(seek_to(0.5, 30_000, 200_000), PlayerCommand::SeekBy(70_000));
+    assert_eq!(
+        seek_to(0.1, 30_000, 200_000),
+        PlayerCommand::SeekBy(-10_000)
+    );
+    assert_eq!(seek_to(1.2, 0, 200_000), PlayerCommand::SeekBy(200_000));
+}
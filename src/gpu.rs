@@ -0,0 +1,127 @@
+use std::sync::Arc;
+use wgpu::{Adapter, Instance, Surface};
+use winit::window::Window;
+
+// tried in order. GL is last, for boards like the raspberry pi whose only
+// driver is GLES. WGPU_BACKEND=gl (or vulkan, metal, dx12) picks one instead
+fn backends() -> Vec<wgpu::Backends> {
+    match wgpu::util::backend_bits_from_env() {
+        Some(backends) => vec![backends],
+        None => vec![wgpu::Backends::PRIMARY, wgpu::Backends::GL],
+    }
+}
+
+fn instance(backends: wgpu::Backends) -> Instance {
+    Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        flags: wgpu::InstanceFlags::empty(),
+        ..Default::default()
+    })
+}
+
+fn request_adapter(instance: &Instance, surface: Option<&Surface<'_>>) -> Option<Adapter> {
+    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: surface,
+        force_fallback_adapter: false,
+    }))
+}
+
+// the first backend with an adapter that can draw to the window
+pub fn connect(window: Arc<Window>) -> Option<(Instance, Surface<'static>, Adapter)> {
+    backends().into_iter().find_map(|backends| {
+        let instance = instance(backends);
+        let surface = instance.create_surface(window.clone()).ok()?;
+        let adapter = request_adapter(&instance, Some(&surface))?;
+        Some((instance, surface, adapter))
+    })
+}
+
+// the same, for drawing offscreen
+pub fn connect_offscreen() -> Option<(Instance, Adapter)> {
+    backends().into_iter().find_map(|backends| {
+        let instance = instance(backends);
+        let adapter = request_adapter(&instance, None)?;
+        Some((instance, adapter))
+    })
+}
+
+// the widget needs nothing past what GLES 3 offers, so that's all that's asked
+// for, except textures can be as big as the adapter allows
+pub fn required_limits(adapter: &Adapter) -> wgpu::Limits {
+    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+}
+
+// whether text can be drawn through a view of the frame in another format,
+// see App::text_format. GLES can't
+pub fn supports_text_views(adapter: &Adapter, surface: bool) -> bool {
+    let flag = if surface {
+        wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS
+    } else {
+        wgpu::DownlevelFlags::VIEW_FORMATS
+    };
+    adapter.get_downlevel_capabilities().flags.contains(flag)
+}
+
+// the preferred format if the surface has it, otherwise the first srgb one
+pub fn surface_format(
+    capabilities: &wgpu::SurfaceCapabilities,
+    preferred: wgpu::TextureFormat,
+) -> wgpu::TextureFormat {
+    let formats = &capabilities.formats;
+    if formats.contains(&preferred) {
+        return preferred;
+    }
+    formats
+        .iter()
+        .copied()
+        .find(wgpu::TextureFormat::is_srgb)
+        .or(formats.first().copied())
+        .unwrap_or(preferred)
+}
+
+// the widget is drawn premultiplied over the desktop, where that's possible
+pub fn alpha_mode(capabilities: &wgpu::SurfaceCapabilities) -> wgpu::CompositeAlphaMode {
+    [
+        wgpu::CompositeAlphaMode::PostMultiplied,
+        wgpu::CompositeAlphaMode::PreMultiplied,
+    ]
+    .into_iter()
+    .find(|mode| capabilities.alpha_modes.contains(mode))
+    .or(capabilities.alpha_modes.first().copied())
+    .unwrap_or(wgpu::CompositeAlphaMode::Auto)
+}
+
+// surfaces can't be bigger than the largest texture, which is small on older
+// boards
+pub fn clamp_size(width: u32, height: u32, limits: &wgpu::Limits) -> (u32, u32) {
+    let max = limits.max_texture_dimension_2d;
+    (width.min(max), height.min(max))
+}
+
+#[test]
+fn test_surface_format_falls_back_to_srgb() {
+    let capabilities = |formats: Vec<wgpu::TextureFormat>| wgpu::SurfaceCapabilities {
+        formats,
+        ..Default::default()
+    };
+    let preferred = wgpu::TextureFormat::Bgra8UnormSrgb;
+    assert_eq!(
+        surface_format(
+            &capabilities(vec![wgpu::TextureFormat::Bgra8Unorm, preferred]),
+            preferred
+        ),
+        preferred
+    );
+    // what GLES usually offers
+    assert_eq!(
+        surface_format(
+            &capabilities(vec![
+                wgpu::TextureFormat::Rgba8Unorm,
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            ]),
+            preferred
+        ),
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    );
+}
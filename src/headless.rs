@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::privacy::Privacy;
+use crate::template::{self, Template};
+use crate::{Source, SpotifyData};
+
+// bumped only when a field changes meaning or goes away. new fields can be
+// added without it, so scripts should ignore keys they don't know
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+// how `--headless` prints each change, picked with `--format`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Format {
+    // "artist - track"
+    Plain,
+    Json,
+    // one line per update for a waybar custom module with return-type json
+    Waybar,
+    // e.g. template:{{artist | upper}} / {{track}}
+    Template(Template),
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Self, anyhow::Error> {
+        match format {
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            "waybar" => Ok(Format::Waybar),
+            _ => match format.strip_prefix("template:") {
+                Some(template) => Ok(Format::Template(Template::parse(template)?)),
+                None => Err(anyhow::anyhow!(
+                    "unknown format {:?}, expected json, plain, waybar or template:<tmpl>",
+                    format
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    schema_version: u32,
+    // "playing", "paused", "stopped" or "private"
+    status: &'static str,
+    track: Option<&'a str>,
+    artist: Option<&'a str>,
+    album: Option<&'a str>,
+    art_url: Option<&'a str>,
+    progress_ms: Option<u64>,
+    duration_ms: Option<u64>,
+    // "spotify" or "mpris"
+    source: Option<&'static str>,
+    track_id: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct WaybarOutput {
+    text: String,
+    tooltip: String,
+    // the status, for styling
+    class: &'static str,
+    percentage: u32,
+}
+
+fn status(data: Option<&SpotifyData>, private: bool) -> &'static str {
+    match data {
+        _ if private => "private",
+        Some(data) if !data.track_name.is_empty() && data.is_playing => "playing",
+        Some(data) if !data.track_name.is_empty() => "paused",
+        _ => "stopped",
+    }
+}
+
+// one line of output for the latest poll. in privacy mode only the redact
+// message gets out, whatever the format
+pub fn render(
+    format: &Format,
+    data: Option<&SpotifyData>,
+    private: bool,
+    redact_message: &str,
+) -> String {
+    let status = status(data, private);
+    let data = data.filter(|_| status != "private" && status != "stopped");
+    let text = match data {
+        Some(data) => format!("{} - {}", data.artist_name, data.track_name),
+        None if private => redact_message.to_owned(),
+        None => String::new(),
+    };
+    match format {
+        Format::Plain => text,
+        Format::Json => serde_json::to_string(&JsonOutput {
+            schema_version: JSON_SCHEMA_VERSION,
+            status,
+            track: data.map(|data| data.track_name.as_str()),
+            artist: data.map(|data| data.artist_name.as_str()),
+            album: data.map(|data| data.album_name.as_str()),
+            art_url: data
+                .map(|data| data.album_art_url.as_str())
+                .filter(|url| !url.is_empty()),
+            progress_ms: data.and_then(|data| data.progress_ms),
+            duration_ms: data.and_then(|data| data.duration_ms),
+            source: data.map(|data| match data.source {
+                Source::Spotify => "spotify",
+                Source::Mpris => "mpris",
+            }),
+            track_id: data.and_then(|data| data.track_id.as_deref()),
+        })
+        .unwrap(),
+        Format::Waybar => serde_json::to_string(&WaybarOutput {
+            tooltip: data
+                .map(|data| {
+                    format!(
+                        "{}\n{}\n{}",
+                        data.track_name, data.artist_name, data.album_name
+                    )
+                })
+                .unwrap_or_default(),
+            text,
+            class: status,
+            percentage: data
+                .and_then(|data| data.progress(std::time::Duration::ZERO))
+                .map_or(0, |progress| (progress * 100.0) as u32),
+        })
+        .unwrap(),
+        Format::Template(template) => match data {
+            Some(data) => template.render(&template::track_context(data, status)),
+            None => text,
+        },
+    }
+}
+
+// prints a line whenever the poll result changes, for status bars and scripts
+pub async fn run(
+    format: Format,
+    mut polls: watch::Receiver<Option<SpotifyData>>,
+    privacy: Arc<Privacy>,
+    redact_message: String,
+) {
+    let mut last_line = None;
+    while polls.changed().await.is_ok() {
+        let data = polls.borrow_and_update().clone();
+        let line = render(&format, data.as_ref(), privacy.is_active(), &redact_message);
+        // progress alone changing isn't worth a line in plain text
+        if last_line.as_ref() != Some(&line) {
+            println!("{}", line);
+            last_line = Some(line);
+        }
+    }
+}
+
+#[test]
+fn test_headless_formats() {
+    let data = SpotifyData {
+        track_name: String::from("Karma Police"),
+        artist_name: String::from("Radiohead"),
+        album_name: String::from("OK Computer"),
+        is_playing: true,
+        progress_ms: Some(60_000),
+        duration_ms: Some(240_000),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render(&Format::Plain, Some(&data), false, ""),
+        "Radiohead - Karma Police"
+    );
+    assert_eq!(
+        render(&Format::Json, Some(&data), false, ""),
+        r#"{"schema_version":1,"status":"playing","track":"Karma Police","artist":"Radiohead","album":"OK Computer","art_url":null,"progress_ms":60000,"duration_ms":240000,"source":"spotify","track_id":null}"#
+    );
+    assert_eq!(
+        render(&Format::Json, Some(&data), true, "hidden"),
+        r#"{"schema_version":1,"status":"private","track":null,"artist":null,"album":null,"art_url":null,"progress_ms":null,"duration_ms":null,"source":null,"track_id":null}"#
+    );
+    let template = Format::parse("template:{{track}} ({{ progress }}/{{duration}})").unwrap();
+    assert_eq!(
+        render(&template, Some(&data), false, ""),
+        "Karma Police (1:00/4:00)"
+    );
+    assert!(Format::parse("xml").is_err());
+}
+
+#[test]
+fn test_headless_hides_explicit_titles() {
+    // as the aggregator publishes it with hide_explicit on
+    let data = SpotifyData {
+        track_name: String::from("Hidden"),
+        artist_name: String::from("Band"),
+        explicit: true,
+        is_playing: true,
+        ..Default::default()
+    }
+    .hiding_explicit(true);
+    for format in ["plain", "json", "waybar", "template:{{track}}"] {
+        let line = render(&Format::parse(format).unwrap(), Some(&data), false, "");
+        assert!(!line.contains("Hidden"), "{}: {}", format, line);
+        assert!(line.contains("explicit track"), "{}: {}", format, line);
+    }
+}
@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, watch};
+
+use crate::config::Config;
+use crate::history_store::HistoryStore;
+use crate::locale::Formatter;
+use crate::toast::Toast;
+use crate::{Source, SpotifyData};
+
+// listened to for less than this and it counts as a skip
+const SKIP_THRESHOLD: Duration = Duration::from_secs(30);
+// this many skips in a row and the hint suggests something else
+const SKIP_STREAK: usize = 5;
+const MOST_SKIPPED: usize = 5;
+// a play seen by both spotify and MPRIS is recorded by each, a little apart
+// since they settle on their own schedules
+const MERGE_WINDOW: Duration = Duration::from_secs(90);
+
+// one settled track, recorded once the next one settles
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Play {
+    pub artist: String,
+    pub track: String,
+    pub album: String,
+    pub track_id: Option<String>,
+    // unix seconds
+    pub played_at: u64,
+    pub listened_ms: u64,
+    pub skipped: bool,
+    // unset for plays recorded before sources were
+    #[serde(default)]
+    pub source: Option<Source>,
+}
+
+impl Play {
+    fn new(track: &SpotifyData, played_at: SystemTime, listened: Duration) -> Self {
+        let listened_ms = match track.duration_ms {
+            Some(duration_ms) => (listened.as_millis() as u64).min(duration_ms),
+            None => listened.as_millis() as u64,
+        };
+        Play {
+            artist: track.artist_name.clone(),
+            track: track.track_name.clone(),
+            album: track.album_name.clone(),
+            track_id: track.track_id.clone(),
+            played_at: played_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            listened_ms,
+            skipped: listened < SKIP_THRESHOLD,
+            source: Some(track.source),
+        }
+    }
+
+    // a track that got to where the crossfade starts was heard to the end,
+    // the next one just took over early
+    fn with_crossfade(mut self, duration_ms: Option<u64>, crossfade: Duration) -> Self {
+        let Some(duration_ms) = duration_ms else {
+            return self;
+        };
+        let crossfade_ms = crossfade.as_millis() as u64;
+        if self.listened_ms + crossfade_ms >= duration_ms {
+            self.listened_ms = duration_ms;
+            self.skipped = Duration::from_millis(duration_ms) < SKIP_THRESHOLD;
+        }
+        self
+    }
+
+    fn is_same_play(&self, other: &Play) -> bool {
+        let same = |a: &str, b: &str| a.trim().to_lowercase() == b.trim().to_lowercase();
+        self.source.is_some()
+            && other.source.is_some()
+            && self.source != other.source
+            && self.played_at.abs_diff(other.played_at) <= MERGE_WINDOW.as_secs()
+            && same(&self.track, &other.track)
+            && same(&self.artist, &other.artist)
+    }
+
+    // keeps the earliest start and the longest listen. spotify's side wins
+    // for names and ids, MPRIS players often leave them out or tag their own
+    fn absorb(&mut self, other: Play) {
+        let played_at = self.played_at.min(other.played_at);
+        let listened_ms = self.listened_ms.max(other.listened_ms);
+        let (mut kept, rest) = if other.source == Some(Source::Spotify) {
+            (other, std::mem::take(self))
+        } else {
+            (std::mem::take(self), other)
+        };
+        kept.track_id = kept.track_id.or(rest.track_id);
+        if kept.album.is_empty() {
+            kept.album = rest.album;
+        }
+        kept.played_at = played_at;
+        kept.listened_ms = listened_ms;
+        kept.skipped = Duration::from_millis(listened_ms) < SKIP_THRESHOLD;
+        *self = kept;
+    }
+}
+
+// one entry per play, where both sources recorded it. each entry takes in
+// at most one from the other source, so replaying a track straight away
+// still counts twice
+pub fn merge(mut plays: Vec<Play>) -> Vec<Play> {
+    plays.sort_by_key(|play| play.played_at);
+    let mut merged: Vec<(Play, bool)> = Vec::with_capacity(plays.len());
+    for play in plays {
+        let twin = merged
+            .iter_mut()
+            .rev()
+            .take_while(|(kept, _)| play.played_at - kept.played_at <= MERGE_WINDOW.as_secs())
+            .find(|(kept, absorbed)| !absorbed && kept.is_same_play(&play));
+        match twin {
+            Some((kept, absorbed)) => {
+                kept.absorb(play);
+                *absorbed = true;
+            }
+            None => merged.push((play, false)),
+        }
+    }
+    merged.into_iter().map(|(play, _)| play).collect()
+}
+
+// appended to as tracks finish. plays recorded by both sources are merged
+// as they're read
+pub fn load(store: &dyn HistoryStore) -> Vec<Play> {
+    match store.plays() {
+        Ok(plays) => merge(plays),
+        Err(e) => {
+            eprintln!("couldn't read the history: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn append(store: &mut dyn HistoryStore, plays: &[Play]) {
+    if let Err(e) = store.append(plays) {
+        eprintln!("couldn't record play: {}", e);
+    }
+}
+
+// one stream in spotify's extended streaming history export
+// (Streaming_History_Audio_*.json from the privacy page). podcast episodes
+// have no track name and are left out
+#[derive(Deserialize)]
+struct ExportedStream {
+    // when the stream ended
+    ts: String,
+    ms_played: u64,
+    master_metadata_track_name: Option<String>,
+    master_metadata_album_artist_name: Option<String>,
+    master_metadata_album_album_name: Option<String>,
+    spotify_track_uri: Option<String>,
+}
+
+fn parse_export(raw: &str) -> Result<Vec<Play>, anyhow::Error> {
+    let streams: Vec<ExportedStream> = serde_json::from_str(raw)?;
+    Ok(streams
+        .into_iter()
+        .filter_map(|stream| {
+            let ended = chrono::DateTime::parse_from_rfc3339(&stream.ts).ok()?;
+            Some(Play {
+                artist: stream.master_metadata_album_artist_name?,
+                track: stream.master_metadata_track_name?,
+                album: stream.master_metadata_album_album_name.unwrap_or_default(),
+                track_id: stream
+                    .spotify_track_uri
+                    .and_then(|uri| uri.strip_prefix("spotify:track:").map(String::from)),
+                played_at: (ended.timestamp() as u64).saturating_sub(stream.ms_played / 1000),
+                listened_ms: stream.ms_played,
+                skipped: Duration::from_millis(stream.ms_played) < SKIP_THRESHOLD,
+                source: Some(Source::Spotify),
+            })
+        })
+        .collect())
+}
+
+// the imported plays that aren't in the history yet, going by track and
+// start time, so importing the same files twice adds nothing. plays of the
+// same track close together are all kept, they're real repeats
+fn new_plays(existing: &[Play], imported: Vec<Play>) -> Vec<Play> {
+    let key = |play: &Play| {
+        (
+            play.artist.to_lowercase(),
+            play.track.to_lowercase(),
+            play.played_at,
+        )
+    };
+    let mut seen: HashSet<_> = existing.iter().map(key).collect();
+    imported
+        .into_iter()
+        .filter(|play| seen.insert(key(play)))
+        .collect()
+}
+
+// the export's json files, or every one in a directory
+fn import(store: &mut dyn HistoryStore, paths: &[String]) -> Result<usize, anyhow::Error> {
+    let mut files = Vec::new();
+    for path in paths.iter().map(Path::new) {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|ext| ext == "json") {
+                    files.push(file);
+                }
+            }
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    let mut imported = Vec::new();
+    for file in files {
+        let raw = std::fs::read_to_string(&file)?;
+        match parse_export(&raw) {
+            Ok(plays) => imported.extend(plays),
+            Err(e) => eprintln!("skipping {}: {}", file.display(), e),
+        }
+    }
+    imported.sort_by_key(|play| play.played_at);
+    let plays = new_plays(&load(store), imported);
+    store.append(&plays)?;
+    Ok(plays.len())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SkipStats {
+    pub plays: usize,
+    pub skips: usize,
+    // "artist - track" with how often it was skipped, most first
+    pub most_skipped: Vec<(String, usize)>,
+}
+
+impl SkipStats {
+    pub fn from_plays(plays: &[Play]) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for play in plays.iter().filter(|play| play.skipped) {
+            *counts
+                .entry(format!("{} - {}", play.artist, play.track))
+                .or_default() += 1;
+        }
+        let mut most_skipped: Vec<(String, usize)> = counts.into_iter().collect();
+        most_skipped.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_skipped.truncate(MOST_SKIPPED);
+        SkipStats {
+            plays: plays.len(),
+            skips: plays.iter().filter(|play| play.skipped).count(),
+            most_skipped,
+        }
+    }
+}
+
+// how much was listened to and since when, e.g.
+// "1,234 plays, 4,321 minutes listened since 03/01/24"
+fn summary(plays: &[Play], format: &Formatter) -> String {
+    let minutes = plays.iter().map(|play| play.listened_ms).sum::<u64>() / 60_000;
+    let mut line = format!(
+        "{} plays, {} minutes listened",
+        format.number(plays.len() as u64),
+        format.number(minutes)
+    );
+    let first = plays
+        .iter()
+        .map(|play| play.played_at)
+        .min()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0));
+    if let Some(first) = first {
+        line.push_str(&format!(
+            " since {}",
+            format.date(&first.with_timezone(&chrono::Local))
+        ));
+    }
+    line
+}
+
+// `kyomi history`, with `--skips` for what gets skipped, or
+// `--import <files or directory>` to add spotify's streaming history export
+pub fn history_command(args: &[String]) {
+    let config = Config::load();
+    let mut store = crate::history_store::open(config.history_backend);
+    let skips = match args.first().map(String::as_str) {
+        None => false,
+        Some("--skips") => true,
+        Some("--import") if args.len() > 1 => {
+            match import(store.as_mut(), &args[1..]) {
+                Ok(count) => println!("imported {} plays", count),
+                Err(e) => eprintln!("import failed: {}", e),
+            }
+            return;
+        }
+        Some(_) => {
+            println!("usage: kyomi history [--skips | --import <files or directory>]");
+            return;
+        }
+    };
+    let plays = load(store.as_ref());
+    if plays.is_empty() {
+        println!("no plays recorded yet");
+        return;
+    }
+    let format = Formatter::new(config.locale.as_deref());
+    if !skips {
+        println!("{}", summary(&plays, &format));
+        return;
+    }
+    let stats = SkipStats::from_plays(&plays);
+    println!(
+        "{} of {} plays skipped ({:.0}%)",
+        format.number(stats.skips as u64),
+        format.number(stats.plays as u64),
+        stats.skips as f64 / stats.plays as f64 * 100.0
+    );
+    for (track, skips) in stats.most_skipped {
+        println!("{:>6}  {}", format.number(skips as u64), track);
+    }
+}
+
+// records each settled track once the next one settles, timing the listen
+// from when it settled. the dwell is added back since a track has already
+// been playing that long by then, and so is the crossfade when the track
+// played out. optionally hints at a change of scenery after a run of skips
+pub fn spawn_history(
+    mut store: Box<dyn HistoryStore + Send>,
+    mut settled: broadcast::Receiver<SpotifyData>,
+    dwell: Duration,
+    crossfade: Duration,
+    streak_hint: bool,
+) -> watch::Receiver<Option<Toast>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let mut current: Option<(SpotifyData, SystemTime, Instant)> = None;
+        let mut streak = 0;
+        loop {
+            let track = match settled.recv().await {
+                Ok(track) => track,
+                // tracks went by unseen, so there's no telling how long the
+                // current one played for. it's left out rather than guessed
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    current = None;
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let now = (SystemTime::now(), Instant::now());
+            if let Some((last, settled_at, since)) = current.replace((track, now.0, now.1)) {
+                let play = Play::new(&last, settled_at - dwell, since.elapsed() + dwell)
+                    .with_crossfade(last.duration_ms, crossfade);
+                append(store.as_mut(), std::slice::from_ref(&play));
+
+                streak = if play.skipped { streak + 1 } else { 0 };
+                if streak_hint && streak == SKIP_STREAK {
+                    let toast = Toast::new(vec![
+                        format!("skipped {} in a row", streak),
+                        String::from("try a radio or playlist?"),
+                    ]);
+                    if tx.send(Some(toast)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_skip_stats() {
+    let track = |name: &str| SpotifyData {
+        track_name: name.to_owned(),
+        artist_name: String::from("artist"),
+        duration_ms: Some(180_000),
+        ..Default::default()
+    };
+    let plays = vec![
+        Play::new(&track("a"), UNIX_EPOCH, Duration::from_secs(10)),
+        Play::new(&track("b"), UNIX_EPOCH, Duration::from_secs(600)),
+        Play::new(&track("a"), UNIX_EPOCH, Duration::from_secs(20)),
+        Play::new(&track("c"), UNIX_EPOCH, Duration::from_secs(5)),
+    ];
+    assert_eq!(plays[1].listened_ms, 180_000);
+    assert!(summary(&plays, &Formatter::new(Some("C")))
+        .starts_with("4 plays, 3 minutes listened since "));
+
+    assert_eq!(
+        SkipStats::from_plays(&plays),
+        SkipStats {
+            plays: 4,
+            skips: 3,
+            most_skipped: vec![
+                (String::from("artist - a"), 2),
+                (String::from("artist - c"), 1)
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_crossfade_counts_as_played_out() {
+    let track = |duration_ms| SpotifyData {
+        duration_ms,
+        ..Default::default()
+    };
+    let crossfade = Duration::from_secs(12);
+    let listened = |duration_ms, secs| {
+        let play = Play::new(&track(duration_ms), UNIX_EPOCH, Duration::from_secs(secs))
+            .with_crossfade(duration_ms, crossfade);
+        (play.listened_ms, play.skipped)
+    };
+    // faded out right where the crossfade starts, or a moment before
+    assert_eq!(listened(Some(180_000), 168), (180_000, false));
+    assert_eq!(listened(Some(180_000), 167), (167_000, false));
+    // too short to count without the crossfade, heard in full with it
+    assert_eq!(listened(Some(35_000), 23), (35_000, false));
+    assert_eq!(listened(Some(35_000), 22), (22_000, true));
+    assert_eq!(listened(None, 100), (100_000, false));
+    assert_eq!(
+        Play::new(&track(Some(180_000)), UNIX_EPOCH, Duration::from_secs(168))
+            .with_crossfade(Some(180_000), Duration::ZERO)
+            .listened_ms,
+        168_000
+    );
+}
+
+#[test]
+fn test_plays_seen_by_both_sources_merge() {
+    let play = |track: &str, source, played_at, listened_secs: u64| Play {
+        artist: String::from("Artist"),
+        track: track.to_owned(),
+        album: String::new(),
+        track_id: None,
+        played_at,
+        listened_ms: listened_secs * 1000,
+        skipped: listened_secs < 30,
+        source,
+    };
+    let spotify = Play {
+        track_id: Some(String::from("id")),
+        album: String::from("Album"),
+        ..play("Song", Some(Source::Spotify), 1_010, 170)
+    };
+    let plays = vec![
+        play("song ", Some(Source::Mpris), 1_000, 175),
+        spotify,
+        // replayed right after, only mpris saw it
+        play("Song", Some(Source::Mpris), 1_060, 20),
+        // same source, same time: two instances of one player, kept apart
+        play("Other", Some(Source::Mpris), 2_000, 60),
+        play("Other", Some(Source::Mpris), 2_005, 60),
+        // too far apart
+        play("Far", Some(Source::Spotify), 3_000, 60),
+        play("Far", Some(Source::Mpris), 3_200, 60),
+        // from before sources were recorded
+        play("Old", None, 4_000, 60),
+        play("Old", Some(Source::Spotify), 4_001, 60),
+    ];
+
+    let merged = merge(plays);
+    assert_eq!(merged.len(), 8);
+    assert_eq!(merged[0].track, "Song");
+    assert_eq!(merged[0].track_id.as_deref(), Some("id"));
+    assert_eq!(merged[0].played_at, 1_000);
+    assert_eq!(merged[0].listened_ms, 175_000);
+    assert_eq!(merged[0].source, Some(Source::Spotify));
+    assert_eq!(merged[1].played_at, 1_060);
+    assert!(merged[1].skipped);
+}
+
+#[test]
+fn test_import_skips_known_plays() {
+    let export = r#"[
+        {"ts": "2021-03-01T12:03:00Z", "ms_played": 180000,
+         "master_metadata_track_name": "Song", "master_metadata_album_artist_name": "Artist",
+         "master_metadata_album_album_name": "Album", "spotify_track_uri": "spotify:track:abc"},
+        {"ts": "2021-03-01T13:00:00Z", "ms_played": 5000,
+         "master_metadata_track_name": "Other", "master_metadata_album_artist_name": "Artist",
+         "master_metadata_album_album_name": "Album", "spotify_track_uri": "spotify:track:def"},
+        {"ts": "2021-03-01T14:00:00Z", "ms_played": 900000,
+         "master_metadata_track_name": null, "master_metadata_album_artist_name": null,
+         "master_metadata_album_album_name": null, "spotify_track_uri": null,
+         "episode_name": "A podcast"}
+    ]"#;
+    let imported = parse_export(export).unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0].played_at, 1_614_600_000);
+    assert_eq!(imported[0].track_id.as_deref(), Some("abc"));
+    assert!(imported[1].skipped);
+
+    // the first one was imported before
+    let existing = vec![imported[0].clone()];
+    let twice: Vec<Play> = imported.iter().chain(&imported).cloned().collect();
+    let added = new_plays(&existing, twice);
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].track, "Other");
+
+    // played again a minute later is another play
+    let replayed = Play {
+        played_at: 1_614_600_060,
+        ..imported[0].clone()
+    };
+    assert_eq!(new_plays(&existing, vec![replayed]).len(), 1);
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::history::Play;
+
+// where plays are kept, set in the config as history_backend
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    // history.sqlite, for anyone who'd rather query it with SQL
+    #[default]
+    Sqlite,
+    // history.jsonl, one play per line, with nothing to link
+    Jsonl,
+}
+
+pub trait HistoryStore {
+    // every play recorded, in the order they were added and not yet merged
+    fn plays(&self) -> Result<Vec<Play>, anyhow::Error>;
+    fn append(&mut self, plays: &[Play]) -> Result<(), anyhow::Error>;
+}
+
+fn data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+}
+
+pub struct JsonlStore {
+    path: PathBuf,
+}
+
+impl JsonlStore {
+    pub fn new(path: PathBuf) -> Self {
+        JsonlStore { path }
+    }
+}
+
+impl HistoryStore for JsonlStore {
+    fn plays(&self) -> Result<Vec<Play>, anyhow::Error> {
+        Ok(std::fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn append(&mut self, plays: &[Play]) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(self.path.parent().unwrap())?;
+        let mut file = std::io::BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?,
+        );
+        for play in plays {
+            writeln!(file, "{}", serde_json::to_string(play)?)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    connection: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub fn open(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let connection = rusqlite::Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS plays (
+                id INTEGER PRIMARY KEY,
+                artist TEXT NOT NULL,
+                track TEXT NOT NULL,
+                album TEXT NOT NULL,
+                track_id TEXT,
+                played_at INTEGER NOT NULL,
+                listened_ms INTEGER NOT NULL,
+                skipped INTEGER NOT NULL,
+                source TEXT
+            );
+            CREATE INDEX IF NOT EXISTS plays_played_at ON plays (played_at);",
+        )?;
+        Ok(SqliteStore { connection })
+    }
+
+    fn is_empty(&self) -> Result<bool, anyhow::Error> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM plays", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+}
+
+// sources go in as the same lowercase names the jsonl file uses
+#[cfg(feature = "sqlite")]
+fn source_name(source: Option<crate::Source>) -> Option<String> {
+    serde_json::to_value(source?)
+        .ok()?
+        .as_str()
+        .map(String::from)
+}
+
+#[cfg(feature = "sqlite")]
+impl HistoryStore for SqliteStore {
+    fn plays(&self) -> Result<Vec<Play>, anyhow::Error> {
+        let mut statement = self.connection.prepare(
+            "SELECT artist, track, album, track_id, played_at, listened_ms, skipped, source
+             FROM plays ORDER BY id",
+        )?;
+        let plays = statement.query_map([], |row| {
+            let source: Option<String> = row.get(7)?;
+            Ok(Play {
+                artist: row.get(0)?,
+                track: row.get(1)?,
+                album: row.get(2)?,
+                track_id: row.get(3)?,
+                played_at: row.get::<_, i64>(4)? as u64,
+                listened_ms: row.get::<_, i64>(5)? as u64,
+                skipped: row.get(6)?,
+                source: source.and_then(|name| serde_json::from_value(name.into()).ok()),
+            })
+        })?;
+        Ok(plays.collect::<Result<_, _>>()?)
+    }
+
+    fn append(&mut self, plays: &[Play]) -> Result<(), anyhow::Error> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO plays
+                 (artist, track, album, track_id, played_at, listened_ms, skipped, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for play in plays {
+                statement.execute(rusqlite::params![
+                    play.artist,
+                    play.track,
+                    play.album,
+                    play.track_id,
+                    play.played_at as i64,
+                    play.listened_ms as i64,
+                    play.skipped,
+                    source_name(play.source),
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+// a new database starts with whatever the jsonl file already recorded, so
+// switching backends doesn't lose the history
+#[cfg(feature = "sqlite")]
+fn open_sqlite() -> Result<SqliteStore, anyhow::Error> {
+    let mut store = SqliteStore::open(&data_dir().join("history.sqlite"))?;
+    if store.is_empty()? {
+        let earlier = JsonlStore::new(data_dir().join("history.jsonl")).plays()?;
+        store.append(&earlier)?;
+    }
+    Ok(store)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite() -> Result<JsonlStore, anyhow::Error> {
+    Err(anyhow::anyhow!("built without sqlite"))
+}
+
+// the configured store, or the jsonl file if that can't be opened
+pub fn open(backend: HistoryBackend) -> Box<dyn HistoryStore + Send> {
+    let jsonl = || Box::new(JsonlStore::new(data_dir().join("history.jsonl")));
+    match backend {
+        HistoryBackend::Jsonl => jsonl(),
+        HistoryBackend::Sqlite => match open_sqlite() {
+            Ok(store) => Box::new(store),
+            Err(e) => {
+                eprintln!("couldn't open the history database, using jsonl: {}", e);
+                jsonl()
+            }
+        },
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_stores_round_trip() {
+    let dir = std::env::temp_dir().join(format!("kyomi-history-{}", std::process::id()));
+    let plays = vec![
+        Play {
+            artist: String::from("Artist"),
+            track: String::from("Song"),
+            track_id: Some(String::from("id")),
+            played_at: 1_000,
+            listened_ms: 180_000,
+            source: Some(crate::Source::Mpris),
+            ..Default::default()
+        },
+        Play {
+            artist: String::from("Artist"),
+            track: String::from("Other"),
+            played_at: 1_200,
+            listened_ms: 5_000,
+            skipped: true,
+            ..Default::default()
+        },
+    ];
+    let mut stores: Vec<Box<dyn HistoryStore>> = vec![
+        Box::new(JsonlStore::new(dir.join("history.jsonl"))),
+        Box::new(SqliteStore::open(&dir.join("history.sqlite")).unwrap()),
+    ];
+    for store in stores.iter_mut() {
+        store.append(&plays[..1]).unwrap();
+        store.append(&plays[1..]).unwrap();
+        assert_eq!(store.plays().unwrap(), plays);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
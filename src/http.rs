@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::audit::SendAudited;
+
+// the wait before the first retry, doubling after that
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+
+// requests are grouped by how long they're worth waiting for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endpoint {
+    // getting and refreshing tokens
+    Auth,
+    // the player api, and everything else small that's asked for often
+    Polling,
+    // cover art and the other lookups that can arrive late, like artist bios
+    Art,
+}
+
+// any of these left out of the config use the endpoint's default
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Limits {
+    pub connect_secs: Option<u64>,
+    pub read_secs: Option<u64>,
+    pub retries: Option<u32>,
+    // for every attempt and the waits between them together
+    pub budget_secs: Option<u64>,
+}
+
+// set in the config, e.g.
+//   [timeouts.polling]
+//   read_secs = 5
+//   retries = 0
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Timeouts {
+    pub auth: Limits,
+    pub polling: Limits,
+    pub art: Limits,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Budget {
+    connect: Duration,
+    read: Duration,
+    retries: u32,
+    total: Duration,
+}
+
+impl Endpoint {
+    // polling gives up quickest, the next poll isn't far off anyway
+    fn default_limits(self) -> (u64, u64, u32, u64) {
+        match self {
+            Endpoint::Auth => (10, 15, 2, 45),
+            Endpoint::Polling => (5, 10, 1, 15),
+            Endpoint::Art => (10, 30, 2, 90),
+        }
+    }
+
+    fn budget(self, timeouts: &Timeouts) -> Budget {
+        let limits = match self {
+            Endpoint::Auth => timeouts.auth,
+            Endpoint::Polling => timeouts.polling,
+            Endpoint::Art => timeouts.art,
+        };
+        let (connect, read, retries, total) = self.default_limits();
+        Budget {
+            connect: Duration::from_secs(limits.connect_secs.unwrap_or(connect)),
+            read: Duration::from_secs(limits.read_secs.unwrap_or(read)),
+            retries: limits.retries.unwrap_or(retries),
+            total: Duration::from_secs(limits.budget_secs.unwrap_or(total)),
+        }
+    }
+}
+
+impl Budget {
+    // how long to wait before trying again, if there's a retry left and it
+    // would start inside the budget
+    fn retry_wait(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        let wait = FIRST_BACKOFF * 2u32.saturating_pow(attempt);
+        (attempt < self.retries && elapsed + wait < self.total).then_some(wait)
+    }
+}
+
+// what every http client is built with, for networks that need a proxy or
+// intercept tls with their own certificate authority
+#[derive(Clone, Default)]
+pub struct Network {
+    proxy: Option<reqwest::Proxy>,
+    certificates: Vec<reqwest::Certificate>,
+    timeouts: Timeouts,
+}
+
+// set once at startup from the config, before any client is made
+static NETWORK: OnceLock<Network> = OnceLock::new();
+
+impl Network {
+    // proxy is a url, http://, https://, socks5:// or socks5h:// (dns through
+    // the proxy). ca_bundle is a pem file with one or more certificates,
+    // trusted alongside the usual ones
+    pub fn new(proxy: Option<&str>, ca_bundle: Option<&Path>) -> Result<Self, anyhow::Error> {
+        let proxy = proxy
+            .map(reqwest::Proxy::all)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("bad proxy: {}", e))?;
+        let certificates = match ca_bundle {
+            Some(path) => {
+                let pem = std::fs::read(path)
+                    .map_err(|e| anyhow::anyhow!("couldn't read {}: {}", path.display(), e))?;
+                reqwest::Certificate::from_pem_bundle(&pem)?
+            }
+            None => Vec::new(),
+        };
+        Ok(Network {
+            proxy,
+            certificates,
+            timeouts: Timeouts::default(),
+        })
+    }
+}
+
+// a bad setting is reported and left out rather than stopping the widget.
+// without a proxy set, the usual HTTP(S)_PROXY variables still apply
+pub fn configure(config: &crate::config::Config) {
+    let network = Network::new(config.proxy.as_deref(), config.ca_bundle.as_deref())
+        .unwrap_or_else(|e| {
+            eprintln!("{:?}, using the default network settings", e);
+            Network::default()
+        });
+    let _ = NETWORK.set(Network {
+        timeouts: config.timeouts,
+        ..network
+    });
+}
+
+fn budget(endpoint: Endpoint) -> Budget {
+    let timeouts = NETWORK
+        .get()
+        .map(|network| network.timeouts)
+        .unwrap_or_default();
+    endpoint.budget(&timeouts)
+}
+
+pub fn builder(endpoint: Endpoint) -> reqwest::ClientBuilder {
+    let budget = budget(endpoint);
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(budget.connect)
+        .read_timeout(budget.read);
+    if let Some(network) = NETWORK.get() {
+        if let Some(proxy) = &network.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &network.certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+    }
+    builder
+}
+
+pub fn client(endpoint: Endpoint) -> reqwest::Client {
+    builder(endpoint).build().unwrap_or_else(|e| {
+        eprintln!("couldn't set up the http client: {}", e);
+        reqwest::Client::new()
+    })
+}
+
+// a POST that timed out may still have skipped the track or submitted the
+// listen, so those are only sent again when they never got out
+fn worth_retrying(res: &reqwest::Result<reqwest::Response>, idempotent: bool) -> bool {
+    match res {
+        Ok(response) => idempotent && response.status().is_server_error(),
+        Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+    }
+}
+
+// sends, trying again after connection failures, timeouts and 5xx answers
+// until the endpoint's retries or budget run out. each attempt only gets
+// what's left of the budget, and goes in the audit log on its own
+async fn send(
+    request: reqwest::RequestBuilder,
+    endpoint: Endpoint,
+) -> reqwest::Result<reqwest::Response> {
+    let budget = budget(endpoint);
+    let started = Instant::now();
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let idempotent = request.method().is_idempotent();
+    let mut attempt = 0;
+    loop {
+        let retry = request.try_clone();
+        *request.timeout_mut() = Some(budget.total.saturating_sub(started.elapsed()));
+        let res = reqwest::RequestBuilder::from_parts(client.clone(), request)
+            .send_audited()
+            .await;
+        let wait = budget.retry_wait(attempt, started.elapsed());
+        match (retry, wait) {
+            (Some(retry), Some(wait)) if worth_retrying(&res, idempotent) => {
+                tokio::time::sleep(wait).await;
+                request = retry;
+                attempt += 1;
+            }
+            _ => return res,
+        }
+    }
+}
+
+// used in place of RequestBuilder::send everywhere
+pub trait SendRetrying {
+    fn send_retrying(
+        self,
+        endpoint: Endpoint,
+    ) -> impl std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send;
+}
+
+impl SendRetrying for reqwest::RequestBuilder {
+    fn send_retrying(
+        self,
+        endpoint: Endpoint,
+    ) -> impl std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send {
+        send(self, endpoint)
+    }
+}
+
+#[test]
+fn test_retries_stay_in_budget() {
+    let timeouts: Timeouts = toml::from_str("[polling]\nretries = 3\nbudget_secs = 4").unwrap();
+    let budget = Endpoint::Polling.budget(&timeouts);
+    assert_eq!(budget.read, Duration::from_secs(10));
+    assert_eq!(budget.retry_wait(0, Duration::ZERO), Some(FIRST_BACKOFF));
+    assert_eq!(
+        budget.retry_wait(2, Duration::from_secs(1)),
+        Some(FIRST_BACKOFF * 4)
+    );
+    // the wait would run past the budget
+    assert_eq!(budget.retry_wait(2, Duration::from_secs(2)), None);
+    assert_eq!(budget.retry_wait(3, Duration::ZERO), None);
+    assert_eq!(Endpoint::Auth.budget(&timeouts).retries, 2);
+}
+
+#[test]
+fn test_proxy_settings() {
+    assert!(Network::new(Some("socks5h://127.0.0.1:1080"), None).is_ok());
+    assert!(Network::new(Some("http://proxy.corp:3128"), None).is_ok());
+    assert!(Network::new(Some("not a proxy"), None).is_err());
+    assert!(Network::new(None, Some(Path::new("/nonexistent/ca.pem"))).is_err());
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
+
+use crate::player::PlayerCommand;
+use crate::rating::Rating;
+
+const SEEK_STEP_MS: i64 = 5000;
+
+// keys that change the widget itself rather than playback
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UiAction {
+    ToggleExpanded,
+    // there's no way to get keyboard input back on a hidden window, so this
+    // only hides. the widget comes back when it's restored from the taskbar
+    Hide,
+    NextPage,
+    TogglePrivacy,
+    // streamer mode: hide the track from whoever is watching the screen
+    ToggleRedacted,
+    // cycles through the installed themes
+    NextTheme,
+    // one beat for the tap tempo tool, opening it if it isn't showing
+    TapTempo,
+    // adds the track to the spotify library, or takes it out
+    ToggleLike,
+    // the quick search launcher
+    OpenSearch,
+}
+
+pub fn ui_action_for_key(logical_key: &Key) -> Option<UiAction> {
+    match logical_key {
+        Key::Named(NamedKey::Tab) => Some(UiAction::NextPage),
+        Key::Character(c) if c.eq_ignore_ascii_case("e") => Some(UiAction::ToggleExpanded),
+        Key::Character(c) if c.eq_ignore_ascii_case("h") => Some(UiAction::Hide),
+        Key::Character(c) if c.eq_ignore_ascii_case("p") => Some(UiAction::TogglePrivacy),
+        Key::Character(c) if c.eq_ignore_ascii_case("x") => Some(UiAction::ToggleRedacted),
+        Key::Character(c) if c.eq_ignore_ascii_case("t") => Some(UiAction::NextTheme),
+        Key::Character(c) if c.eq_ignore_ascii_case("b") => Some(UiAction::TapTempo),
+        Key::Character(c) if c.eq_ignore_ascii_case("l") => Some(UiAction::ToggleLike),
+        Key::Character(c) if c == "/" => Some(UiAction::OpenSearch),
+        _ => None,
+    }
+}
+
+// the key that makes a click-through widget take input while it's held
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HoldModifier {
+    Alt,
+    Ctrl,
+    Shift,
+    Super,
+}
+
+impl HoldModifier {
+    // either side of the keyboard counts
+    pub fn matches(&self, code: KeyCode) -> bool {
+        match self {
+            HoldModifier::Alt => matches!(code, KeyCode::AltLeft | KeyCode::AltRight),
+            HoldModifier::Ctrl => matches!(code, KeyCode::ControlLeft | KeyCode::ControlRight),
+            HoldModifier::Shift => matches!(code, KeyCode::ShiftLeft | KeyCode::ShiftRight),
+            HoldModifier::Super => matches!(code, KeyCode::SuperLeft | KeyCode::SuperRight),
+        }
+    }
+}
+
+// maps a key press on the focused widget to a playback command.
+// media keys always work; space/arrows follow the spotify desktop bindings
+// (ctrl+arrow skips tracks), and the numpad mirrors them for one-handed use.
+// s and r toggle shuffle and cycle repeat, + and - rate the track, and d
+// moves playback to the next connect device.
+pub fn command_for_key(
+    logical_key: &Key,
+    physical_key: PhysicalKey,
+    modifiers: ModifiersState,
+) -> Option<PlayerCommand> {
+    if let PhysicalKey::Code(code) = physical_key {
+        match code {
+            KeyCode::Numpad5 => return Some(PlayerCommand::PlayPause),
+            KeyCode::Numpad4 => return Some(PlayerCommand::SeekBy(-SEEK_STEP_MS)),
+            KeyCode::Numpad6 => return Some(PlayerCommand::SeekBy(SEEK_STEP_MS)),
+            KeyCode::Numpad7 => return Some(PlayerCommand::Previous),
+            KeyCode::Numpad9 => return Some(PlayerCommand::Next),
+            _ => {}
+        }
+    }
+
+    match logical_key {
+        Key::Named(NamedKey::MediaPlayPause) | Key::Named(NamedKey::Space) => {
+            Some(PlayerCommand::PlayPause)
+        }
+        Key::Named(NamedKey::MediaTrackNext) => Some(PlayerCommand::Next),
+        Key::Named(NamedKey::MediaTrackPrevious) => Some(PlayerCommand::Previous),
+        Key::Named(NamedKey::ArrowRight) if modifiers.control_key() => Some(PlayerCommand::Next),
+        Key::Named(NamedKey::ArrowLeft) if modifiers.control_key() => Some(PlayerCommand::Previous),
+        Key::Named(NamedKey::ArrowRight) => Some(PlayerCommand::SeekBy(SEEK_STEP_MS)),
+        Key::Named(NamedKey::ArrowLeft) => Some(PlayerCommand::SeekBy(-SEEK_STEP_MS)),
+        Key::Named(NamedKey::Home) => Some(PlayerCommand::SetPosition(0)),
+        Key::Character(c) if c.eq_ignore_ascii_case("s") => Some(PlayerCommand::ToggleShuffle),
+        Key::Character(c) if c.eq_ignore_ascii_case("r") => Some(PlayerCommand::CycleRepeat),
+        Key::Character(c) if c.eq_ignore_ascii_case("d") => Some(PlayerCommand::NextDevice),
+        Key::Character(c) if c.eq_ignore_ascii_case("f") => Some(PlayerCommand::AddToFavorites),
+        Key::Character(c) if c == "+" || c == "=" => Some(PlayerCommand::Rate(Rating::Up)),
+        Key::Character(c) if c == "-" => Some(PlayerCommand::Rate(Rating::Down)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_arrow_keys_seek_and_skip() {
+    let left = Key::Named(NamedKey::ArrowLeft);
+    let physical = PhysicalKey::Code(KeyCode::ArrowLeft);
+
+    assert_eq!(
+        command_for_key(&left, physical, ModifiersState::empty()),
+        Some(PlayerCommand::SeekBy(-SEEK_STEP_MS))
+    );
+    assert_eq!(
+        command_for_key(&left, physical, ModifiersState::CONTROL),
+        Some(PlayerCommand::Previous)
+    );
+}
+
+#[test]
+fn test_numpad_uses_physical_key() {
+    // with numlock on the logical key is just the digit
+    let five = Key::Character("5".into());
+    assert_eq!(
+        command_for_key(
+            &five,
+            PhysicalKey::Code(KeyCode::Numpad5),
+            ModifiersState::empty()
+        ),
+        Some(PlayerCommand::PlayPause)
+    );
+    assert_eq!(
+        command_for_key(
+            &five,
+            PhysicalKey::Code(KeyCode::Digit5),
+            ModifiersState::empty()
+        ),
+        None
+    );
+}
@@ -0,0 +1,20 @@
+use chrono::{DateTime, Local};
+use winit::window::{Fullscreen, WindowAttributes, WindowLevel};
+
+// `--kiosk` fills a whole monitor instead of sitting in a corner, for a spare
+// screen or a small board next to the speakers. text is drawn this many times
+// the widget's size
+pub const TEXT_SCALE: f32 = 4.0;
+
+// borderless full screen on the monitor the window opens on. it's the only
+// thing on that screen, so it doesn't need keeping above anything
+pub fn with_kiosk(attributes: WindowAttributes) -> WindowAttributes {
+    attributes
+        .with_fullscreen(Some(Fullscreen::Borderless(None)))
+        .with_window_level(WindowLevel::Normal)
+}
+
+// shown under the track
+pub fn clock_line(now: DateTime<Local>) -> String {
+    now.format("%H:%M").to_string()
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use wgpu_text::glyph_brush::ab_glyph::{point, Font, Rect, ScaleFont};
+
+// px size the widget text is drawn at
+pub const TEXT_SIZE: f32 = 16.0;
+
+// where each element of the widget goes, in window pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WidgetLayout {
+    pub size: (f32, f32),
+    pub text_position: (f32, f32),
+    pub text_bounds: (f32, f32),
+    // top right, right-aligned
+    pub indicator_position: (f32, f32),
+    // x, y, width, height of the album cover, while it's shown
+    pub art: Option<[f32; 4]>,
+}
+
+impl WidgetLayout {
+    pub fn new(width: f32, height: f32, padding: f32) -> Self {
+        WidgetLayout {
+            size: (width, height),
+            text_position: (padding, padding),
+            text_bounds: (
+                (width - 2.0 * padding).max(0.0),
+                (height - 2.0 * padding).max(0.0),
+            ),
+            indicator_position: (width - padding, padding),
+            art: None,
+        }
+    }
+
+    // a square cover at the left of the text area, as tall as it but at most
+    // half as wide, with the text moved over to start after it
+    pub fn with_art(mut self, padding: f32) -> Self {
+        let (x, y) = self.text_position;
+        let side = self.text_bounds.1.min(self.text_bounds.0 / 2.0);
+        self.art = Some([x, y, side, side]);
+        self.text_position.0 += side + padding;
+        self.text_bounds.0 = (self.text_bounds.0 - side - padding).max(0.0);
+        self
+    }
+
+    // the round ends of a pill would clip text at the corners of the text
+    // area, so it's pulled in to where the curve is at 45 degrees
+    pub fn with_shape(width: f32, height: f32, padding: f32, shape: WidgetShape) -> Self {
+        let mut layout = WidgetLayout::new(width, height, padding);
+        if shape == WidgetShape::Pill {
+            let inset =
+                shape.corner_radius(0.0, width, height) * (1.0 - std::f32::consts::FRAC_1_SQRT_2);
+            layout.text_position.0 += inset;
+            layout.text_bounds.0 = (layout.text_bounds.0 - 2.0 * inset).max(0.0);
+            layout.indicator_position.0 -= inset;
+        }
+        layout
+    }
+
+    // whether a point, in window pixels, is on the widget rather than in the
+    // transparent corners around its outline
+    pub fn contains(&self, point: (f32, f32), corner_radius: f32) -> bool {
+        let (width, height) = self.size;
+        if !(0.0..=width).contains(&point.0) || !(0.0..=height).contains(&point.1) {
+            return false;
+        }
+        // the same distance the shader's corner_mask uses
+        let x = (point.0 - width / 2.0).abs() - (width / 2.0 - corner_radius);
+        let y = (point.1 - height / 2.0).abs() - (height / 2.0 - corner_radius);
+        x.max(0.0).hypot(y.max(0.0)) <= corner_radius
+    }
+}
+
+// the parts of the widget that react to the cursor on their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Element {
+    // anywhere inside the outline that's not one of the below
+    Widget,
+    Artist,
+    ProgressBar,
+}
+
+// where each element was laid out or drawn last frame, in window pixels, for
+// mouse and touch handling to ask what's under a point
+#[derive(Clone, Debug, Default)]
+pub struct HitRegions {
+    layout: WidgetLayout,
+    corner_radius: f32,
+    // later ones are on top
+    regions: Vec<(Element, Rect)>,
+}
+
+impl HitRegions {
+    pub fn new(layout: WidgetLayout, corner_radius: f32) -> Self {
+        HitRegions {
+            layout,
+            corner_radius,
+            regions: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, element: Element, rect: Rect) {
+        self.regions.push((element, rect));
+    }
+
+    // a strip across the whole width, from top to bottom as fractions of the
+    // height, like the quads are placed
+    pub fn insert_band(&mut self, element: Element, top: f32, bottom: f32) {
+        let (width, height) = self.layout.size;
+        self.insert(
+            element,
+            Rect {
+                min: point(0.0, top * height),
+                max: point(width, bottom * height),
+            },
+        );
+    }
+
+    // the topmost element at a point, None outside the widget's outline
+    pub fn at(&self, point: (f32, f32)) -> Option<Element> {
+        if !self.layout.contains(point, self.corner_radius) {
+            return None;
+        }
+        let element = self.regions.iter().rev().find(|(_, rect)| {
+            (rect.min.x..rect.max.x).contains(&point.0)
+                && (rect.min.y..rect.max.y).contains(&point.1)
+        });
+        Some(element.map_or(Element::Widget, |(element, _)| *element))
+    }
+}
+
+// the widget's outline
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WidgetShape {
+    // a rectangle with the theme's corner radius
+    Rounded,
+    // a capsule, with ends rounded all the way
+    Pill,
+}
+
+impl WidgetShape {
+    // in the same units as width and height
+    pub fn corner_radius(self, corner_radius: f32, width: f32, height: f32) -> f32 {
+        let most = width.min(height) / 2.0;
+        match self {
+            WidgetShape::Rounded => corner_radius.clamp(0.0, most),
+            WidgetShape::Pill => most,
+        }
+    }
+}
+
+// vertical metrics of the widget font at a given size, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineMetrics {
+    pub ascent: f32,
+    // negative, below the baseline
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    pub fn from_font(font: &impl Font, size: f32) -> Self {
+        let scaled = font.as_scaled(size);
+        LineMetrics {
+            ascent: scaled.ascent(),
+            descent: scaled.descent(),
+            line_gap: scaled.line_gap(),
+        }
+    }
+
+    fn height(&self) -> f32 {
+        self.ascent - self.descent
+    }
+}
+
+// scripts whose marks stack well past the latin ascender and descender get
+// taller lines, so they aren't clipped or run into their neighbours
+fn script_line_factor(line: &str) -> f32 {
+    line.chars()
+        .map(|c| match c as u32 {
+            // thai, lao, tibetan, myanmar
+            0x0E00..=0x109F => 1.5,
+            // arabic, indic scripts
+            0x0600..=0x06FF | 0x0900..=0x0DFF => 1.3,
+            // kana, cjk, hangul
+            0x3040..=0x30FF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF => 1.1,
+            _ => 1.0,
+        })
+        .fold(1.0, f32::max)
+}
+
+impl WidgetLayout {
+    // top left of each line, with the lines centered as a block in the text
+    // area. lines are stacked by baseline from the font's metrics rather than by
+    // the glyphs they happen to contain, so every line keeps room for its
+    // ascenders and descenders. a block too tall for the area starts at the top
+    pub fn line_positions(&self, lines: &[String], metrics: &LineMetrics) -> Vec<(f32, f32)> {
+        let heights: Vec<f32> = lines
+            .iter()
+            .map(|line| metrics.height() * script_line_factor(line))
+            .collect();
+        let gaps = metrics.line_gap * lines.len().saturating_sub(1) as f32;
+        let block = heights.iter().sum::<f32>() + gaps;
+
+        let mut top = self.text_position.1 + ((self.text_bounds.1 - block) / 2.0).max(0.0);
+        heights
+            .iter()
+            .map(|height| {
+                // extra room from the script factor is split above and below
+                let baseline = top + (height - metrics.height()) / 2.0 + metrics.ascent;
+                top += height + metrics.line_gap;
+                // sections are positioned by the top of the line box
+                (self.text_position.0, baseline - metrics.ascent)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_lines_center_on_metrics() {
+    let layout = WidgetLayout::new(100.0, 100.0, 0.0);
+    let metrics = LineMetrics {
+        ascent: 12.0,
+        descent: -4.0,
+        line_gap: 2.0,
+    };
+    let lines = vec![String::from("a"), String::from("b")];
+
+    // 16 + 2 + 16 tall, centered in 100
+    let positions = layout.line_positions(&lines, &metrics);
+    assert_eq!(positions, vec![(0.0, 33.0), (0.0, 51.0)]);
+
+    // thai gets taller lines, the glyphs stay centered within them
+    let positions = layout.line_positions(&[String::from("ไทย")], &metrics);
+    assert_eq!(positions, vec![(0.0, 42.0)]);
+}
+
+#[test]
+fn test_art_makes_room_left_of_the_text() {
+    let layout = WidgetLayout::new(256.0, 128.0, 10.0).with_art(10.0);
+    assert_eq!(layout.art, Some([10.0, 10.0, 108.0, 108.0]));
+    assert_eq!(layout.text_position, (128.0, 10.0));
+    assert_eq!(layout.text_bounds, (118.0, 108.0));
+
+    // never more than half of a narrow widget
+    let layout = WidgetLayout::new(100.0, 128.0, 10.0).with_art(10.0);
+    assert_eq!(layout.art, Some([10.0, 10.0, 40.0, 40.0]));
+}
+
+#[test]
+fn test_pill_corners_are_outside() {
+    let layout = WidgetLayout::with_shape(200.0, 40.0, 0.0, WidgetShape::Pill);
+    let radius = WidgetShape::Pill.corner_radius(0.0, 200.0, 40.0);
+    assert_eq!(radius, 20.0);
+    assert!(layout.contains((100.0, 20.0), radius));
+    assert!(layout.contains((2.0, 20.0), radius));
+    assert!(!layout.contains((2.0, 2.0), radius));
+    assert!(!layout.contains((210.0, 20.0), radius));
+    assert!(layout.contains((2.0, 2.0), 0.0));
+    assert!(layout.text_position.0 > 5.0);
+}
+
+#[test]
+fn test_hit_regions_pick_topmost() {
+    let layout = WidgetLayout::new(200.0, 100.0, 0.0);
+    let mut regions = HitRegions::new(layout, 10.0);
+    regions.insert_band(Element::ProgressBar, 0.9, 1.0);
+    regions.insert(
+        Element::Artist,
+        Rect {
+            min: point(10.0, 40.0),
+            max: point(80.0, 95.0),
+        },
+    );
+    assert_eq!(regions.at((100.0, 20.0)), Some(Element::Widget));
+    assert_eq!(regions.at((50.0, 50.0)), Some(Element::Artist));
+    assert_eq!(regions.at((50.0, 92.0)), Some(Element::Artist));
+    assert_eq!(regions.at((150.0, 92.0)), Some(Element::ProgressBar));
+    assert_eq!(regions.at((1.0, 99.0)), None);
+}
@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{broadcast, watch};
+
+use crate::player::{self, Backend};
+use crate::SpotifyData;
+
+// how long the heart stays up, dimmed, after a track is unliked
+const UNLIKE_FLASH: Duration = Duration::from_secs(2);
+const LIKED_COLOR: [f32; 4] = [1.0, 0.35, 0.45, 1.0];
+const UNLIKED_COLOR: [f32; 4] = [0.5, 0.5, 0.55, 1.0];
+
+// whether the track playing is in the spotify library
+#[derive(Clone, Debug, PartialEq)]
+pub struct Liked {
+    pub track_id: String,
+    pub saved: bool,
+    // when it was last liked or unliked from the widget
+    pub changed_at: Option<Instant>,
+}
+
+impl Liked {
+    // the heart's color, if one is shown for this track: red while it's
+    // saved, and grey for a moment after it's taken out
+    pub fn heart(&self, track_id: Option<&str>, now: Instant) -> Option<[f32; 4]> {
+        if track_id != Some(self.track_id.as_str()) {
+            return None;
+        }
+        if self.saved {
+            return Some(LIKED_COLOR);
+        }
+        self.changed_at
+            .filter(|at| now.duration_since(*at) < UNLIKE_FLASH)
+            .map(|_| UNLIKED_COLOR)
+    }
+}
+
+// looks up whether each settled track is saved, and saves or removes the
+// current one when anything is sent on the returned channel. only spotify
+// has a library, with MPRIS nothing is ever liked
+pub fn spawn_likes(
+    backend: Backend,
+    mut settled: broadcast::Receiver<SpotifyData>,
+) -> (watch::Receiver<Option<Liked>>, UnboundedSender<()>) {
+    let (liked_tx, liked_rx) = watch::channel(None::<Liked>);
+    let (toggle_tx, mut toggle_rx) = mpsc::unbounded_channel::<()>();
+    tokio::spawn(async move {
+        let Backend::Spotify(spotify) = backend else {
+            while toggle_rx.recv().await.is_some() {
+                eprintln!("liking tracks needs the spotify source");
+            }
+            return;
+        };
+        loop {
+            tokio::select! {
+                track = settled.recv() => match track {
+                    Ok(SpotifyData { track_id: Some(track_id), .. }) => {
+                        let res = async {
+                            player::spotify_ready(&spotify)
+                                .await?
+                                .is_track_saved(&track_id)
+                                .await
+                        }
+                        .await;
+                        match res {
+                            Ok(saved) => {
+                                liked_tx.send_replace(Some(Liked {
+                                    track_id,
+                                    saved,
+                                    changed_at: None,
+                                }));
+                            }
+                            Err(e) => eprintln!("couldn't check the library: {:?}", e),
+                        }
+                    }
+                    Ok(_) => {
+                        liked_tx.send_replace(None);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                toggle = toggle_rx.recv() => {
+                    if toggle.is_none() {
+                        break;
+                    }
+                    let Some(current) = liked_tx.borrow().clone() else {
+                        eprintln!("nothing playing to like");
+                        continue;
+                    };
+                    let res = async {
+                        let spotify = player::spotify_ready(&spotify).await?;
+                        if current.saved {
+                            spotify.remove_saved_track(&current.track_id).await
+                        } else {
+                            spotify.save_track(&current.track_id).await
+                        }
+                    }
+                    .await;
+                    match res {
+                        Ok(()) => {
+                            liked_tx.send_replace(Some(Liked {
+                                saved: !current.saved,
+                                changed_at: Some(Instant::now()),
+                                ..current
+                            }));
+                        }
+                        Err(e) => eprintln!("couldn't change the library: {:?}", e),
+                    }
+                }
+            }
+        }
+    });
+    (liked_rx, toggle_tx)
+}
+
+#[test]
+fn test_heart_follows_the_track() {
+    let now = Instant::now();
+    let mut liked = Liked {
+        track_id: String::from("a"),
+        saved: true,
+        changed_at: None,
+    };
+    assert_eq!(liked.heart(Some("a"), now), Some(LIKED_COLOR));
+    assert_eq!(liked.heart(Some("b"), now), None);
+    assert_eq!(liked.heart(None, now), None);
+
+    liked.saved = false;
+    assert_eq!(liked.heart(Some("a"), now), None);
+    liked.changed_at = Some(now);
+    assert_eq!(
+        liked.heart(Some("a"), now + Duration::from_secs(1)),
+        Some(UNLIKED_COLOR)
+    );
+    assert_eq!(liked.heart(Some("a"), now + UNLIKE_FLASH), None);
+}
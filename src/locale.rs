@@ -0,0 +1,76 @@
+use chrono::{DateTime, TimeZone};
+use num_format::ToFormattedString;
+
+// numbers and dates written the way the user's locale writes them, for the
+// stats pages
+pub struct Formatter {
+    numbers: num_format::Locale,
+    dates: chrono::Locale,
+}
+
+// LC_ALL wins over the category's own variable, which wins over LANG
+fn env_locale(category: &str) -> Option<String> {
+    ["LC_ALL", category, "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+// "de_DE.UTF-8" or "sr_RS@latin" to "de_DE" and "sr_RS"
+fn strip_modifiers(name: &str) -> &str {
+    name.split(['.', '@']).next().unwrap_or_default()
+}
+
+// num-format knows some regions and not others, so fall back to the
+// language alone, and plain english for "C", "POSIX" and anything unknown
+fn number_locale(name: &str) -> num_format::Locale {
+    let name = strip_modifiers(name).replace('_', "-");
+    let language = name.split('-').next().unwrap_or_default();
+    num_format::Locale::from_name(&name)
+        .or_else(|_| num_format::Locale::from_name(language))
+        .unwrap_or(num_format::Locale::en)
+}
+
+fn date_locale(name: &str) -> chrono::Locale {
+    chrono::Locale::try_from(strip_modifiers(name)).unwrap_or(chrono::Locale::POSIX)
+}
+
+impl Formatter {
+    // the configured locale, e.g. "de_DE", otherwise the environment's
+    pub fn new(configured: Option<&str>) -> Self {
+        let name = |category| {
+            configured
+                .map(String::from)
+                .or_else(|| env_locale(category))
+                .unwrap_or_default()
+        };
+        Formatter {
+            numbers: number_locale(&name("LC_NUMERIC")),
+            dates: date_locale(&name("LC_TIME")),
+        }
+    }
+
+    pub fn number(&self, n: u64) -> String {
+        n.to_formatted_string(&self.numbers)
+    }
+
+    pub fn date<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        at.format_localized("%x", self.dates).to_string()
+    }
+}
+
+#[test]
+fn test_formats_follow_locale() {
+    let at = chrono::Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+
+    let german = Formatter::new(Some("de_DE.UTF-8"));
+    assert_eq!(german.number(1_234_567), "1.234.567");
+    assert_eq!(german.date(&at), "01.03.2024");
+
+    let plain = Formatter::new(Some("C"));
+    assert_eq!(plain.number(1_234_567), "1,234,567");
+    assert_eq!(plain.date(&at), "03/01/24");
+}
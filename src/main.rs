@@ -1,4 +1,3 @@
-use display_info::DisplayInfo;
 use regex::Regex;
 use std::io::Read;
 use std::sync::Arc;
@@ -6,20 +5,102 @@ use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
 use tokio::sync::Mutex;
 use webbrowser;
 use wgpu::util::DeviceExt;
 use wgpu::{Instance, Surface};
-use wgpu_text::glyph_brush::ab_glyph::FontRef;
-use wgpu_text::glyph_brush::{OwnedSection, Section as TextSection, Text};
+use wgpu_text::glyph_brush::ab_glyph::{Font, FontRef};
+use wgpu_text::glyph_brush::{FontId, OwnedSection, Section as TextSection, Text};
 use wgpu_text::TextBrush;
 use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event::{KeyEvent, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::keyboard::{Key, NamedKey};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
 use winit::window::{Window, WindowId, WindowLevel};
 
+mod aggregator;
+mod art;
+mod audit;
+mod automation;
+mod bio;
+mod capture;
+mod clock;
+mod config;
+mod cover;
+mod ctl;
+mod device;
+mod enrich;
+mod features;
+mod focus;
+mod font;
+mod fuzzy;
+mod gesture;
+mod gpu;
+mod headless;
+mod history;
+mod history_store;
+mod http;
+mod input;
+mod kiosk;
+mod layout;
+mod like;
+mod locale;
+mod mpris;
+mod musicbrainz;
+mod now_json;
+mod player;
+mod playlist;
+mod positioning;
+mod preview;
+mod privacy;
+mod quad;
+mod queue;
+mod rating;
+mod recap;
+mod romanize;
+mod scrobble;
+mod search;
+mod secrets;
+mod setup;
+mod sleep;
 mod spotify;
+mod state;
+mod status;
+mod template;
+mod tempo;
+mod theme;
+mod toast;
+mod watchdog;
+mod webhook;
+mod workspaces;
+mod x11;
+
+use player::PlayerCommand;
+
+// which backend a track came from
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    #[default]
+    Spotify,
+    Mpris,
+}
+
+impl Source {
+    fn badge_color(&self) -> [f32; 4] {
+        match self {
+            Source::Spotify => [0.11, 0.73, 0.33, 1.0],
+            Source::Mpris => [0.6, 0.6, 0.6, 1.0],
+        }
+    }
+}
+
+// stands in for the titles of explicit tracks with hide_explicit
+const HIDDEN_EXPLICIT_TITLE: &str = "explicit track";
 
 #[derive(Clone, Debug, Default)]
 struct SpotifyData {
@@ -27,6 +108,176 @@ struct SpotifyData {
     pub artist_name: String,
     pub album_name: String,
     pub album_art_url: String,
+    pub is_playing: bool,
+    pub progress_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub source: Source,
+    // spotify track id, when the source knows it
+    pub track_id: Option<String>,
+    // when the source says playback state last changed, in unix ms
+    pub timestamp: Option<u64>,
+    // None when the source doesn't say
+    pub shuffle: Option<bool>,
+    pub repeat: Option<spotify::RepeatState>,
+    // the playlist, album or artist being played from, spotify only
+    pub context_uri: Option<String>,
+    pub context_name: Option<String>,
+    // spotify is in a private session, so it won't say what's playing
+    pub private_session: bool,
+    // spotify marks it explicit
+    pub explicit: bool,
+    // where it comes on its album, and how many tracks the album has. the
+    // track number counts from the start of its disc
+    pub album_id: Option<String>,
+    pub disc_number: Option<u32>,
+    pub track_number: Option<u32>,
+    pub total_tracks: Option<u32>,
+    pub release: Option<spotify::ReleaseDate>,
+}
+
+impl SpotifyData {
+    // track identity, ignoring anything that changes while a track plays
+    fn same_track(&self, other: &SpotifyData) -> bool {
+        self.track_name == other.track_name
+            && self.artist_name == other.artist_name
+            && self.album_name == other.album_name
+    }
+
+    // whether a poll shows the same playback state as an earlier one. spotify
+    // stamps every play, pause, skip and seek, so equal stamps mean nothing
+    // happened. other sources fall back to comparing what they sent
+    fn unchanged_since(&self, earlier: &SpotifyData) -> bool {
+        match (self.timestamp, earlier.timestamp) {
+            (Some(now), Some(then)) => now == then,
+            _ => {
+                self.same_track(earlier)
+                    && self.is_playing == earlier.is_playing
+                    && self.progress_ms == earlier.progress_ms
+            }
+        }
+    }
+
+    // playback position extrapolated from when the data was fetched. None
+    // when the source hasn't told us where playback is
+    fn played_ms(&self, since_fetch: Duration) -> Option<u64> {
+        let progress_ms = self.progress_ms?;
+        if self.is_playing {
+            Some(progress_ms + since_fetch.as_millis() as u64)
+        } else {
+            Some(progress_ms)
+        }
+    }
+
+    // with the title left out if it's explicit and those are hidden. done
+    // where tracks are published, see aggregator, so every output gets it
+    pub fn hiding_explicit(self, hide_explicit: bool) -> Self {
+        if !(hide_explicit && self.explicit) {
+            return self;
+        }
+        SpotifyData {
+            track_name: String::from(HIDDEN_EXPLICIT_TITLE),
+            ..self
+        }
+    }
+
+    // whether its album is what's playing, and not shuffled, so the tracks
+    // come in the album's order
+    fn in_album_order(&self) -> bool {
+        let album = self
+            .album_id
+            .as_ref()
+            .map(|id| format!("spotify:album:{}", id));
+        album.is_some() && self.context_uri == album && self.shuffle != Some(true)
+    }
+
+    // fraction of the track played
+    fn progress(&self, since_fetch: Duration) -> Option<f32> {
+        let duration_ms = self.duration_ms.filter(|duration_ms| *duration_ms > 0)?;
+        let played_ms = self.played_ms(since_fetch)?;
+        Some((played_ms as f32 / duration_ms as f32).min(1.0))
+    }
+}
+
+impl SpotifyData {
+    // the track part, which both of spotify's playback endpoints send alike.
+    // an episode shows as its title by its show, from the publisher. it has
+    // no track id, which keeps liking, favorites and audio features to tracks
+    fn from_item(item: Option<spotify::PlayableItem>, progress_ms: Option<u64>) -> Self {
+        match item {
+            Some(spotify::PlayableItem::Track(track)) => SpotifyData {
+                track_id: track.id,
+                track_name: track.name,
+                release: track.album.release(),
+                artist_name: track
+                    .artists
+                    .first()
+                    .or(track.album.artists.first())
+                    .map(|artist| artist.name.clone())
+                    .unwrap_or_default(),
+                album_art_url: track.album.cover_url().unwrap_or_default().to_owned(),
+                album_name: track.album.name,
+                progress_ms,
+                duration_ms: Some(track.duration_ms as u64),
+                explicit: track.explicit,
+                album_id: track.album.id,
+                disc_number: track.disc_number.filter(|number| *number > 0),
+                track_number: track.track_number.filter(|number| *number > 0),
+                total_tracks: track.album.total_tracks,
+                ..Default::default()
+            },
+            Some(spotify::PlayableItem::Episode(episode)) => SpotifyData {
+                track_name: episode.name,
+                artist_name: episode.show.name,
+                album_name: episode.show.publisher,
+                progress_ms,
+                duration_ms: Some(episode.duration_ms as u64),
+                explicit: episode.explicit,
+                ..Default::default()
+            },
+            None => SpotifyData::default(),
+        }
+    }
+}
+
+impl From<spotify::CurrentlyPlayingResponse> for SpotifyData {
+    fn from(res: spotify::CurrentlyPlayingResponse) -> Self {
+        SpotifyData {
+            is_playing: res.is_playing,
+            timestamp: Some(res.timestamp),
+            shuffle: res.shuffle_state,
+            repeat: res.repeat_state,
+            context_uri: res.context.map(|context| context.uri),
+            ..SpotifyData::from_item(res.item, Some(res.progress_ms as u64))
+        }
+    }
+}
+
+impl From<spotify::CurrentlyPlaying> for SpotifyData {
+    fn from(playing: spotify::CurrentlyPlaying) -> Self {
+        match playing {
+            spotify::CurrentlyPlaying::Playing(res) => SpotifyData::from(*res),
+            spotify::CurrentlyPlaying::NothingPlaying => SpotifyData::default(),
+            spotify::CurrentlyPlaying::PrivateSession => SpotifyData {
+                private_session: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// the context's name isn't in the player state, it's looked up separately
+impl From<spotify::PlaybackStateResponse> for SpotifyData {
+    fn from(res: spotify::PlaybackStateResponse) -> Self {
+        SpotifyData {
+            is_playing: res.is_playing,
+            timestamp: Some(res.timestamp),
+            shuffle: Some(res.shuffle_state),
+            repeat: Some(res.repeat_state),
+            context_uri: res.context.map(|context| context.uri),
+            private_session: res.device.is_some_and(|device| device.is_private_session),
+            ..SpotifyData::from_item(res.item, res.progress_ms)
+        }
+    }
 }
 
 #[repr(C)]
@@ -77,68 +328,94 @@ const VERTICES: &[Vertex] = &[
 
 const INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
 
+// everything the shader reads that's shared by the whole frame, written once
+// per frame. laid out to match the WGSL struct's uniform alignment
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
-// bytemuck::Pod, bytemuck::Zeroable)]
-struct TimerUniform {
-    t: f32,
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    // animation time in seconds
+    time: f32,
+    // fraction of the track played, negative while it's unknown
+    progress: f32,
+    // surface size in physical pixels
+    resolution: [f32; 2],
+    accent: [f32; 4],
+    // in physical pixels
+    corner_radius: f32,
+    opacity: f32,
+    // see theme::AccentEffect
+    accent_effect: u32,
+    // in seconds
+    accent_period: f32,
+    palette: [[f32; 4]; theme::PALETTE_SIZE],
+    palette_len: u32,
+    // the track's bpm and energy, for the pulse effect. zero while unknown
+    tempo: f32,
+    energy: f32,
+    // seconds into the track, carried on between polls, which the pulse
+    // counts beats from
+    position: f32,
 }
-#[repr(C)]
-struct Timer {
-    start: std::time::Instant,
-    elapsed: f64,
-    last: f64,
-    acc: f64,
-    timer_uniform: TimerUniform,
-    timer_buffer: wgpu::Buffer,
-    timer_bind_group: wgpu::BindGroup,
-    timer_bind_group_layout: wgpu::BindGroupLayout,
+struct Uniforms {
+    clock: clock::AnimationClock,
+    globals: Globals,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
 }
-impl Timer {
+impl Uniforms {
     fn new(device: &wgpu::Device) -> Self {
-        let mut timer_uniform = TimerUniform { t: 0.2 };
-        let timer_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Timer Buffer"),
-            contents: &timer_uniform.t.to_le_bytes(),
+        let globals = Globals {
+            time: 0.2,
+            progress: -1.0,
+            resolution: [WIDTH as f32, HEIGHT as f32],
+            accent: [0.9, 1.0, 1.0, 1.0],
+            corner_radius: 0.0,
+            opacity: 1.0,
+            accent_effect: 0,
+            accent_period: 1.0,
+            palette: [[0.0; 4]; theme::PALETTE_SIZE],
+            palette_len: 0,
+            tempo: 0.0,
+            energy: 0.0,
+            position: 0.0,
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Globals Buffer"),
+            contents: bytemuck::cast_slice(&[globals]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let timer_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("bind_group_for_timer_uniform"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_for_globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
 
-                    count: None,
-                }],
-            });
+                count: None,
+            }],
+        });
 
-        let timer_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
-            layout: &timer_bind_group_layout,
+            layout: &bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: timer_buffer.as_entire_binding(),
+                resource: buffer.as_entire_binding(),
             }],
         });
 
-        let start = std::time::Instant::now();
-
-        Timer {
-            start,
-            elapsed: 0.0,
-            last: 0.0,
-            acc: 0.0f64,
-            timer_uniform,
-            timer_buffer,
-            timer_bind_group,
-            timer_bind_group_layout,
+        Uniforms {
+            clock: clock::AnimationClock::new(std::time::Instant::now()),
+            globals,
+            buffer,
+            bind_group,
+            bind_group_layout,
         }
     }
 }
@@ -150,19 +427,100 @@ struct App {
     instance: Option<Instance>,
     // surface for drawing
     surface: Option<Surface<'static>>,
+    surface_config: Option<wgpu::SurfaceConfiguration>,
     device: Option<wgpu::Device>,
     queue: Option<wgpu::Queue>,
+    // what frames are drawn in, TEXTURE_FORMAT unless the surface lacks it
+    frame_format: Option<wgpu::TextureFormat>,
+    // whether text can be drawn through its own view of the frame
+    text_views: bool,
 
     vertex_buffer: Option<wgpu::Buffer>,
     index_buffer: Option<wgpu::Buffer>,
-    timer: Option<Timer>,
+    uniforms: Option<Uniforms>,
+    quads: Option<quad::QuadBatch>,
+    cover: Option<cover::CoverTexture>,
 
     brush: Option<TextBrush<FontRef<'static>>>,
-    text_section: Option<OwnedSection>,
+    text_sections: Vec<OwnedSection>,
+    line_metrics: layout::LineMetrics,
+    // small status glyphs in the top right corner
+    indicator_section: Option<OwnedSection>,
 
     render_pipeline: Option<wgpu::RenderPipeline>,
 
+    config: config::Config,
+    ui_state: state::UiState,
+    privacy: Arc<privacy::Privacy>,
+    layout: layout::WidgetLayout,
+    themes: theme::Themes,
+    // true while an app listed in hide_when_focused has focus
+    focus_hidden: Option<watch::Receiver<bool>>,
+    // commands from `kyomi ctl`
+    ctl: Option<UnboundedReceiver<ctl::CtlRequest>>,
+    // the now playing page, parsed from the config
+    line_templates: Vec<template::Template>,
+    expanded_templates: Vec<template::Template>,
+
     spotify_data: Option<SpotifyData>,
+    spotify_data_fetched: Option<std::time::Instant>,
+    // the latest poll from the aggregator, None when it failed. spotify_data
+    // follows it so song changes show up live
+    polls: Option<watch::Receiver<Option<SpotifyData>>>,
+
+    controls: Option<UnboundedSender<PlayerCommand>>,
+    // the connect device playback is on, kept current in the background
+    devices: Option<watch::Receiver<device::Devices>>,
+    // whether the connect_target device was the active one last time
+    on_connect_target: bool,
+    // opened by resting the cursor on the artist, if enabled
+    hover_card: Option<bio::HoverCard>,
+    // where each element was last laid out or drawn, in logical pixels
+    hit_regions: layout::HitRegions,
+    // musicbrainz metadata or the album label for the last settled track, if
+    // either is enabled
+    enrichment: Option<watch::Receiver<Option<(SpotifyData, enrich::Enrichment)>>>,
+    // the settled track's cover, when album_art is on
+    album_art: Option<watch::Receiver<cover::TrackArt>>,
+    // the settled track's tempo, energy and loudness, from spotify
+    audio_features: Option<watch::Receiver<features::TrackFeatures>>,
+    // while the tap tempo tool is showing
+    tap_tempo: Option<tempo::TapTempo>,
+    // up next, under the current track when queue_preview is on
+    up_next: Option<watch::Receiver<queue::Upcoming>>,
+    queue_adds: Option<UnboundedSender<String>>,
+    // whether the track is in the spotify library, and where to ask for it
+    // to be liked or unliked
+    liked: Option<watch::Receiver<Option<like::Liked>>>,
+    like_toggles: Option<UnboundedSender<()>>,
+    // while the quick search launcher is open
+    launcher: Option<search::Launcher>,
+    searches: Option<UnboundedSender<search::Request>>,
+    // starts the focus timer on true and stops it on false
+    focus_timer: Option<UnboundedSender<bool>>,
+    sleep_timer: Option<UnboundedSender<Option<Duration>>>,
+    search_hits: Option<watch::Receiver<Vec<search::Hit>>>,
+    // lines for the recap page, when it's on
+    recap: Option<watch::Receiver<Vec<String>>>,
+    // from the focus timer, and the playback watchdog, the skip streak hint
+    // and the up next toast, whichever are on
+    toasts: Vec<watch::Receiver<Option<toast::Toast>>>,
+    modifiers: ModifiersState,
+    // the interact modifier keys held right now, left and right tracked apart
+    held_interact_keys: Vec<winit::keyboard::KeyCode>,
+    click_through: bool,
+    gestures: gesture::Recognizer,
+    touches: gesture::TouchTracker,
+    wheel: gesture::WheelVolume,
+    // frames streamed to a virtual camera or similar, if configured
+    frame_output: Option<capture::FrameOutput>,
+    // full screen with big text and a clock, see kiosk.rs
+    kiosk: bool,
+    // last cursor position over the widget, in logical pixels
+    cursor: (f32, f32),
+    // what the last press or touch began on, and where, for gestures that
+    // act on the element under them
+    pressed_on: Option<(layout::Element, (f32, f32))>,
 }
 
 struct Pipeline {
@@ -170,59 +528,60 @@ struct Pipeline {
 }
 
 const WIDTH: u32 = 256;
+// progress bar along the bottom edge, as a fraction of the height
+const BAR_HEIGHT: f32 = 0.04;
 const HEIGHT: u32 = 128;
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let mut x = 0;
-        let mut y = 0;
-        let display_infos = DisplayInfo::all().unwrap();
-        for display_info in display_infos {
-            if display_info.is_primary {
-                x = display_info.width - WIDTH;
-                y = display_info.height - HEIGHT;
-                break;
-            }
+        let mut attributes = Window::default_attributes()
+            .with_decorations(false)
+            .with_title(workspaces::WINDOW_TITLE)
+            .with_inner_size(winit::dpi::LogicalSize::new(WIDTH, HEIGHT))
+            .with_transparent(true)
+            .with_window_level(WindowLevel::AlwaysOnTop);
+        if self.kiosk {
+            attributes = kiosk::with_kiosk(attributes);
+        } else {
+            attributes = x11::with_hints(attributes, &self.config);
+        }
+        if let Some(placement) = positioning::place_widget(
+            event_loop,
+            winit::dpi::LogicalSize::new(WIDTH, HEIGHT),
+            winit::dpi::LogicalSize::new(self.config.margin_x, self.config.margin_y),
+        )
+        .filter(|_| !self.kiosk)
+        {
+            attributes = attributes
+                .with_inner_size(placement.size)
+                .with_position(placement.position);
         }
 
-        self.window = Some(Arc::new(
-            event_loop
-                .create_window(
-                    Window::default_attributes()
-                        .with_decorations(false)
-                        .with_inner_size(winit::dpi::LogicalSize::new(WIDTH, HEIGHT))
-                        .with_position(winit::dpi::LogicalPosition::new(x, y))
-                        .with_transparent(true)
-                        .with_window_level(WindowLevel::AlwaysOnTop),
-                )
-                .unwrap(),
-        ));
+        self.window = Some(Arc::new(event_loop.create_window(attributes).unwrap()));
+        if self.ui_state.hidden {
+            self.window.as_ref().unwrap().set_minimized(true);
+        }
+        if self.kiosk {
+            self.window.as_ref().unwrap().set_cursor_visible(false);
+        }
+        if self.config.sticky {
+            workspaces::show_on_all_workspaces(self.window.as_ref().unwrap());
+        }
+        if self.config.interact_modifier.is_some() {
+            self.set_click_through(true);
+        }
 
-        self.instance = Some(Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            flags: wgpu::InstanceFlags::empty(),
-            ..Default::default()
-        }));
-        self.surface = Some(
-            self.instance
-                .as_ref()
-                .unwrap()
-                .create_surface(self.window.clone().unwrap())
-                .unwrap(),
-        );
-        let adapter = pollster::block_on(self.instance.as_ref().unwrap().request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: self.surface.as_ref(),
-                force_fallback_adapter: false,
-            },
-        ))
-        .unwrap();
+        let (instance, surface, adapter) =
+            gpu::connect(self.window.clone().unwrap()).expect("no graphics adapter");
+        eprintln!("drawing with {:?}", adapter.get_info().backend);
+        self.instance = Some(instance);
+        self.surface = Some(surface);
         let device_queue = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("device-descriptor"),
                 required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
+                required_limits: gpu::required_limits(&adapter),
                 ..Default::default()
             },
             None,
@@ -232,48 +591,291 @@ impl ApplicationHandler for App {
         self.device = Some(device_queue.0);
         self.queue = Some(device_queue.1);
 
-        let texture_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-
+        let capabilities = self.surface.as_ref().unwrap().get_capabilities(&adapter);
+        let format = gpu::surface_format(&capabilities, TEXTURE_FORMAT);
+        self.frame_format = Some(format);
+        self.text_views = gpu::supports_text_views(&adapter, true);
         let size = self.window.as_ref().unwrap().inner_size();
+        // laid out now rather than on the first resize or redraw, so presses
+        // before then land
+        let logical: LogicalSize<f32> = size.to_logical(self.scale_factor());
+        self.layout.size = (logical.width, logical.height);
+        self.lay_out();
+        let (width, height) = gpu::clamp_size(
+            size.width,
+            size.height,
+            &self.device.as_ref().unwrap().limits(),
+        );
+        self.surface_config = Some(wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 1,
+            alpha_mode: gpu::alpha_mode(&capabilities),
+            // alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: if self.text_views {
+                vec![format.add_srgb_suffix(), format.remove_srgb_suffix()]
+            } else {
+                Vec::new()
+            },
+        });
         self.surface.as_ref().unwrap().configure(
             &self.device.as_ref().unwrap(),
-            &wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                // not really sure what the TextureFormat is
-                format: texture_format,
-                width: size.width,
-                height: size.height,
-                present_mode: wgpu::PresentMode::Fifo,
-                desired_maximum_frame_latency: 1,
-                alpha_mode: wgpu::CompositeAlphaMode::PostMultiplied,
-                // alpha_mode: wgpu::CompositeAlphaMode::Opaque,
-                view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
-            },
+            self.surface_config.as_ref().unwrap(),
         );
 
+        self.init_renderer(size);
+
+        // initial redraw request
+        self.window.as_ref().unwrap().request_redraw();
+    }
+
+    // a hidden window gets no redraws, so the focus check can't live there
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        // answered while hidden as well
+        self.handle_ctl();
+        if let Some(gesture) = self.gestures.poll(std::time::Instant::now()) {
+            self.apply_gesture(gesture);
+        }
+        if let Some(command) = self.wheel.poll(std::time::Instant::now()) {
+            if let Some(controls) = self.controls.as_ref() {
+                let _ = controls.send(command);
+            }
+        }
+
+        let (Some(focus_hidden), Some(window)) = (self.focus_hidden.as_mut(), self.window.as_ref())
+        else {
+            return;
+        };
+        if !focus_hidden.has_changed().unwrap_or(false) {
+            return;
+        }
+        let hide = *focus_hidden.borrow_and_update();
+        window.set_visible(!hide);
+        if let Some(uniforms) = self.uniforms.as_mut() {
+            if hide {
+                uniforms.clock.pause();
+            } else {
+                uniforms.clock.resume(std::time::Instant::now());
+            }
+        }
+    }
+
+    // a click-through window never has focus, so the modifier is watched
+    // through raw keyboard events instead of window ones
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: winit::event::DeviceEvent,
+    ) {
+        let Some(modifier) = self.config.interact_modifier else {
+            return;
+        };
+        let winit::event::DeviceEvent::Key(winit::event::RawKeyEvent {
+            physical_key: winit::keyboard::PhysicalKey::Code(code),
+            state,
+        }) = event
+        else {
+            return;
+        };
+        if !modifier.matches(code) {
+            return;
+        }
+        self.held_interact_keys.retain(|held| *held != code);
+        if state == winit::event::ElementState::Pressed {
+            self.held_interact_keys.push(code);
+        }
+        self.set_click_through(self.held_interact_keys.is_empty());
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        match event {
+            // typing goes to the launcher while it's open, escape included
+            WindowEvent::KeyboardInput { event, .. } if self.launcher.is_some() => {
+                self.launcher_key(event)
+            }
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: winit::event::ElementState::Pressed,
+                        logical_key: Key::Named(NamedKey::Escape),
+                        ..
+                    },
+                ..
+            } => {
+                eprintln!("The close button was pressed; stopping");
+                event_loop.exit();
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: winit::event::ElementState::Pressed,
+                        logical_key,
+                        physical_key,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(action) = input::ui_action_for_key(&logical_key) {
+                    self.apply_ui_action(action);
+                } else if let Some(command) =
+                    input::command_for_key(&logical_key, physical_key, self.modifiers)
+                {
+                    if let Some(controls) = self.controls.as_ref() {
+                        let _ = controls.send(command);
+                    }
+                }
+            }
+            WindowEvent::Resized(size) => self.resize(size),
+            WindowEvent::CursorMoved { position, .. } => {
+                let scale_factor = self.window.as_ref().unwrap().scale_factor();
+                let position = position.to_logical::<f32>(scale_factor);
+                self.cursor = (position.x, position.y);
+                self.gestures.moved(self.cursor);
+                let over_artist = self.hit_regions.at(self.cursor) == Some(layout::Element::Artist);
+                if let Some(hover_card) = self.hover_card.as_mut() {
+                    hover_card.hover(over_artist, std::time::Instant::now());
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                if let Some(hover_card) = self.hover_card.as_mut() {
+                    hover_card.leave();
+                }
+            }
+            // presses in the corners outside the outline are ignored
+            WindowEvent::MouseInput {
+                state: winit::event::ElementState::Pressed,
+                ..
+            } if !self.over_widget(self.cursor) => {}
+            WindowEvent::MouseInput {
+                state: winit::event::ElementState::Pressed,
+                button: winit::event::MouseButton::Left,
+                ..
+            } if self.idle_device().is_some() => {
+                if let Some(controls) = self.controls.as_ref() {
+                    let _ = controls.send(PlayerCommand::ResumeLastDevice);
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: winit::event::MouseButton::Left,
+                ..
+            } => {
+                let now = std::time::Instant::now();
+                if state == winit::event::ElementState::Pressed {
+                    self.pressed_on = self.hit_regions.at(self.cursor).zip(Some(self.cursor));
+                    self.gestures.press(self.cursor, now);
+                } else if let Some(gesture) = self.gestures.release(self.cursor, now) {
+                    self.apply_gesture(gesture);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.over_widget(self.cursor) => {
+                let volume = self.devices.as_ref().and_then(|rx| {
+                    rx.borrow()
+                        .active
+                        .as_ref()
+                        .and_then(|device| device.volume_percent)
+                });
+                self.wheel
+                    .scrolled(delta, volume, std::time::Instant::now());
+            }
+            WindowEvent::Touch(touch) => {
+                let scale_factor = self.window.as_ref().unwrap().scale_factor();
+                let position = touch.location.to_logical::<f32>(scale_factor);
+                let position = (position.x, position.y);
+                match touch.phase {
+                    winit::event::TouchPhase::Started if self.over_widget(position) => {
+                        self.pressed_on = self.hit_regions.at(position).zip(Some(position));
+                        self.touches.start(touch.id, position)
+                    }
+                    winit::event::TouchPhase::Ended => {
+                        if let Some(touch) = self.touches.end(touch.id, position) {
+                            self.apply_touch(touch);
+                        }
+                    }
+                    winit::event::TouchPhase::Cancelled => self.touches.cancel(touch.id),
+                    winit::event::TouchPhase::Started | winit::event::TouchPhase::Moved => {}
+                }
+            }
+            // restored from the taskbar after being hidden
+            WindowEvent::Focused(true) if self.ui_state.hidden => {
+                self.ui_state.hidden = false;
+                self.ui_state.save();
+                if let Some(uniforms) = self.uniforms.as_mut() {
+                    uniforms.clock.resume(std::time::Instant::now());
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                let output = self
+                    .surface
+                    .as_ref()
+                    .unwrap()
+                    .get_current_texture()
+                    .unwrap();
+                self.render(&output.texture);
+                output.present();
+                self.capture_frame();
+                self.window.as_ref().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    }
+}
+impl App {
+    // everything drawing needs besides somewhere to draw to, shared by the
+    // window and offscreen rendering
+    fn init_renderer(&mut self, size: PhysicalSize<u32>) {
         /////// brush stuff
-        let font = include_bytes!("../fonts/Fira_Code_v6.2/ttf/FiraCode-Light.ttf") as &[u8];
+        let font = font::load(self.config.font.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let mut fonts = vec![FontRef::try_from_slice(font).unwrap()];
+        let fallback = font::load_fallback(
+            self.config.fallback_font.as_deref(),
+            self.config.metadata_language.as_deref(),
+        );
+        match fallback.map(FontRef::try_from_slice) {
+            Some(Ok(fallback)) => fonts.push(fallback),
+            Some(Err(e)) => eprintln!("couldn't load the fallback font: {}", e),
+            None => (),
+        }
         self.brush = Some(
-            wgpu_text::BrushBuilder::using_font_bytes(font)
-                .unwrap()
-                .build(self.device.as_ref().unwrap(), WIDTH, HEIGHT, texture_format),
+            wgpu_text::BrushBuilder::using_fonts(fonts)
+                .draw_cache_position_tolerance(self.config.text_hinting.position_tolerance())
+                .build(
+                    self.device.as_ref().unwrap(),
+                    WIDTH,
+                    HEIGHT,
+                    self.text_format(),
+                ),
         );
 
-        self.text_section = Some(
-            TextSection::default()
-                .add_text(Text::new("Hello!").with_color([0.9, 1.0, 1.0, 1.0]))
-                .with_bounds((WIDTH as f32, HEIGHT as f32))
-                .with_layout(
-                    wgpu_text::glyph_brush::Layout::default()
-                        .v_align(wgpu_text::glyph_brush::VerticalAlign::Center),
-                )
-                .with_screen_position((10.0, 10.0))
-                .to_owned(),
+        self.line_metrics = layout::LineMetrics::from_font(
+            &self.brush.as_ref().unwrap().fonts()[0],
+            self.text_size(),
         );
+        self.resize(size);
         ////
 
         //// uniform buffer
-        self.timer = Some(Timer::new(self.device.as_ref().unwrap()));
+        self.uniforms = Some(Uniforms::new(self.device.as_ref().unwrap()));
+        if self.ui_state.hidden {
+            self.uniforms.as_mut().unwrap().clock.pause();
+        }
+        self.quads = Some(quad::QuadBatch::new(self.device.as_ref().unwrap()));
+        self.cover = Some(cover::CoverTexture::new(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+        ));
 
         ///// shader time
         let shader =
@@ -290,7 +892,11 @@ impl ApplicationHandler for App {
                 .unwrap()
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &[&self.timer.as_ref().unwrap().timer_bind_group_layout],
+                    bind_group_layouts: &[
+                        &self.uniforms.as_ref().unwrap().bind_group_layout,
+                        &self.quads.as_ref().unwrap().bind_group_layout,
+                        &self.cover.as_ref().unwrap().bind_group_layout,
+                    ],
                     push_constant_ranges: &[],
                 });
 
@@ -310,7 +916,6 @@ impl ApplicationHandler for App {
                 usage: wgpu::BufferUsages::INDEX,
             },
         ));
-        let num_indices = INDICES.len() as u32;
 
         // render pipelinne
         self.render_pipeline = Some(self.device.as_ref().unwrap().create_render_pipeline(
@@ -327,7 +932,7 @@ impl ApplicationHandler for App {
                     module: &shader,
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: texture_format,
+                        format: self.frame_format(),
                         blend: Some(wgpu::BlendState::REPLACE),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
@@ -355,236 +960,1194 @@ impl ApplicationHandler for App {
                 cache: None,
             },
         ));
-
-        // initial redraw request
-        self.window.as_ref().unwrap().request_redraw();
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
-        match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state: winit::event::ElementState::Pressed,
-                        logical_key: Key::Named(NamedKey::Escape),
-                        ..
-                    },
-                ..
-            } => {
-                println!("The close button was pressed; stopping");
-                event_loop.exit();
-            }
-            WindowEvent::RedrawRequested => {
-                self.update();
-                let output = self
-                    .surface
-                    .as_ref()
-                    .unwrap()
-                    .get_current_texture()
-                    .unwrap();
-
-                let view = output
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-                let mut encoder = self.device.as_ref().unwrap().create_command_encoder(
-                    &wgpu::CommandEncoderDescriptor {
-                        label: Some("render encoder"),
-                    },
-                );
-
-                // println!("{:?}", self.spotify_data.clone());
-
-                self.text_section = Some(match &self.spotify_data {
-                    Some(data) => TextSection::default()
-                        .add_text(
-                            Text::new(data.artist_name.as_str()).with_color([0.9, 1.0, 1.0, 1.0]),
-                        )
-                        .with_bounds((WIDTH as f32, HEIGHT as f32))
-                        .with_layout(
-                            wgpu_text::glyph_brush::Layout::default()
-                                .v_align(wgpu_text::glyph_brush::VerticalAlign::Center),
-                        )
-                        .with_screen_position((10.0, 10.0))
-                        .to_owned(),
-                    None => TextSection::default()
-                        .add_text(Text::new("test!").with_color([0.9, 1.0, 1.0, 1.0]))
-                        .with_bounds((WIDTH as f32, HEIGHT as f32))
-                        .with_layout(
-                            wgpu_text::glyph_brush::Layout::default()
-                                .v_align(wgpu_text::glyph_brush::VerticalAlign::Center),
-                        )
-                        .with_screen_position((10.0, 10.0))
-                        .to_owned(),
+    // draws one frame into a texture made with frame_format
+    fn render(&mut self, texture: &wgpu::Texture) {
+        let size = texture.size();
+        self.update(PhysicalSize::new(size.width, size.height));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder =
+            self.device
+                .as_ref()
+                .unwrap()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render encoder"),
                 });
 
-                // text-drawing brush
-                match self.brush.as_mut().unwrap().queue(
-                    self.device.as_ref().unwrap(),
-                    self.queue.as_ref().unwrap(),
-                    [self.text_section.as_ref().unwrap()],
-                ) {
-                    Ok(_) => {}
-                    Err(e) => println!("Brush Error: {:?}", e),
-                }
+        // println!("{:?}", self.spotify_data.clone());
 
-                {
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some("render pass"),
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                        timestamp_writes: None,
-                        occlusion_query_set: None,
-                    });
-
-                    render_pass.set_pipeline(&self.render_pipeline.as_ref().unwrap());
-                    render_pass.set_bind_group(
-                        0,
-                        &self.timer.as_ref().unwrap().timer_bind_group,
-                        &[],
+        // one section per line, each placed on its own baseline
+        let mut lines = self.text_lines();
+        if self.kiosk {
+            lines.push(kiosk::clock_line(chrono::Local::now()));
+        }
+        let positions = self.layout.line_positions(&lines, &self.line_metrics);
+        let [r, g, b] = self.themes.current().text_color;
+        let badged = self.explicit_badge_line();
+        self.text_sections = lines
+            .iter()
+            .zip(positions)
+            .enumerate()
+            .map(|(i, (line, position))| {
+                let mut texts = self.texts(line, [r, g, b, 1.0]);
+                if badged == Some(i) {
+                    let [r, g, b] = self.accent();
+                    texts.push(
+                        Text::new(" E")
+                            .with_color([r, g, b, 1.0])
+                            .with_scale(self.text_size() * 0.7),
                     );
-                    render_pass
-                        .set_vertex_buffer(0, self.vertex_buffer.as_ref().unwrap().slice(..));
-                    render_pass.set_index_buffer(
-                        self.index_buffer.as_ref().unwrap().slice(..),
-                        wgpu::IndexFormat::Uint16,
-                    ); // 1.
-                    render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1); // 2.
-
-                    self.brush.as_ref().unwrap().draw(&mut render_pass);
                 }
-
-                // submit will accept anything that implements IntoIter
-                self.queue
-                    .as_ref()
-                    .unwrap()
-                    .submit(std::iter::once(encoder.finish()));
-                output.present();
-                self.window.as_ref().unwrap().request_redraw();
+                TextSection::default()
+                    .with_text(texts)
+                    .with_bounds(self.layout.text_bounds)
+                    .with_layout(wgpu_text::glyph_brush::Layout::default_single_line())
+                    .with_screen_position(position)
+                    .to_owned()
+            })
+            .collect();
+
+        self.hit_regions = self.outline_regions();
+        if self.shows_artist() {
+            let artist = self.artist_line(&lines);
+            let brush = self.brush.as_mut().unwrap();
+            if let Some(bounds) = brush.glyph_bounds(&self.text_sections[artist].to_borrowed()) {
+                self.hit_regions.insert(layout::Element::Artist, bounds);
             }
-            _ => (),
-        }
-    }
-}
-impl App {
-    fn update(&mut self) {
-        match self.timer.as_mut() {
-            Some(timer) => {
-                let target_fps = 1.0 / 60.0 as f64;
-                timer.elapsed = timer.start.elapsed().as_secs_f64();
-                timer.acc += timer.elapsed - timer.last;
-                timer.last = timer.elapsed;
-                // framerate stuff goes here?
-                timer.timer_uniform.t = timer.elapsed as f32;
-                self.queue.as_ref().unwrap().write_buffer(
-                    &timer.timer_buffer,
-                    0,
-                    &timer.timer_uniform.t.to_le_bytes(),
-                );
+            if let Some(hover_card) = self.hover_card.as_mut() {
+                hover_card.poll(&lines[artist], std::time::Instant::now());
             }
-            None => {}
-        };
-    }
-}
+        }
 
-#[tokio::main]
-async fn main() {
-    // performs auth request
-    let mut spotify = spotify::Spotify::from_client_id(spotify::CLIENT_ID)
-        .with_scope("user-read-private user-read-playback-state user-read-currently-playing")
-        .with_redirect_uri(spotify::REDIRECT_URI);
-    spotify.show_dialog = false;
+        let indicators = self.indicators();
+        self.indicator_section = (!indicators.is_empty()).then(|| {
+            TextSection::default()
+                .with_text(
+                    indicators
+                        .iter()
+                        .flat_map(|(text, color)| self.texts(text, *color))
+                        .collect(),
+                )
+                .with_layout(
+                    wgpu_text::glyph_brush::Layout::default_single_line()
+                        .h_align(wgpu_text::glyph_brush::HorizontalAlign::Right),
+                )
+                .with_screen_position(self.layout.indicator_position)
+                .to_owned()
+        });
 
-    let auth_url = spotify.auth_url();
-    // let mut auth_code_buffer = [0; 512];
-    let auth_code = Arc::new(Mutex::new(String::with_capacity(512)));
-    let thread_auth_code = auth_code.clone();
-
-    // create temp http server for OAuth2 loopback
-    let task = tokio::spawn(async move {
-        let listener = TcpListener::bind("localhost:8000").await.unwrap();
-        println!("listening");
-        // loop {
-        if let Ok((mut socket, addr)) = listener.accept().await {
-            println!("new connection from {}", addr.ip());
-            let thread_auth_code = Arc::clone(&thread_auth_code);
-            tokio::spawn(async move {
-                let mut buffer = [0; 512];
-                if let Ok(n) = socket.read(&mut buffer).await {
-                    if n != 0 {
-                        println!("received: {}", String::from_utf8_lossy(&buffer[..n]));
-                        let received_val = String::from_utf8_lossy(&buffer[..n]).to_string();
-                        let re = Regex::new(r"^GET \/\?code=(.*) HTTP").unwrap();
-                        let caps = re.captures(received_val.as_str()).unwrap();
-
-                        // match String::from_utf16_lossy(&buffer[..n]) {
-                        //     "GET /?code="
-                        // }
-
-                        let mut auth_code = thread_auth_code.lock().await;
-                        println!("captures found:");
-                        for cap in caps.iter() {
-                            println!("{:#?}", cap);
-                        }
-                        *auth_code = caps[1].to_owned();
-                        // String::from_utf8_lossy(&buffer[..n]).to_string();
-
-                        socket
-                            .write_all(b"hello from tokio server\n")
-                            .await
-                            .unwrap();
-                    } else {
-                        println!("didn't receive any data");
-                        socket.write_all(b"hello anyway!\n").await.unwrap();
-                    }
-                }
+        // text-drawing brush
+        match self.brush.as_mut().unwrap().queue(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            self.text_sections
+                .iter()
+                .chain(self.indicator_section.as_ref()),
+        ) {
+            Ok(_) => {}
+            Err(e) => eprintln!("Brush Error: {:?}", e),
+        }
+
+        // quads under the text, the text, then overlays on top. the
+        // text gets its own pass so it can blend through a different
+        // view of the frame, see text_format
+        let quads = self.quads.as_ref().unwrap();
+        let num_indices = INDICES.len() as u32;
+        {
+            let mut render_pass =
+                begin_render_pass(&mut encoder, &view, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+            self.set_quad_pipeline(&mut render_pass);
+            quads.draw(&mut render_pass, 1, num_indices, ..quad::Layer::Overlay);
+        }
+        {
+            let text_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(self.text_format()),
+                ..Default::default()
             });
+            let mut render_pass = begin_render_pass(&mut encoder, &text_view, wgpu::LoadOp::Load);
+            self.brush.as_ref().unwrap().draw(&mut render_pass);
+        }
+        {
+            let mut render_pass = begin_render_pass(&mut encoder, &view, wgpu::LoadOp::Load);
+            self.set_quad_pipeline(&mut render_pass);
+            quads.draw(&mut render_pass, 1, num_indices, quad::Layer::Overlay..);
         }
-        // }
-    });
 
-    println!("outside of loop");
+        // submit will accept anything that implements IntoIter
+        self.queue
+            .as_ref()
+            .unwrap()
+            .submit(std::iter::once(encoder.finish()));
+    }
 
-    webbrowser::open(auth_url.as_str()).unwrap();
-    task.await.unwrap();
+    // a texture render can draw into and read_rgba can read back
+    fn offscreen_texture(&self, size: PhysicalSize<u32>) -> wgpu::Texture {
+        let text_format = self.text_format();
+        let view_formats: &[wgpu::TextureFormat] = if text_format != self.frame_format() {
+            &[text_format]
+        } else {
+            &[]
+        };
+        self.device
+            .as_ref()
+            .unwrap()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("offscreen texture"),
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.frame_format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats,
+            })
+    }
 
-    // wait for auth_code
-    loop {
-        if auth_code.lock().await.len() != 0 {
-            break;
+    // surface textures can't be read back, so the frame is drawn again
+    // offscreen at the same size
+    fn capture_frame(&mut self) {
+        let now = std::time::Instant::now();
+        if !self
+            .frame_output
+            .as_ref()
+            .is_some_and(|output| output.due(now))
+        {
+            return;
+        }
+        let surface_config = self.surface_config.as_ref().unwrap();
+        let size = PhysicalSize::new(surface_config.width, surface_config.height);
+        let texture = self.offscreen_texture(size);
+        self.render(&texture);
+        match preview::read_rgba(self, &texture) {
+            Ok(rgba) => {
+                let output = self.frame_output.as_mut().unwrap();
+                output.send((size.width, size.height), rgba, now);
+            }
+            Err(e) => eprintln!("couldn't capture frame: {:?}", e),
         }
     }
 
-    // println!("auth_code: {:#?}", auth_code.lock().await);
-    spotify.token(&auth_code.lock().await).await.unwrap();
-    let currently_playing_res = spotify.get_currently_playing().await;
+    fn set_quad_pipeline(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, &self.uniforms.as_ref().unwrap().bind_group, &[]);
+        self.cover.as_ref().unwrap().bind(render_pass, 2);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().unwrap().slice(..));
+        render_pass.set_index_buffer(
+            self.index_buffer.as_ref().unwrap().slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+    }
 
-    // match currently_playing_res {
-    //     Ok(res) => {
-    //         println!("{:?}", res.to_string());
-    //     }
+    // an srgb view blends text coverage in linear light, which is correct but
+    // makes small light-on-dark text look thin. the plain view blends in gamma
+    // space like most desktop text. without separate views (GLES) text is
+    // blended however the frame is
+    fn text_format(&self) -> wgpu::TextureFormat {
+        let format = self.frame_format();
+        if !self.text_views {
+            format
+        } else if self.config.gamma_correct_text {
+            format.add_srgb_suffix()
+        } else {
+            format.remove_srgb_suffix()
+        }
+    }
+
+    fn frame_format(&self) -> wgpu::TextureFormat {
+        self.frame_format.unwrap_or(TEXTURE_FORMAT)
+    }
+
+    fn text_size(&self) -> f32 {
+        if self.kiosk {
+            layout::TEXT_SIZE * kiosk::TEXT_SCALE
+        } else {
+            layout::TEXT_SIZE
+        }
+    }
+
+    // the theme's accent, unless the context being played has its own
+    fn accent(&self) -> [f32; 3] {
+        self.context_theme()
+            .and_then(|rule| rule.accent)
+            .unwrap_or(self.themes.current().accent)
+    }
+
+    // text split between the main and fallback fonts, by which has each
+    // character
+    fn texts<'a>(&self, text: &'a str, color: [f32; 4]) -> Vec<Text<'a>> {
+        let fonts = self.brush.as_ref().unwrap().fonts();
+        let has = |font: usize, c| fonts[font].glyph_id(c).0 != 0;
+        font::runs(text, has, fonts.len())
+            .into_iter()
+            .map(|(run, font)| {
+                Text::new(run)
+                    .with_color(color)
+                    .with_scale(self.text_size())
+                    .with_font_id(FontId(font))
+            })
+            .collect()
+    }
+
+    // text and layout work in logical pixels, so the brush's view is the
+    // logical size of the surface. sections are rebuilt from the layout every
+    // frame, which re-lays out the glyphs for the new bounds
+    // offscreen frames are drawn at 1x
+    fn scale_factor(&self) -> f64 {
+        self.window
+            .as_ref()
+            .map_or(1.0, |window| window.scale_factor())
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        if let Some(surface_config) = self.surface_config.as_mut() {
+            (surface_config.width, surface_config.height) = gpu::clamp_size(
+                size.width,
+                size.height,
+                &self.device.as_ref().unwrap().limits(),
+            );
+            self.surface
+                .as_ref()
+                .unwrap()
+                .configure(self.device.as_ref().unwrap(), surface_config);
+        }
+
+        let logical: LogicalSize<f32> = size.to_logical(self.scale_factor());
+        if let Some(brush) = self.brush.as_ref() {
+            brush.resize_view(logical.width, logical.height, self.queue.as_ref().unwrap());
+        }
+        self.layout.size = (logical.width, logical.height);
+        self.lay_out();
+    }
+
+    // where everything goes at the widget's size, leaving room for the cover
+    // while it's shown
+    fn lay_out(&mut self) {
+        let (width, height) = self.layout.size;
+        let layout =
+            layout::WidgetLayout::with_shape(width, height, self.config.padding, self.config.shape);
+        self.layout = if self.shows_cover() {
+            layout.with_art(self.config.padding)
+        } else {
+            layout
+        };
+        self.hit_regions = self.outline_regions();
+    }
+
+    // the regions that don't depend on the text, which each redraw adds to
+    fn outline_regions(&self) -> layout::HitRegions {
+        let (width, height) = self.layout.size;
+        let corner_radius =
+            self.config
+                .shape
+                .corner_radius(self.themes.current().corner_radius, width, height);
+        let mut regions = layout::HitRegions::new(self.layout, corner_radius);
+        regions.insert_band(layout::Element::ProgressBar, 1.0 - BAR_HEIGHT, 1.0);
+        regions
+    }
+
+    // the uploaded cover, when it's the playing track's and streamer and
+    // privacy mode aren't holding the track back
+    fn shows_cover(&self) -> bool {
+        let Some(cover) = self.cover.as_ref().and_then(|cover| cover.cover.as_ref()) else {
+            return false;
+        };
+        !self.ui_state.redacted
+            && !self.privacy.is_active()
+            && self
+                .spotify_data
+                .as_ref()
+                .is_some_and(|data| cover.is_for(data))
+    }
+
+    fn follow_album_art(&mut self) {
+        let (Some(album_art), Some(cover)) = (self.album_art.as_mut(), self.cover.as_mut()) else {
+            return;
+        };
+        if !album_art.has_changed().unwrap_or(false) {
+            return;
+        }
+        if let Some((found, art)) = album_art.borrow_and_update().clone() {
+            cover.upload(
+                self.device.as_ref().unwrap(),
+                self.queue.as_ref().unwrap(),
+                &found,
+                &art,
+            );
+        }
+    }
+
+    fn apply_ui_action(&mut self, action: input::UiAction) {
+        match action {
+            input::UiAction::ToggleExpanded => self.ui_state.expanded = !self.ui_state.expanded,
+            input::UiAction::Hide => {
+                self.ui_state.hidden = true;
+                self.window.as_ref().unwrap().set_minimized(true);
+                if let Some(uniforms) = self.uniforms.as_mut() {
+                    uniforms.clock.pause();
+                }
+            }
+            input::UiAction::NextPage => {
+                self.ui_state.page = self.ui_state.page.next(self.config.weekly_recap)
+            }
+            input::UiAction::ToggleRedacted => self.ui_state.redacted = !self.ui_state.redacted,
+            input::UiAction::NextTheme => {
+                if let Err(e) = self.select_theme(&theme::ThemeSelection::Next) {
+                    eprintln!("couldn't switch theme: {:?}", e);
+                }
+                return;
+            }
+            input::UiAction::TapTempo => {
+                if self.config.tap_tempo {
+                    self.tap_tempo
+                        .get_or_insert_with(Default::default)
+                        .tap(std::time::Instant::now());
+                }
+                return;
+            }
+            input::UiAction::OpenSearch => {
+                self.open_launcher();
+                return;
+            }
+            input::UiAction::ToggleLike => {
+                if let Some(like_toggles) = self.like_toggles.as_ref() {
+                    let _ = like_toggles.send(());
+                }
+                return;
+            }
+            // privacy isn't ui state, it's deliberately not remembered
+            input::UiAction::TogglePrivacy => {
+                self.privacy.toggle();
+                return;
+            }
+        }
+        self.ui_state.save();
+    }
+
+    // a click on the progress bar seeks to where it landed, anything else
+    // does what it's set to in the config
+    fn apply_gesture(&mut self, gesture: gesture::Gesture) {
+        if let (gesture::Gesture::Click, Some((layout::Element::ProgressBar, (x, _)))) =
+            (gesture, self.pressed_on)
+        {
+            if let (Some(command), Some(controls)) = (self.seek_on_bar(x), self.controls.as_ref()) {
+                let _ = controls.send(command);
+            }
+            return;
+        }
+        match self.config.gestures.outcome(gesture) {
+            Some(gesture::Outcome::Command(command)) => {
+                if let Some(controls) = self.controls.as_ref() {
+                    let _ = controls.send(command);
+                }
+            }
+            Some(gesture::Outcome::Ui(action)) => self.apply_ui_action(action),
+            None => {}
+        }
+    }
+
+    fn seek_on_bar(&self, x: f32) -> Option<PlayerCommand> {
+        let data = self.spotify_data.as_ref()?;
+        let duration_ms = data.duration_ms.filter(|duration_ms| *duration_ms > 0)?;
+        let played_ms = data.played_ms(self.spotify_data_fetched?.elapsed())?;
+        Some(gesture::seek_to(
+            x / self.layout.size.0,
+            played_ms,
+            duration_ms,
+        ))
+    }
+
+    // a tap does whatever a click is set to do. swipes page through tracks
+    // like a carousel, and up and down open and close the expanded view
+    fn apply_touch(&mut self, touch: gesture::Touch) {
+        let command = match touch {
+            gesture::Touch::Tap => return self.apply_gesture(gesture::Gesture::Click),
+            gesture::Touch::SwipeLeft => PlayerCommand::Next,
+            gesture::Touch::SwipeRight => PlayerCommand::Previous,
+            gesture::Touch::SwipeUp | gesture::Touch::SwipeDown => {
+                let expand = touch == gesture::Touch::SwipeUp;
+                if self.ui_state.expanded != expand {
+                    self.apply_ui_action(input::UiAction::ToggleExpanded);
+                }
+                return;
+            }
+        };
+        if let Some(controls) = self.controls.as_ref() {
+            let _ = controls.send(command);
+        }
+    }
+
+    // remembered for the next launch, like the rest of the ui state
+    fn select_theme(&mut self, selection: &theme::ThemeSelection) -> Result<String, anyhow::Error> {
+        let name = self.themes.select(selection)?.name.clone();
+        self.ui_state.theme = Some(name.clone());
+        self.ui_state.save();
+        Ok(name)
+    }
+
+    // whether a point in logical pixels is inside the widget's outline
+    fn over_widget(&self, position: (f32, f32)) -> bool {
+        self.hit_regions.at(position).is_some()
+    }
+
+    fn set_click_through(&mut self, click_through: bool) {
+        if click_through == self.click_through {
+            return;
+        }
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        match window.set_cursor_hittest(!click_through) {
+            Ok(()) => self.click_through = click_through,
+            Err(e) => eprintln!("couldn't change click-through: {}", e),
+        }
+    }
+
+    fn open_launcher(&mut self) {
+        self.launcher = Some(search::Launcher::default());
+        if self.ui_state.hidden {
+            self.ui_state.hidden = false;
+            self.window.as_ref().unwrap().set_minimized(false);
+            if let Some(uniforms) = self.uniforms.as_mut() {
+                uniforms.clock.resume(std::time::Instant::now());
+            }
+        }
+        self.window.as_ref().unwrap().focus_window();
+    }
+
+    fn search_hits(&self) -> Vec<search::Hit> {
+        self.search_hits
+            .as_ref()
+            .map(|rx| rx.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    // enter plays the chosen hit, shift+enter queues it instead if it's a
+    // track
+    fn launcher_key(&mut self, event: KeyEvent) {
+        if event.state != winit::event::ElementState::Pressed {
+            return;
+        }
+        let hits = self.search_hits();
+        let Some(launcher) = self.launcher.as_mut() else {
+            return;
+        };
+        match event.logical_key {
+            Key::Named(NamedKey::Escape) => self.launcher = None,
+            Key::Named(NamedKey::Enter) => {
+                if let Some(hit) = launcher.chosen(&hits) {
+                    if self.modifiers.shift_key() && hit.uri.starts_with("spotify:track:") {
+                        if let Some(adds) = self.queue_adds.as_ref() {
+                            let _ = adds.send(hit.uri.clone());
+                        }
+                    } else if let Some(searches) = self.searches.as_ref() {
+                        let _ = searches.send(search::Request::Play(hit.uri.clone()));
+                    }
+                }
+                self.launcher = None;
+            }
+            Key::Named(NamedKey::ArrowUp) => launcher.select_by(-1, hits.len()),
+            Key::Named(NamedKey::ArrowDown) => launcher.select_by(1, hits.len()),
+            Key::Named(NamedKey::Backspace) => {
+                launcher.backspace();
+                if let Some(searches) = self.searches.as_ref() {
+                    let _ = searches.send(search::Request::Search(launcher.query.clone()));
+                }
+            }
+            _ => {
+                let Some(text) = event
+                    .text
+                    .filter(|text| !text.chars().any(char::is_control))
+                else {
+                    return;
+                };
+                launcher.typed(&text);
+                if let Some(searches) = self.searches.as_ref() {
+                    let _ = searches.send(search::Request::Search(launcher.query.clone()));
+                }
+            }
+        }
+    }
+
+    fn handle_ctl(&mut self) {
+        while let Some((command, answer)) = self.ctl.as_mut().and_then(|ctl| ctl.try_recv().ok()) {
+            let res = match command {
+                ctl::CtlCommand::Theme(selection) => self.select_theme(&selection),
+                ctl::CtlCommand::Status => Ok(self.status()),
+                ctl::CtlCommand::Queue(uri) => match self.queue_adds.as_ref() {
+                    Some(adds) if adds.send(uri).is_ok() => Ok(String::from("queued")),
+                    _ => Err(anyhow::anyhow!("nothing to queue on")),
+                },
+                ctl::CtlCommand::Search => {
+                    self.open_launcher();
+                    Ok(String::from("searching"))
+                }
+                // answered once spotify has been asked
+                ctl::CtlCommand::PlayPlaylist(name) => {
+                    if let Some(searches) = self.searches.as_ref() {
+                        let _ = searches.send(search::Request::PlayPlaylist(name, answer));
+                    }
+                    continue;
+                }
+                ctl::CtlCommand::Focus(start) => match self.focus_timer.as_ref() {
+                    Some(timer) if timer.send(start).is_ok() => {
+                        Ok(String::from(if start { "focusing" } else { "stopped" }))
+                    }
+                    _ => Err(anyhow::anyhow!("the focus timer isn't running")),
+                },
+                ctl::CtlCommand::Sleep(after) => match self.sleep_timer.as_ref() {
+                    Some(timer) if timer.send(after).is_ok() => Ok(match after {
+                        Some(after) => {
+                            format!("pausing in {} min", (after.as_secs_f64() / 60.0).ceil())
+                        }
+                        None => String::from("sleep timer off"),
+                    }),
+                    _ => Err(anyhow::anyhow!("the sleep timer isn't running")),
+                },
+            };
+            let _ = answer.send(res.map_err(|e| e.to_string()));
+        }
+    }
+
+    // what's playing leaves the widget here too, so streamer and privacy
+    // mode hold it back
+    fn status(&self) -> String {
+        if self.ui_state.redacted || self.privacy.is_active() {
+            return self.config.redact_message.clone();
+        }
+        let since_fetch = self
+            .spotify_data_fetched
+            .map(|fetched| fetched.elapsed())
+            .unwrap_or_default();
+        let data = self.spotify_data.as_ref().map(|data| SpotifyData {
+            progress_ms: data.played_ms(since_fetch),
+            ..data.clone()
+        });
+        let active = self
+            .devices
+            .as_ref()
+            .and_then(|rx| rx.borrow().active.clone());
+        status::sentence(data.as_ref(), active.as_ref())
+    }
+
+    fn artist_card(&self) -> Option<bio::ArtistCard> {
+        self.hover_card.as_ref()?.card()
+    }
+
+    // whether the lines drawn are the now playing page's templates, rather
+    // than the launcher, a toast or a card over them
+    fn shows_now_playing(&self) -> bool {
+        self.spotify_data.is_some()
+            && self.launcher.is_none()
+            && !self.ui_state.redacted
+            && self.artist_card().is_none()
+            && self.tap_tempo.is_none()
+            && self.idle_device().is_none()
+            && self.toast().is_none()
+            && self.ui_state.page == state::Page::NowPlaying
+    }
+
+    // whether the artist is on the second line, where it can be hovered
+    fn shows_artist(&self) -> bool {
+        self.shows_now_playing()
+            && self
+                .line_templates
+                .get(1)
+                .is_some_and(|line| line.is_field("artist"))
+    }
+
+    // the first line showing the track
+    fn title_line(&self) -> Option<usize> {
+        self.line_templates
+            .iter()
+            .position(|line| line.uses_field("track"))
+    }
+
+    // the title's line, when it gets the explicit badge
+    fn explicit_badge_line(&self) -> Option<usize> {
+        let data = self.spotify_data.as_ref()?;
+        let badged = self.config.explicit_badge
+            && !self.config.hide_explicit
+            && data.explicit
+            && !data.track_name.is_empty()
+            && self.shows_now_playing();
+        badged.then(|| self.title_line())?
+    }
+
+    // the romanized line for the first line showing the track, and where that
+    // line is
+    fn romanized_title(&self, lines: &[String]) -> Option<(usize, String)> {
+        if !self.config.romanize_titles {
+            return None;
+        }
+        let at = self.title_line()?;
+        let japanese = self
+            .config
+            .metadata_language
+            .as_deref()
+            .is_some_and(|language| language.to_lowercase().starts_with("ja"));
+        Some((at, romanize::romanized(lines.get(at)?, japanese)?))
+    }
+
+    // the artist's line, pushed down when the title's romanization goes above
+    // it
+    fn artist_line(&self, lines: &[String]) -> usize {
+        match self.romanized_title(lines) {
+            Some((0, _)) => 2,
+            _ => 1,
+        }
+    }
+
+    // only while it still belongs to the track being shown
+    fn enrichment(&self) -> Option<enrich::Enrichment> {
+        let data = self.spotify_data.as_ref()?;
+        let enrichment = self.enrichment.as_ref()?.borrow();
+        match enrichment.as_ref() {
+            Some((track, enrichment)) if track.same_track(data) => Some(enrichment.clone()),
+            _ => None,
+        }
+    }
+
+    // the newest toast still up
+    fn toast(&self) -> Option<toast::Toast> {
+        let now = std::time::Instant::now();
+        self.toasts
+            .iter()
+            .filter_map(|toasts| toasts.borrow().clone())
+            .filter(|toast| toast.visible(now))
+            .max_by_key(|toast| toast.shown_at)
+    }
+
+    // the device playback stopped on, while nothing is playing
+    fn idle_device(&self) -> Option<spotify::Device> {
+        self.devices
+            .as_ref()
+            .and_then(|rx| rx.borrow().idle_device().cloned())
+    }
+
+    // the config's look for the playlist, album or artist playing from
+    fn context_theme(&self) -> Option<&theme::ContextTheme> {
+        let data = self.spotify_data.as_ref()?;
+        theme::for_context(
+            &self.config.context_themes,
+            data.context_uri.as_deref(),
+            data.context_name.as_deref(),
+        )
+    }
+
+    // the audio features, if they're for this track
+    fn features_for(&self, data: &SpotifyData) -> Option<spotify::AudioFeatures> {
+        let features = self.audio_features.as_ref()?.borrow().clone();
+        features
+            .filter(|(id, _)| data.track_id.as_ref() == Some(id))
+            .map(|(_, features)| features)
+    }
+
+    // expands when playback moves to the connect_target device, and collapses
+    // when it moves off again
+    fn follow_connect_target(&mut self) {
+        let (Some(target), Some(devices)) =
+            (self.config.connect_target.as_deref(), self.devices.as_mut())
+        else {
+            return;
+        };
+        if !devices.has_changed().unwrap_or(false) {
+            return;
+        }
+        let selected = devices
+            .borrow_and_update()
+            .active
+            .as_ref()
+            .is_some_and(|device| device.name.eq_ignore_ascii_case(target));
+        if selected == self.on_connect_target {
+            return;
+        }
+        self.on_connect_target = selected;
+        if self.ui_state.expanded != selected {
+            self.apply_ui_action(input::UiAction::ToggleExpanded);
+        }
+    }
+
+    // text and color of each status indicator, drawn left to right
+    fn indicators(&self) -> Vec<(String, [f32; 4])> {
+        let mut indicators = Vec::new();
+        if self.config.device_indicator {
+            let active = self
+                .devices
+                .as_ref()
+                .and_then(|rx| rx.borrow().active.clone());
+            if let Some(device) = active {
+                let label = format!("{} ", device::label(&device));
+                indicators.push((label, [0.6, 0.7, 0.7, 1.0]));
+            }
+        }
+        if self.config.playback_mode_indicator {
+            if let Some(data) = &self.spotify_data {
+                if data.shuffle == Some(true) {
+                    indicators.push((String::from("shuf "), [0.6, 0.7, 0.7, 1.0]));
+                }
+                let repeat = match data.repeat {
+                    Some(spotify::RepeatState::Context) => "rep ",
+                    Some(spotify::RepeatState::Track) => "rep1 ",
+                    Some(spotify::RepeatState::Off) | None => "",
+                };
+                if !repeat.is_empty() {
+                    indicators.push((String::from(repeat), [0.6, 0.7, 0.7, 1.0]));
+                }
+            }
+        }
+        if self.config.like_indicator {
+            let track_id = self
+                .spotify_data
+                .as_ref()
+                .and_then(|data| data.track_id.as_deref());
+            let heart = self.liked.as_ref().and_then(|rx| {
+                rx.borrow()
+                    .as_ref()
+                    .and_then(|liked| liked.heart(track_id, std::time::Instant::now()))
+            });
+            if let Some(color) = heart {
+                indicators.push((String::from("♥ "), color));
+            }
+        }
+        if let Some(rule) = self.context_theme() {
+            if let Some(icon) = &rule.icon {
+                let [r, g, b] = rule.accent.unwrap_or(self.themes.current().accent);
+                indicators.push((format!("{} ", icon), [r, g, b, 1.0]));
+            }
+        }
+        if self.privacy.is_active() {
+            indicators.push((String::from("◐"), [0.9, 1.0, 1.0, 1.0]));
+        }
+        if self.config.source_badge {
+            if let Some(data) = &self.spotify_data {
+                indicators.push((String::from("●"), data.source.badge_color()));
+            }
+        }
+        indicators
+    }
+
+    // what the current page shows, one entry per line
+    fn text_lines(&self) -> Vec<String> {
+        if let Some(launcher) = &self.launcher {
+            return launcher.lines(&self.search_hits());
+        }
+        let data = match &self.spotify_data {
+            Some(data) => data,
+            None => return vec![String::from("test!")],
+        };
+        // only what's drawn is replaced, the real data stays available locally
+        if self.ui_state.redacted {
+            return vec![self.config.redact_message.clone()];
+        }
+        if let Some(toast) = self.toast() {
+            return toast.lines;
+        }
+        if let Some(card) = self.artist_card() {
+            return bio::card_lines(&card);
+        }
+        if let Some(tap_tempo) = &self.tap_tempo {
+            let track_tempo = self.features_for(data).map(|features| features.tempo);
+            return tap_tempo.lines(track_tempo);
+        }
+        if let Some(device) = self.idle_device() {
+            return vec![
+                String::from("nothing playing"),
+                format!("click to resume on {}", device::label(&device)),
+            ];
+        }
+        if data.track_name.is_empty() {
+            return vec![String::from(if data.private_session {
+                "private session"
+            } else {
+                "nothing playing"
+            })];
+        }
+        let enrichment = self.enrichment().unwrap_or_default();
+        match self.ui_state.page {
+            state::Page::NowPlaying => {
+                let since_fetch = self
+                    .spotify_data_fetched
+                    .map(|fetched| fetched.elapsed())
+                    .unwrap_or_default();
+                let data = SpotifyData {
+                    track_name: data.track_name.clone(),
+                    release: release(data, &enrichment),
+                    artist_name: enrichment
+                        .artist
+                        .unwrap_or_else(|| data.artist_name.clone()),
+                    progress_ms: data.played_ms(since_fetch),
+                    ..data.clone()
+                };
+                let status = if data.is_playing { "playing" } else { "paused" };
+                let mut context = template::track_context(&data, status);
+                context.insert("label", enrichment.label.clone().unwrap_or_default());
+                context.insert("genres", enrichment.genres.join(", "));
+                context.insert(
+                    "copyright",
+                    enrichment.copyright.clone().unwrap_or_default(),
+                );
+                let expanded = if self.ui_state.expanded {
+                    self.expanded_templates.as_slice()
+                } else {
+                    &[]
+                };
+                let up_next = match (&self.up_next, self.config.queue_preview) {
+                    (Some(queue), true) => queue::preview_lines(&queue.borrow()),
+                    _ => Vec::new(),
+                };
+                let mut lines: Vec<String> = self
+                    .line_templates
+                    .iter()
+                    .map(|line| line.render(&context))
+                    .collect();
+                if let Some((at, romanized)) = self.romanized_title(&lines) {
+                    lines.insert(at + 1, romanized);
+                }
+                // fields a source doesn't have leave no blank lines
+                lines.extend(
+                    expanded
+                        .iter()
+                        .map(|line| line.render(&context))
+                        .filter(|line| !line.is_empty()),
+                );
+                lines.extend(up_next);
+                lines
+            }
+            state::Page::Details => {
+                let since_fetch = self
+                    .spotify_data_fetched
+                    .map(|fetched| fetched.elapsed())
+                    .unwrap_or_default();
+                let position = match (data.played_ms(since_fetch), data.duration_ms) {
+                    (Some(played_ms), Some(duration_ms)) => format!(
+                        "{} / {}",
+                        format_ms(played_ms.min(duration_ms)),
+                        format_ms(duration_ms)
+                    ),
+                    (None, Some(duration_ms)) => format!("-:-- / {}", format_ms(duration_ms)),
+                    _ => String::from("-:--"),
+                };
+                let album = match release(data, &enrichment) {
+                    Some(release) => format!("{} ({})", data.album_name, release.year()),
+                    None => data.album_name.clone(),
+                };
+                let mut lines = vec![album, position];
+                if self.ui_state.expanded {
+                    let status = if data.is_playing { "playing" } else { "paused" };
+                    lines.push(String::from(status));
+                    lines.extend(enrichment.label);
+                    if !enrichment.genres.is_empty() {
+                        lines.push(enrichment.genres.join(", "));
+                    }
+                    if let Some(target) = self.config.loudness_target {
+                        lines
+                            .extend(self.features_for(data).map(|features| {
+                                features::loudness_line(features.loudness, target)
+                            }));
+                    }
+                }
+                lines
+            }
+            state::Page::Recap => {
+                let mut lines = self
+                    .recap
+                    .as_ref()
+                    .map(|recap| recap.borrow().clone())
+                    .filter(|lines| !lines.is_empty())
+                    .unwrap_or_else(|| vec![String::from("no recap yet")]);
+                if !self.ui_state.expanded {
+                    lines.truncate(2);
+                }
+                lines
+            }
+        }
+    }
+
+    // takes the newest poll, if there's been one since the last frame. a failed
+    // poll keeps showing what was there before
+    fn follow_polls(&mut self) {
+        let Some(polls) = self.polls.as_mut() else {
+            return;
+        };
+        if !polls.has_changed().unwrap_or(false) {
+            return;
+        }
+        if let Some(data) = polls.borrow_and_update().clone() {
+            self.spotify_data = Some(data);
+            // progress is extrapolated from when the poll came in
+            self.spotify_data_fetched = Some(std::time::Instant::now());
+        }
+    }
+
+    fn update(&mut self, size: PhysicalSize<u32>) {
+        self.handle_ctl();
+        let now = std::time::Instant::now();
+        self.tap_tempo.take_if(|tap_tempo| tap_tempo.is_idle(now));
+        self.follow_polls();
+        self.follow_connect_target();
+        self.follow_album_art();
+        if self.shows_cover() != self.layout.art.is_some() {
+            self.lay_out();
+        }
+        let art = self.layout.art;
+        let (width, height) = self.layout.size;
+        let covered = self.toast().is_some() || self.artist_card().is_some();
+        let scale_factor = self.scale_factor();
+        // the beat only carries on while the track does
+        let features = self
+            .spotify_data
+            .as_ref()
+            .filter(|data| data.is_playing)
+            .and_then(|data| self.features_for(data));
+        let accent = self.accent();
+        match self.uniforms.as_mut() {
+            Some(uniforms) => {
+                let dt = uniforms.clock.tick(std::time::Instant::now());
+                let globals = &mut uniforms.globals;
+                animate(globals, dt);
+                let since_fetch = self.spotify_data_fetched.map(|fetched| fetched.elapsed());
+                // unknown progress is drawn as an indeterminate bar by the shader
+                globals.progress = self
+                    .spotify_data
+                    .as_ref()
+                    .zip(since_fetch)
+                    .and_then(|(data, since_fetch)| data.progress(since_fetch))
+                    .unwrap_or(-1.0);
+                globals.position = self
+                    .spotify_data
+                    .as_ref()
+                    .zip(since_fetch)
+                    .and_then(|(data, since_fetch)| data.played_ms(since_fetch))
+                    .map_or(0.0, |played_ms| played_ms as f32 / 1000.0);
+                globals.resolution = [size.width as f32, size.height as f32];
+                let theme = self.themes.current();
+                let [r, g, b] = accent;
+                globals.accent = [r, g, b, 1.0];
+                globals.corner_radius = self.config.shape.corner_radius(
+                    theme.corner_radius * scale_factor as f32,
+                    globals.resolution[0],
+                    globals.resolution[1],
+                );
+                globals.opacity = theme.opacity.clamp(0.0, 1.0);
+                globals.accent_effect = if self.config.reduce_motion {
+                    theme::AccentEffect::Still.shader_value()
+                } else {
+                    theme.accent_effect.shader_value()
+                };
+                globals.accent_period = theme.accent_period.max(0.1);
+                (globals.palette, globals.palette_len) = theme.shader_palette();
+                (globals.tempo, globals.energy) = features
+                    .map(|features| (features.tempo, features.energy))
+                    .unwrap_or_default();
+                self.queue.as_ref().unwrap().write_buffer(
+                    &uniforms.buffer,
+                    0,
+                    bytemuck::cast_slice(&[uniforms.globals]),
+                );
+
+                if let Some(quads) = self.quads.as_mut() {
+                    quads.clear();
+                    quads.push(
+                        quad::Layer::Background,
+                        quad::QuadParams::new([0.0, 0.0, 1.0, 1.0], [0.0; 4], quad::QUAD_GRADIENT),
+                    );
+                    quads.push(
+                        quad::Layer::Content,
+                        quad::QuadParams::new(
+                            [0.0, 1.0 - BAR_HEIGHT, 1.0, BAR_HEIGHT],
+                            uniforms.globals.accent,
+                            quad::QUAD_PROGRESS | quad::QUAD_ACCENT,
+                        ),
+                    );
+                    if let Some([x, y, art_width, art_height]) = art {
+                        quads.push(
+                            quad::Layer::Content,
+                            quad::QuadParams::new(
+                                [
+                                    x / width,
+                                    y / height,
+                                    art_width / width,
+                                    art_height / height,
+                                ],
+                                [0.0; 4],
+                                quad::QUAD_TEXTURE,
+                            ),
+                        );
+                    }
+                    // toasts and the artist card cover the widget, under their text
+                    if covered {
+                        quads.push(
+                            quad::Layer::Content,
+                            quad::QuadParams::new([0.0, 0.0, 1.0, 1.0], [0.05, 0.05, 0.08, 1.0], 0),
+                        );
+                    }
+                    quads.upload(self.queue.as_ref().unwrap());
+                }
+            }
+            None => {}
+        };
+    }
+}
+
+// steps everything animated by one frame. driven by the frame delta rather
+// than time since launch, so pausing the clock freezes it in place
+fn animate(globals: &mut Globals, dt: std::time::Duration) {
+    globals.time += dt.as_secs_f32();
+}
+
+fn begin_render_pass<'encoder>(
+    encoder: &'encoder mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'encoder> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("render pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+async fn spotify_source(config: &config::Config) -> (SpotifyData, player::Backend) {
+    let redirect_uri = config
+        .spotify_redirect_uri
+        .as_deref()
+        .unwrap_or(spotify::REDIRECT_URI);
+    // without a secret in the config, login goes through PKCE, which needs
+    // none. the binary doesn't carry one. setup leaves it sealed
+    let client_secret = match config
+        .spotify_client_secret
+        .as_deref()
+        .map(secrets::open_value)
+    {
+        Some(Ok(secret)) => secret,
+        Some(Err(e)) => {
+            eprintln!(
+                "couldn't open the client secret, logging in with PKCE: {}",
+                e
+            );
+            String::new()
+        }
+        None => String::new(),
+    };
+    // performs auth request
+    let mut spotify = spotify::Spotify::from_client_id(
+        config
+            .spotify_client_id
+            .as_deref()
+            .unwrap_or(spotify::CLIENT_ID),
+    )
+    .with_client_secret(&client_secret)
+    .with_external_token(config.spotify_token_file.as_deref())
+    .with_language(config.metadata_language.as_deref())
+    .with_refresh_margin(Duration::from_secs(config.token_refresh_margin))
+    .with_scope(spotify::SCOPES)
+    .with_redirect_uri(redirect_uri)
+    .build();
+    spotify.show_dialog = false;
+
+    // a saved token that still works (or refreshes) skips the browser
+    if !spotify.load_saved_token().await {
+        let auth_url = spotify.login_url();
+        // let mut auth_code_buffer = [0; 512];
+        let auth_code = Arc::new(Mutex::new(String::with_capacity(512)));
+        let thread_auth_code = auth_code.clone();
+
+        let address = spotify::loopback_address(redirect_uri)
+            .unwrap_or_else(|| String::from("localhost:8000"));
+        // create temp http server for OAuth2 loopback
+        let task = tokio::spawn(async move {
+            let listener = TcpListener::bind(address).await.unwrap();
+            eprintln!("listening");
+            // loop {
+            if let Ok((mut socket, addr)) = listener.accept().await {
+                eprintln!("new connection from {}", addr.ip());
+                let thread_auth_code = Arc::clone(&thread_auth_code);
+                tokio::spawn(async move {
+                    let mut buffer = [0; 512];
+                    if let Ok(n) = socket.read(&mut buffer).await {
+                        if n != 0 {
+                            eprintln!("received: {}", String::from_utf8_lossy(&buffer[..n]));
+                            let received_val = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let re = Regex::new(r"^GET \/\?code=(.*) HTTP").unwrap();
+                            let caps = re.captures(received_val.as_str()).unwrap();
+
+                            // match String::from_utf16_lossy(&buffer[..n]) {
+                            //     "GET /?code="
+                            // }
+
+                            let mut auth_code = thread_auth_code.lock().await;
+                            eprintln!("captures found:");
+                            for cap in caps.iter() {
+                                eprintln!("{:#?}", cap);
+                            }
+                            *auth_code = caps[1].to_owned();
+                            // String::from_utf8_lossy(&buffer[..n]).to_string();
+
+                            socket
+                                .write_all(b"hello from tokio server\n")
+                                .await
+                                .unwrap();
+                        } else {
+                            eprintln!("didn't receive any data");
+                            socket.write_all(b"hello anyway!\n").await.unwrap();
+                        }
+                    }
+                });
+            }
+            // }
+        });
+
+        eprintln!("outside of loop");
+
+        webbrowser::open(auth_url.as_str()).unwrap();
+        task.await.unwrap();
+
+        // wait for auth_code
+        loop {
+            if auth_code.lock().await.len() != 0 {
+                break;
+            }
+        }
+
+        // println!("auth_code: {:#?}", auth_code.lock().await);
+        spotify.token(&auth_code.lock().await).await.unwrap();
+    }
+    let currently_playing_res = spotify.get_currently_playing().await;
+
+    // match currently_playing_res {
+    //     Ok(res) => {
+    //         println!("{:?}", res.to_string());
+    //     }
     //     Err(e) => {
     //         println!("{:?}", e);
     //     }
     // }
 
-    let mut spotify_data = SpotifyData::default();
-    spotify_data.artist_name = currently_playing_res.unwrap().item.unwrap().album.artists[0]
-        .name
-        .clone();
+    let spotify_data = match currently_playing_res {
+        Ok(playing) => SpotifyData::from(playing),
+        Err(e) => {
+            eprintln!("couldn't get what's playing: {}", e);
+            SpotifyData::default()
+        }
+    };
 
-    println!("{:?}", spotify_data.artist_name.clone());
-    // spotify_data.artist_name = match currently_playing_res.unwrap().item.unwrap() {
-    //     spotify::PlayableItem::EpisodeObject(_episode) => String::new(),
-    //     spotify::PlayableItem::TrackObject(track) => track.artists[0].name.clone(),
-    // };
     // spotify_data.artist_name = unsafe { currently_playing_res.iter()
 
     // let track_object = currently_playing_res.unwrap().item.unwrap();
@@ -594,6 +2157,286 @@ async fn main() {
 
     // return;
 
+    (
+        spotify_data,
+        player::Backend::Spotify(Arc::new(Mutex::new(spotify))),
+    )
+}
+
+async fn mpris_source() -> (SpotifyData, player::Backend) {
+    let mpris = mpris::Mpris::connect().await.unwrap();
+    let spotify_data = mpris.now_playing().await.unwrap();
+
+    (spotify_data, player::Backend::Mpris(mpris))
+}
+
+// spotify's own release date, or the year musicbrainz found for the rest
+fn release(data: &SpotifyData, enrichment: &enrich::Enrichment) -> Option<spotify::ReleaseDate> {
+    data.release.or_else(|| {
+        let year = enrichment.year.as_deref()?;
+        spotify::ReleaseDate::parse(year, None)
+    })
+}
+
+// m:ss
+fn format_ms(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+// `kyomi config export [file]` / `kyomi config import <file>`
+fn config_command(args: &[String]) {
+    let res = match (args.first().map(String::as_str), args.get(1)) {
+        (Some("export"), path) => {
+            let path = path.map(String::as_str).unwrap_or("kyomi-config.tar");
+            config::export_bundle(std::path::Path::new(path)).map(|_| println!("wrote {}", path))
+        }
+        (Some("import"), Some(path)) => {
+            config::import_bundle(std::path::Path::new(path)).map(|_| println!("imported {}", path))
+        }
+        _ => {
+            println!("usage: kyomi config export [file] | kyomi config import <file>");
+            return;
+        }
+    };
+    if let Err(e) = res {
+        eprintln!("config {}: {:?}", args[0], e);
+        std::process::exit(1);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("config") {
+        config_command(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("setup") {
+        setup::setup_command(&args[2..]).await;
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("status") {
+        ctl::status_command();
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("ctl") {
+        ctl::ctl_command(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("themes") {
+        preview::themes_command(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("history") {
+        history::history_command(&args[2..]);
+        return;
+    }
+
+    // `--headless` prints updates to stdout instead of opening a window,
+    // formatted by `--format`
+    let headless = match args.iter().position(|arg| arg == "--headless") {
+        Some(_) => {
+            let format = std::env::args()
+                .skip_while(|arg| arg != "--format")
+                .nth(1)
+                .unwrap_or_else(|| String::from("plain"));
+            match headless::Format::parse(&format) {
+                Ok(format) => Some(format),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // `--kiosk` opens full screen, for a dedicated now playing display
+    let kiosk = args.iter().any(|arg| arg == "--kiosk");
+
+    let config = config::Config::load();
+    http::configure(&config);
+    if config.audit_log {
+        audit::enable();
+        eprintln!("logging requests to {}", audit::log_path().display());
+    }
+    #[cfg(not(feature = "romanize"))]
+    if config.romanize_titles {
+        eprintln!("romanize_titles needs kyomi built with --features romanize");
+    }
+
+    // `--source mpris` reads from a local player instead of the web API
+    let source = std::env::args().skip_while(|arg| arg != "--source").nth(1);
+    let (spotify_data, backend) = match source.as_deref() {
+        Some("mpris") => mpris_source().await,
+        _ => spotify_source(&config).await,
+    };
+
+    // sinks subscribe to settled tracks rather than raw polls, so skipping
+    // through a few songs doesn't fire for every one of them
+    let dwell = std::env::args()
+        .skip_while(|arg| arg != "--dwell")
+        .nth(1)
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .unwrap_or(aggregator::DEFAULT_DWELL);
+    let privacy = Arc::new(privacy::Privacy::new(
+        config.private,
+        config.privacy_schedule.as_deref(),
+    ));
+    let devices = device::spawn_device_watcher(backend.clone());
+    let (controls, last_command) =
+        player::spawn_controller(backend.clone(), config.clone(), devices.clone());
+    let schedule = aggregator::PollSchedule {
+        base: config::secs(
+            config.poll_interval,
+            Duration::from_millis(100),
+            Duration::from_secs(10 * 60),
+        ),
+        heartbeat: matches!(backend, player::Backend::Spotify(_)).then(|| {
+            config::secs(
+                config.poll_heartbeat,
+                Duration::from_millis(100),
+                Duration::from_secs(10 * 60),
+            )
+        }),
+        adaptive: config.adaptive_polling,
+    };
+    let (settled_tracks, polls) = aggregator::spawn_aggregator(
+        backend.clone(),
+        dwell,
+        privacy.clone(),
+        config.hide_explicit,
+        schedule,
+        last_command.clone(),
+    );
+    let art_providers = config
+        .art_providers
+        .iter()
+        .filter_map(|name| {
+            let provider = art::provider(name);
+            if provider.is_none() {
+                eprintln!("unknown art provider {:?}", name);
+            }
+            provider
+        })
+        .collect();
+    let covers = art::spawn_art_resolver(
+        art::ArtResolver::new(art_providers),
+        settled_tracks.subscribe(),
+    );
+    if let Some(settings) = config.now_json.clone() {
+        let address = settings.address.clone();
+        if let Err(e) =
+            now_json::spawn_now_json(settings, polls.clone(), covers.clone(), privacy.clone())
+        {
+            eprintln!("couldn't serve now.json on {}: {}", address, e);
+        }
+    }
+    if let Some(settings) = config.webhook.clone() {
+        webhook::spawn_webhook(
+            settings,
+            settled_tracks.subscribe(),
+            polls.clone(),
+            privacy.clone(),
+        );
+    }
+    let themes = theme::Themes::load(&config);
+    let audio_features = (config.loudness_target.is_some()
+        || config.tap_tempo
+        || themes.uses(theme::AccentEffect::Pulse))
+    .then(|| features::spawn_audio_features(backend.clone(), settled_tracks.subscribe()));
+    let album_art = config
+        .album_art
+        .then(|| cover::spawn_album_art(backend.clone(), covers.clone()));
+    let (liked, like_toggles) = like::spawn_likes(backend.clone(), settled_tracks.subscribe());
+    let (searches, search_hits) = search::spawn_search(backend.clone());
+    let (focus_toasts, focus_timer) = automation::spawn_automations(
+        backend.clone(),
+        controls.clone(),
+        config.automations.clone(),
+        config.focus_timer.clone(),
+    );
+    let sleep_timer = sleep::spawn_sleep_timer(
+        backend.clone(),
+        controls.clone(),
+        config::secs(config.sleep_fade, Duration::ZERO, sleep::MAX_SLEEP),
+        config.sleep_fade_curve,
+    );
+    let labels_from = match &backend {
+        player::Backend::Spotify(spotify) if config.album_labels => Some(spotify.clone()),
+        _ => None,
+    };
+    let (up_next, queue_adds) = queue::spawn_queue(
+        backend,
+        settled_tracks.subscribe(),
+        config.queue_preview || config.up_next_toast.is_some(),
+        config.hide_explicit,
+    );
+    // headless mode's stdout is its output, so the log stays out of it
+    if headless.is_none() {
+        let mut now_playing_log = settled_tracks.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let track = match now_playing_log.recv().await {
+                    Ok(track) => track,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                eprintln!("now playing: {} - {}", track.artist_name, track.track_name);
+            }
+        });
+    }
+
+    let enrichment = (config.musicbrainz_enrichment || labels_from.is_some()).then(|| {
+        enrich::spawn_enricher(
+            settled_tracks.subscribe(),
+            config.musicbrainz_enrichment,
+            labels_from,
+        )
+    });
+
+    let crossfade = config::secs(config.crossfade, Duration::ZERO, Duration::from_secs(60));
+    if let Some(token) = config.listenbrainz_token.clone() {
+        scrobble::spawn_listenbrainz(
+            scrobble::ListenBrainz::new(token),
+            settled_tracks.subscribe(),
+            crossfade,
+        );
+    }
+
+    let mut toasts = vec![focus_toasts];
+    if config.history {
+        toasts.push(history::spawn_history(
+            history_store::open(config.history_backend),
+            settled_tracks.subscribe(),
+            dwell,
+            crossfade,
+            config.skip_streak_hint,
+        ));
+    }
+    if let Some(lead) = config.up_next_toast {
+        toasts.push(queue::spawn_up_next_toast(
+            polls.clone(),
+            up_next.clone(),
+            config::secs(lead, Duration::ZERO, Duration::from_secs(60 * 60)),
+        ));
+    }
+    if config.playback_watchdog {
+        toasts.push(watchdog::spawn_watchdog(
+            polls.clone(),
+            last_command,
+            controls.clone(),
+            config.watchdog_auto_resume,
+        ));
+    }
+
+    if let Some(format) = headless {
+        headless::run(format, polls, privacy, config.redact_message).await;
+        return;
+    }
+
     let event_loop = EventLoop::new().unwrap();
 
     // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
@@ -605,8 +2448,64 @@ async fn main() {
     // input, and uses significantly less power/CPU time than ControlFlow::Poll.
     // event_loop.set_control_flow(ControlFlow::Wait);
 
+    // key presses elsewhere only arrive when asked for
+    if config.interact_modifier.is_some() {
+        event_loop.listen_device_events(winit::event_loop::DeviceEvents::Always);
+    }
+
     let mut app = App::default();
-    app.spotify_data = Some(spotify_data);
+    app.config = config;
+    app.privacy = privacy;
+    app.kiosk = kiosk;
+    app.frame_output = app
+        .config
+        .frame_output
+        .clone()
+        .map(capture::FrameOutput::new);
+    app.ui_state = state::UiState::load();
+    app.themes = themes;
+    if let Some(name) = app.ui_state.theme.clone() {
+        if let Err(e) = app.themes.select(&theme::ThemeSelection::Named(name)) {
+            eprintln!("{:?}, using the default theme", e);
+        }
+    }
+    app.ctl = match ctl::spawn_server() {
+        Ok(ctl) => Some(ctl),
+        Err(e) => {
+            eprintln!("kyomi ctl won't work: {:?}", e);
+            None
+        }
+    };
+    app.line_templates = template::parse_lines(&app.config.lines, config::DEFAULT_LINES);
+    app.expanded_templates =
+        template::parse_lines(&app.config.expanded_lines, config::DEFAULT_EXPANDED_LINES);
+    app.spotify_data = Some(spotify_data.hiding_explicit(app.config.hide_explicit));
+    app.spotify_data_fetched = Some(std::time::Instant::now());
+    app.polls = Some(polls);
+    app.controls = Some(controls);
+    app.devices = Some(devices);
+    app.enrichment = enrichment;
+    app.up_next = Some(up_next);
+    app.album_art = album_art;
+    app.audio_features = audio_features;
+    app.queue_adds = Some(queue_adds);
+    app.liked = Some(liked);
+    app.like_toggles = Some(like_toggles);
+    app.searches = Some(searches);
+    app.focus_timer = Some(focus_timer);
+    app.sleep_timer = Some(sleep_timer);
+    app.search_hits = Some(search_hits);
+    app.recap = (app.config.weekly_recap || app.config.recap_notification).then(|| {
+        recap::spawn_recap(
+            app.config.history_backend,
+            app.config.locale.clone(),
+            app.config.recap_notification,
+        )
+    });
+    app.toasts = toasts;
+    app.hover_card = app.config.artist_cards.then(bio::HoverCard::spawn);
+    app.focus_hidden = (!app.config.hide_when_focused.is_empty())
+        .then(|| focus::spawn_focus_watcher(app.config.hide_when_focused.clone()));
     let _ = event_loop.run_app(&mut app);
 }
 
@@ -623,9 +2522,84 @@ async fn test_currently_playing_parsing() {
 
     println!("{:?}", raw_json);
 
-    let mut spotify_data = SpotifyData::default();
-
     let res = serde_json::from_str::<spotify::CurrentlyPlayingResponse>(&raw_json).unwrap();
 
-    spotify_data.artist_name = res.item.unwrap().album.artists[0].name.clone();
+    let spotify_data = SpotifyData::from(res);
+    assert!(!spotify_data.artist_name.is_empty());
+}
+
+#[test]
+fn test_episodes_parse_alongside_tracks() {
+    let state = |item: &str| {
+        let raw = format!(
+            r#"{{"timestamp": 1, "progress_ms": 5000, "is_playing": true,
+                "shuffle_state": false, "repeat_state": "off", "context": null,
+                "device": null, "item": {}}}"#,
+            item
+        );
+        SpotifyData::from(serde_json::from_str::<spotify::PlaybackStateResponse>(&raw).unwrap())
+    };
+
+    let episode = state(
+        r#"{"type": "episode", "id": "512ojhOuo1ktJprKbVcKyQ", "name": "Episode 12",
+            "duration_ms": 3600000, "show": {"name": "A Show", "publisher": "A Network"}}"#,
+    );
+    assert_eq!(episode.track_name, "Episode 12");
+    assert_eq!(episode.artist_name, "A Show");
+    assert_eq!(episode.album_name, "A Network");
+    assert_eq!(episode.duration_ms, Some(3600000));
+    assert_eq!(episode.track_id, None);
+
+    let track = state(
+        r#"{"type": "track", "id": "11dFghVXANMlKmJXsNCbNl", "name": "Cut To The Feeling",
+            "duration_ms": 207959, "explicit": true,
+            "artists": [{"id": "", "name": "Carly Rae Jepsen", "href": ""}],
+            "album": {"id": "", "name": "Cut To The Feeling", "release_date": "2017-05-26",
+                "release_date_precision": "day", "artists": [], "images": [
+                    {"url": "https://i.scdn.co/image/640", "width": 640, "height": 640},
+                    {"url": "https://i.scdn.co/image/300", "width": 300, "height": 300}
+                ]}}"#,
+    );
+    assert_eq!(track.artist_name, "Carly Rae Jepsen");
+    assert_eq!(track.album_art_url, "https://i.scdn.co/image/300");
+    assert_eq!(track.track_id.as_deref(), Some("11dFghVXANMlKmJXsNCbNl"));
+    assert!(track.explicit && !episode.explicit);
+    assert_eq!(
+        track.clone().hiding_explicit(true).track_name,
+        "explicit track"
+    );
+    assert_eq!(
+        track.clone().hiding_explicit(false).track_name,
+        "Cut To The Feeling"
+    );
+    assert_eq!(episode.hiding_explicit(true).track_name, "Episode 12");
+
+    // local files have no ids, not even for their artists
+    let local = state(
+        r#"{"type": "track", "id": null, "name": "demo", "duration_ms": 1000,
+            "artists": [{"id": null, "name": "Someone", "href": null, "uri": "spotify:local:Someone"}],
+            "album": {"id": null, "name": "", "release_date": null,
+                "release_date_precision": null,
+                "artists": [{"id": null, "name": "Someone", "href": null}]}}"#,
+    );
+    assert_eq!(local.track_id, None);
+    assert_eq!(local.artist_name, "Someone");
+    assert!(local.album_art_url.is_empty());
+}
+
+#[test]
+fn test_idle_states() {
+    let private = SpotifyData::from(spotify::CurrentlyPlaying::PrivateSession);
+    assert!(private.private_session);
+    assert!(private.track_name.is_empty());
+    assert!(!SpotifyData::from(spotify::CurrentlyPlaying::NothingPlaying).private_session);
+
+    let state: spotify::PlaybackStateResponse = serde_json::from_str(
+        r#"{"timestamp": 1, "is_playing": true, "shuffle_state": false,
+            "repeat_state": "off", "context": null, "item": null,
+            "device": {"id": "d", "name": "Phone", "type": "Smartphone",
+                "volume_percent": 50, "is_private_session": true}}"#,
+    )
+    .unwrap();
+    assert!(SpotifyData::from(state).private_session);
 }
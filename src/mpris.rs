@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::Connection;
+
+use crate::spotify::RepeatState;
+use crate::{Source, SpotifyData};
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    fn play(&self) -> zbus::Result<()>;
+    fn pause(&self) -> zbus::Result<()>;
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+    // offset in microseconds
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+    fn set_position(&self, track_id: &ObjectPath<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn shuffle(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_shuffle(&self, value: bool) -> zbus::Result<()>;
+    #[zbus(property)]
+    fn loop_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn set_loop_status(&self, value: &str) -> zbus::Result<()>;
+    // 0-1
+    #[zbus(property)]
+    fn volume(&self) -> zbus::Result<f64>;
+    #[zbus(property)]
+    fn set_volume(&self, value: f64) -> zbus::Result<()>;
+}
+
+// a local media player found on the session bus
+#[derive(Clone)]
+pub struct Mpris {
+    player: PlayerProxy<'static>,
+}
+
+impl Mpris {
+    // connects to the first MPRIS player on the bus, preferring the spotify
+    // desktop client if it's running
+    pub async fn connect() -> Result<Self, anyhow::Error> {
+        let connection = Connection::session().await?;
+        let dbus = zbus::fdo::DBusProxy::new(&connection).await?;
+        let mut players: Vec<String> = dbus
+            .list_names()
+            .await?
+            .into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| name.starts_with(MPRIS_PREFIX))
+            .collect();
+        players.sort_by_key(|name| !name.ends_with(".spotify"));
+
+        let name = players
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no MPRIS player running"))?;
+        eprintln!("using MPRIS player {}", name);
+
+        let player = PlayerProxy::builder(&connection)
+            .destination(name)?
+            .build()
+            .await?;
+        Ok(Mpris { player })
+    }
+
+    pub async fn now_playing(&self) -> Result<SpotifyData, anyhow::Error> {
+        let metadata = self.player.metadata().await?;
+        let string = |key: &str| -> String {
+            metadata
+                .get(key)
+                .and_then(|v| <&str>::try_from(&**v).ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+        let artists = metadata
+            .get("xesam:artist")
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| Vec::<String>::try_from(v).ok())
+            .unwrap_or_default();
+        // length is in microseconds. position isn't part of the metadata and
+        // many players don't report it reliably, so progress stays unknown
+        let duration_ms = metadata
+            .get("mpris:length")
+            .and_then(|v| {
+                i64::try_from(&**v)
+                    .ok()
+                    .or_else(|| u64::try_from(&**v).ok().map(|length| length as i64))
+            })
+            .map(|length| length as u64 / 1000);
+        let is_playing = self.player.playback_status().await? == "Playing";
+        // the spotify desktop client uses /com/spotify/track/<id>
+        let track_id = metadata
+            .get("mpris:trackid")
+            .and_then(|v| <&ObjectPath>::try_from(&**v).ok())
+            .and_then(|path| path.as_str().strip_prefix("/com/spotify/track/"))
+            .map(String::from);
+
+        Ok(SpotifyData {
+            track_name: string("xesam:title"),
+            artist_name: artists.join(", "),
+            album_name: string("xesam:album"),
+            album_art_url: string("mpris:artUrl"),
+            is_playing,
+            progress_ms: None,
+            duration_ms,
+            source: Source::Mpris,
+            track_id,
+            // MPRIS has no notion of when things changed
+            timestamp: None,
+            // both are optional for players
+            shuffle: self.player.shuffle().await.ok(),
+            repeat: self.repeat().await.ok(),
+            // nor of playlists
+            context_uri: None,
+            context_name: None,
+            private_session: false,
+            // nor of explicit content
+            explicit: false,
+            album_id: None,
+            disc_number: metadata
+                .get("xesam:discNumber")
+                .and_then(|v| i32::try_from(&**v).ok())
+                .and_then(|number| u32::try_from(number).ok())
+                .filter(|number| *number > 0),
+            track_number: metadata
+                .get("xesam:trackNumber")
+                .and_then(|v| i32::try_from(&**v).ok())
+                .and_then(|number| u32::try_from(number).ok())
+                .filter(|number| *number > 0),
+            total_tracks: None,
+            release: None,
+        })
+    }
+
+    pub async fn play(&self) -> Result<(), anyhow::Error> {
+        Ok(self.player.play().await?)
+    }
+
+    pub async fn pause(&self) -> Result<(), anyhow::Error> {
+        Ok(self.player.pause().await?)
+    }
+
+    pub async fn play_pause(&self) -> Result<(), anyhow::Error> {
+        Ok(self.player.play_pause().await?)
+    }
+
+    pub async fn next(&self) -> Result<(), anyhow::Error> {
+        Ok(self.player.next().await?)
+    }
+
+    pub async fn previous(&self) -> Result<(), anyhow::Error> {
+        Ok(self.player.previous().await?)
+    }
+
+    pub async fn seek_by(&self, delta_ms: i64) -> Result<(), anyhow::Error> {
+        Ok(self.player.seek(delta_ms * 1000).await?)
+    }
+
+    // SetPosition is ignored by players unless it names the current track
+    pub async fn set_position(&self, position_ms: u64) -> Result<(), anyhow::Error> {
+        let track_id = self
+            .player
+            .metadata()
+            .await?
+            .get("mpris:trackid")
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| OwnedObjectPath::try_from(v).ok())
+            .ok_or_else(|| anyhow::anyhow!("player didn't report a track id"))?;
+        Ok(self
+            .player
+            .set_position(&track_id, position_ms as i64 * 1000)
+            .await?)
+    }
+
+    pub async fn shuffle(&self) -> Result<bool, anyhow::Error> {
+        Ok(self.player.shuffle().await?)
+    }
+
+    pub async fn set_shuffle(&self, shuffle: bool) -> Result<(), anyhow::Error> {
+        Ok(self.player.set_shuffle(shuffle).await?)
+    }
+
+    pub async fn repeat(&self) -> Result<RepeatState, anyhow::Error> {
+        Ok(match self.player.loop_status().await?.as_str() {
+            "Track" => RepeatState::Track,
+            "Playlist" => RepeatState::Context,
+            _ => RepeatState::Off,
+        })
+    }
+
+    pub async fn set_repeat(&self, repeat: RepeatState) -> Result<(), anyhow::Error> {
+        let loop_status = match repeat {
+            RepeatState::Off => "None",
+            RepeatState::Track => "Track",
+            RepeatState::Context => "Playlist",
+        };
+        Ok(self.player.set_loop_status(loop_status).await?)
+    }
+
+    pub async fn volume(&self) -> Result<f64, anyhow::Error> {
+        Ok(self.player.volume().await?)
+    }
+
+    pub async fn set_volume(&self, volume: f64) -> Result<(), anyhow::Error> {
+        Ok(self.player.set_volume(volume).await?)
+    }
+}
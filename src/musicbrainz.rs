@@ -0,0 +1,36 @@
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+// musicbrainz asks for an identifying user agent on every request, and at
+// most one request a second
+pub const USER_AGENT: &str = concat!(
+    "kyomi/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/MartMcMahon/kyomi )"
+);
+pub const MIN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// when musicbrainz may next be asked. shared by the enricher and the cover
+// art lookup, so between them they still keep to one a second
+static NEXT_REQUEST: Mutex<Option<Instant>> = Mutex::const_new(None);
+
+// waits until it's this request's turn, then holds the next one off
+pub async fn wait_turn() {
+    let mut next_request = NEXT_REQUEST.lock().await;
+    if let Some(at) = *next_request {
+        tokio::time::sleep_until(at).await;
+    }
+    *next_request = Some(Instant::now() + MIN_INTERVAL);
+}
+
+pub fn client() -> reqwest::Client {
+    crate::http::builder(crate::http::Endpoint::Art)
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap_or_default()
+}
+
+// for quoted terms in search queries
+pub fn escape(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+#[cfg(feature = "now-json")]
+use std::time::Duration;
+#[cfg(feature = "now-json")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::watch;
+
+#[cfg(feature = "now-json")]
+use crate::art;
+use crate::art::AlbumCover;
+use crate::privacy::Privacy;
+use crate::SpotifyData;
+
+// how long a client gets to send its request line before it's dropped
+#[cfg(feature = "now-json")]
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// set in the config under [now_json], e.g.
+//   address = "127.0.0.1:8777"
+//   allow_origin = "*"
+//   cache_control = "no-store"
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct NowJson {
+    pub address: String,
+    // sent as Access-Control-Allow-Origin, left out when unset
+    pub allow_origin: Option<String>,
+    pub cache_control: String,
+}
+
+impl Default for NowJson {
+    fn default() -> Self {
+        NowJson {
+            address: String::from("127.0.0.1:8777"),
+            allow_origin: None,
+            cache_control: String::from("no-store"),
+        }
+    }
+}
+
+// what /now.json answers with, null when nothing is playing
+#[cfg(feature = "now-json")]
+#[derive(Serialize)]
+struct NowPlaying<'a> {
+    track: &'a str,
+    artist: &'a str,
+    album: &'a str,
+    art_url: &'a str,
+    is_playing: bool,
+    progress_ms: Option<u64>,
+    duration_ms: Option<u64>,
+    source: crate::Source,
+    // the playlist or album being played from, when spotify says
+    context: Option<&'a str>,
+    context_uri: Option<&'a str>,
+    explicit: bool,
+}
+
+// cover is the one the art resolver last published, used when the track has
+// none of its own
+#[cfg(feature = "now-json")]
+fn body(data: Option<&SpotifyData>, cover: Option<&AlbumCover>) -> String {
+    let now_playing = data
+        .filter(|data| !data.track_name.is_empty())
+        .map(|data| NowPlaying {
+            track: &data.track_name,
+            artist: &data.artist_name,
+            album: &data.album_name,
+            art_url: art::art_url(data, cover),
+            is_playing: data.is_playing,
+            progress_ms: data.progress_ms,
+            duration_ms: data.duration_ms,
+            source: data.source,
+            context: data.context_name.as_deref(),
+            context_uri: data.context_uri.as_deref(),
+            explicit: data.explicit,
+        });
+    serde_json::to_string(&now_playing).unwrap()
+}
+
+// the whole response to one request line. OPTIONS answers CORS preflights,
+// HEAD gets GET's headers, Content-Length included, with no body
+#[cfg(feature = "now-json")]
+fn response(settings: &NowJson, request_line: &str, body: &str) -> String {
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or_default();
+    let path = words
+        .next()
+        .map(|target| target.split('?').next().unwrap_or_default())
+        .unwrap_or_default();
+
+    let mut headers = String::new();
+    if let Some(origin) = &settings.allow_origin {
+        headers.push_str(&format!("Access-Control-Allow-Origin: {}\r\n", origin));
+    }
+    let (status, body) = match (method, path) {
+        ("GET" | "HEAD", "/now.json") => {
+            headers.push_str(&format!(
+                "Content-Type: application/json\r\nCache-Control: {}\r\n",
+                settings.cache_control
+            ));
+            ("200 OK", body)
+        }
+        ("OPTIONS", "/now.json") => {
+            headers.push_str("Access-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n");
+            ("204 No Content", "")
+        }
+        (_, "/now.json") => ("405 Method Not Allowed", ""),
+        _ => ("404 Not Found", ""),
+    };
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        if method == "HEAD" { "" } else { body }
+    )
+}
+
+// serves /now.json for browser sources in streaming software. the body is
+// rebuilt once per change and swapped in whole, so a request never sees
+// half an update. in privacy mode it's always null
+#[cfg(feature = "now-json")]
+pub fn spawn_now_json(
+    settings: NowJson,
+    mut polls: watch::Receiver<Option<SpotifyData>>,
+    mut covers: watch::Receiver<Option<AlbumCover>>,
+    privacy: Arc<Privacy>,
+) -> Result<(), anyhow::Error> {
+    let listener = std::net::TcpListener::bind(&settings.address)?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+
+    let (body_tx, body_rx) = watch::channel(Arc::<str>::from(body(None, None)));
+    tokio::spawn(async move {
+        loop {
+            let changed = tokio::select! {
+                changed = polls.changed() => changed,
+                changed = covers.changed() => changed,
+            };
+            if changed.is_err() {
+                break;
+            }
+            let next = body(
+                polls.borrow_and_update().as_ref(),
+                covers.borrow_and_update().as_ref(),
+            );
+            body_tx.send_replace(next.into());
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let body = if privacy.is_active() {
+                Arc::from(body(None, None))
+            } else {
+                body_rx.borrow().clone()
+            };
+            let settings = settings.clone();
+            tokio::spawn(async move {
+                let mut buffer = [0; 1024];
+                let Ok(Ok(n)) = tokio::time::timeout(READ_TIMEOUT, socket.read(&mut buffer)).await
+                else {
+                    return;
+                };
+                let request = String::from_utf8_lossy(&buffer[..n]);
+                let request_line = request.lines().next().unwrap_or_default();
+                let answer = response(&settings, request_line, &body);
+                let _ = socket.write_all(answer.as_bytes()).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+// lite builds leave the server out
+#[cfg(not(feature = "now-json"))]
+pub fn spawn_now_json(
+    _settings: NowJson,
+    _polls: watch::Receiver<Option<SpotifyData>>,
+    _covers: watch::Receiver<Option<AlbumCover>>,
+    _privacy: Arc<Privacy>,
+) -> Result<(), anyhow::Error> {
+    Err(anyhow::anyhow!(
+        "kyomi was built without the now-json feature"
+    ))
+}
+
+#[cfg(feature = "now-json")]
+#[test]
+fn test_now_json_responses() {
+    let settings = NowJson {
+        allow_origin: Some(String::from("*")),
+        ..Default::default()
+    };
+    let data = SpotifyData {
+        track_name: String::from("X"),
+        artist_name: String::from("Y"),
+        is_playing: true,
+        ..Default::default()
+    };
+    let json = body(Some(&data), None);
+    assert!(json.starts_with(r#"{"track":"X","artist":"Y","#));
+    let cover = AlbumCover {
+        artist: String::from("Y"),
+        album: String::new(),
+        url: String::from("found.jpg"),
+    };
+    assert!(body(Some(&data), Some(&cover)).contains(r#""art_url":"found.jpg""#));
+
+    let ok = response(&settings, "GET /now.json?t=1 HTTP/1.1", &json);
+    assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(ok.contains("Access-Control-Allow-Origin: *\r\n"));
+    assert!(ok.contains("Cache-Control: no-store\r\n"));
+    assert!(ok.ends_with(&format!("\r\n\r\n{}", json)));
+
+    let head = response(&settings, "HEAD /now.json HTTP/1.1", &json);
+    assert!(head.contains(&format!("Content-Length: {}\r\n", json.len())));
+    assert!(head.ends_with("\r\n\r\n"));
+
+    let preflight = response(&settings, "OPTIONS /now.json HTTP/1.1", &json);
+    assert!(preflight.starts_with("HTTP/1.1 204 No Content\r\n"));
+    assert!(response(&settings, "GET / HTTP/1.1", &json).starts_with("HTTP/1.1 404"));
+    assert_eq!(body(None, None), "null");
+}
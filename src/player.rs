@@ -0,0 +1,341 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::watch;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::config::Config;
+use crate::device::{self, Devices};
+use crate::mpris::Mpris;
+use crate::playlist;
+use crate::rating::{self, Rating};
+use crate::setup;
+use crate::spotify::{self, CurrentlyPlaying, PlaybackStateResponse, Spotify, SpotifyError};
+use crate::SpotifyData;
+
+// commands the overlay can issue, independent of where playback actually lives
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerCommand {
+    PlayPause,
+    // resumes without toggling, for when something else decided to
+    Play,
+    // pauses without toggling
+    Pause,
+    Next,
+    Previous,
+    // relative seek, in milliseconds
+    SeekBy(i64),
+    // absolute position, in milliseconds
+    SetPosition(u64),
+    ToggleShuffle,
+    CycleRepeat,
+    // relative, in percentage points
+    VolumeBy(i8),
+    // absolute, in percent
+    SetVolume(u8),
+    // runs whatever the config binds to thumbs up/down
+    Rate(Rating),
+    // starts playback again on the connect device that was last playing
+    ResumeLastDevice,
+    // moves playback to the next connect device spotify knows about
+    NextDevice,
+    // appends the track to the favorites playlist
+    AddToFavorites,
+}
+
+// where playback is controlled from. both backends get the same commands so
+// the overlay behaves identically whichever one is active.
+#[derive(Clone)]
+pub enum Backend {
+    Spotify(Arc<Mutex<Spotify>>),
+    Mpris(Mpris),
+}
+
+// a token that can't be refreshed opens the browser login again at most
+// this often, so an ignored tab doesn't keep coming back
+const LOGIN_RETRY: Duration = Duration::from_secs(10 * 60);
+// how long that login waits for the browser to come back
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(120);
+
+static LAST_LOGIN: std::sync::Mutex<Option<Instant>> = std::sync::Mutex::new(None);
+
+fn login_due() -> bool {
+    let mut last = LAST_LOGIN.lock().unwrap();
+    let due = last.is_none_or(|at| at.elapsed() >= LOGIN_RETRY);
+    if due {
+        *last = Some(Instant::now());
+    }
+    due
+}
+
+// opens the browser login and waits for it without holding the client, so
+// polls and commands carry on (failing) meanwhile. the client is only locked
+// again to trade the code for the new token
+async fn login_again(
+    spotify: Arc<Mutex<Spotify>>,
+    login_url: String,
+    address: String,
+) -> Result<(), anyhow::Error> {
+    eprintln!("the spotify login has run out, approve kyomi in the browser again");
+    webbrowser::open(&login_url)?;
+    let code = tokio::time::timeout(LOGIN_TIMEOUT, setup::wait_for_redirect(&address))
+        .await
+        .map_err(|_| anyhow::anyhow!("the browser never came back"))??;
+    spotify.lock().await.token(&code).await?;
+    Ok(())
+}
+
+// locks the spotify client for a call, first making sure its token is good:
+// refreshed if it's about to expire. when it can't be, the browser login is
+// started in the background and calls fail with AuthError until it's done
+pub async fn spotify_ready(
+    spotify: &Arc<Mutex<Spotify>>,
+) -> Result<MutexGuard<'_, Spotify>, SpotifyError> {
+    // a short rate limit is sat out before taking the client, not while
+    // holding it
+    spotify::wait_out_rate_limit().await?;
+    let mut guard = spotify.lock().await;
+    let Err(e) = guard.ensure_valid_token().await else {
+        return Ok(guard);
+    };
+    if !guard.needs_login() {
+        // nothing a login would fix, the call can find out for itself
+        eprintln!("couldn't refresh the spotify token: {}", e);
+        return Ok(guard);
+    }
+    if login_due() {
+        match spotify::loopback_address(guard.redirect_uri()) {
+            Some(address) => {
+                let login_url = guard.login_url();
+                let spotify = spotify.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = login_again(spotify, login_url, address).await {
+                        eprintln!("logging in again failed: {}", e);
+                    }
+                });
+            }
+            None => eprintln!("can't log in again, the redirect uri has no port to listen on"),
+        }
+    }
+    Err(SpotifyError::AuthError(String::from(
+        "waiting for the browser login",
+    )))
+}
+
+impl Backend {
+    pub async fn now_playing(&self) -> Result<SpotifyData, anyhow::Error> {
+        match self {
+            Backend::Spotify(spotify) => {
+                let mut spotify = spotify_ready(spotify).await?;
+                let state = player_state(&spotify).await?;
+                let context = state.context.clone();
+                let mut data = SpotifyData::from(state);
+                if let Some(context) = context {
+                    data.context_name = spotify.context_name(&context).await;
+                }
+                Ok(data)
+            }
+            Backend::Mpris(mpris) => mpris.now_playing().await,
+        }
+    }
+
+    // in percent
+    pub async fn volume(&self) -> Result<u8, anyhow::Error> {
+        match self {
+            Backend::Spotify(spotify) => player_state(&*spotify_ready(spotify).await?)
+                .await?
+                .device
+                .and_then(|device| device.volume_percent)
+                .ok_or_else(|| anyhow::anyhow!("the device doesn't allow changing its volume")),
+            Backend::Mpris(mpris) => Ok((mpris.volume().await? * 100.0).round() as u8),
+        }
+    }
+
+    // get fresh credentials, where the source has any
+    pub async fn refresh_auth(&self) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Spotify(spotify) => {
+                spotify.lock().await.refresh_access_token().await?;
+                Ok(())
+            }
+            Backend::Mpris(_) => Ok(()),
+        }
+    }
+}
+
+// spawns a task that owns command routing so the winit thread never blocks on
+// network calls. commands are handled one at a time, in order. when the last
+// one arrived is published, so pollers can tell requested changes from
+// spontaneous ones
+pub fn spawn_controller(
+    backend: Backend,
+    config: Config,
+    devices: watch::Receiver<Devices>,
+) -> (
+    UnboundedSender<PlayerCommand>,
+    watch::Receiver<Option<Instant>>,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<PlayerCommand>();
+    let (commanded_tx, commanded_rx) = watch::channel(None);
+
+    tokio::spawn(async move {
+        while let Some(command) = rx.recv().await {
+            commanded_tx.send_replace(Some(Instant::now()));
+            let res = match (&backend, command) {
+                (_, PlayerCommand::Rate(Rating::Up)) => {
+                    rating::rate(&backend, &config.rate_up, Rating::Up).await
+                }
+                (_, PlayerCommand::Rate(Rating::Down)) => {
+                    rating::rate(&backend, &config.rate_down, Rating::Down).await
+                }
+                (_, PlayerCommand::AddToFavorites) => {
+                    playlist::add_playing(&backend, config.favorites_playlist.as_deref()).await
+                }
+                (Backend::Spotify(spotify), PlayerCommand::ResumeLastDevice) => {
+                    let last_seen = devices.borrow().last_seen.clone();
+                    match last_seen.and_then(|device| device.id) {
+                        Some(id) => async {
+                            spotify_ready(spotify)
+                                .await?
+                                .transfer_playback(&id, true)
+                                .await
+                        }
+                        .await
+                        .map_err(anyhow::Error::from),
+                        None => Err(anyhow::anyhow!("no device seen yet")),
+                    }
+                }
+                (Backend::Mpris(_), PlayerCommand::ResumeLastDevice) => Err(anyhow::anyhow!(
+                    "only spotify connect devices can be resumed"
+                )),
+                (Backend::Spotify(spotify), PlayerCommand::NextDevice) => {
+                    match spotify_ready(spotify).await {
+                        Ok(spotify) => move_to_next_device(&spotify).await,
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                (Backend::Mpris(_), PlayerCommand::NextDevice) => Err(anyhow::anyhow!(
+                    "only spotify connect devices can be switched between"
+                )),
+                (Backend::Spotify(spotify), _) => match spotify_ready(spotify).await {
+                    Ok(spotify) => run_spotify_command(&spotify, command).await,
+                    Err(e) => Err(e.into()),
+                },
+                (Backend::Mpris(mpris), _) => run_mpris_command(mpris, command).await,
+            };
+            if let Err(e) = res {
+                eprintln!("{:?} failed: {:?}", command, e);
+            }
+        }
+    });
+
+    (tx, commanded_rx)
+}
+
+// the one after the active device, see device::next_device
+async fn move_to_next_device(spotify: &Spotify) -> Result<(), anyhow::Error> {
+    let available = spotify.devices().await?;
+    let next = device::next_device(&available)
+        .ok_or_else(|| anyhow::anyhow!("no other device to move to"))?;
+    eprintln!("moving playback to {}", next.name);
+    let id = next.id.as_deref().unwrap_or_default();
+    spotify.transfer_playback(id, false).await?;
+    Ok(())
+}
+
+async fn run_spotify_command(
+    spotify: &Spotify,
+    command: PlayerCommand,
+) -> Result<(), anyhow::Error> {
+    let res = match command {
+        PlayerCommand::PlayPause => {
+            let playing = spotify.get_currently_playing().await?;
+            if matches!(playing, CurrentlyPlaying::Playing(res) if res.is_playing) {
+                spotify.pause().await
+            } else {
+                spotify.resume().await
+            }
+        }
+        PlayerCommand::Play => spotify.resume().await,
+        PlayerCommand::Pause => spotify.pause().await,
+        PlayerCommand::Next => spotify.next_track().await,
+        PlayerCommand::Previous => spotify.previous_track().await,
+        PlayerCommand::SeekBy(delta_ms) => {
+            let progress_ms = spotify
+                .get_currently_playing()
+                .await?
+                .playing()?
+                .progress_ms as i64;
+            spotify
+                .seek_to((progress_ms + delta_ms).max(0) as u64)
+                .await
+        }
+        PlayerCommand::SetPosition(position_ms) => spotify.seek_to(position_ms).await,
+        PlayerCommand::ToggleShuffle => {
+            let state = player_state(spotify).await?;
+            spotify.set_shuffle(!state.shuffle_state).await
+        }
+        PlayerCommand::CycleRepeat => {
+            let state = player_state(spotify).await?;
+            spotify.set_repeat(state.repeat_state.next()).await
+        }
+        PlayerCommand::VolumeBy(delta) => {
+            let Some(volume) = player_state(spotify)
+                .await?
+                .device
+                .and_then(|device| device.volume_percent)
+            else {
+                return Err(anyhow::anyhow!(
+                    "the device doesn't allow changing its volume"
+                ));
+            };
+            spotify
+                .set_volume((volume as i16 + delta as i16).clamp(0, 100) as u8)
+                .await
+        }
+        PlayerCommand::SetVolume(volume) => spotify.set_volume(volume.min(100)).await,
+        PlayerCommand::Rate(_)
+        | PlayerCommand::ResumeLastDevice
+        | PlayerCommand::NextDevice
+        | PlayerCommand::AddToFavorites => {
+            return Err(anyhow::anyhow!(
+                "{:?} is handled by spawn_controller",
+                command
+            ))
+        }
+    };
+    Ok(res?)
+}
+
+async fn player_state(spotify: &Spotify) -> Result<PlaybackStateResponse, SpotifyError> {
+    spotify
+        .get_player_state()
+        .await?
+        .ok_or(SpotifyError::NotPlaying)
+}
+
+async fn run_mpris_command(mpris: &Mpris, command: PlayerCommand) -> Result<(), anyhow::Error> {
+    match command {
+        PlayerCommand::PlayPause => mpris.play_pause().await,
+        PlayerCommand::Play => mpris.play().await,
+        PlayerCommand::Pause => mpris.pause().await,
+        PlayerCommand::Next => mpris.next().await,
+        PlayerCommand::Previous => mpris.previous().await,
+        PlayerCommand::SeekBy(delta_ms) => mpris.seek_by(delta_ms).await,
+        PlayerCommand::SetPosition(position_ms) => mpris.set_position(position_ms).await,
+        PlayerCommand::ToggleShuffle => mpris.set_shuffle(!mpris.shuffle().await?).await,
+        PlayerCommand::CycleRepeat => mpris.set_repeat(mpris.repeat().await?.next()).await,
+        PlayerCommand::VolumeBy(delta) => {
+            let volume = mpris.volume().await? + delta as f64 / 100.0;
+            mpris.set_volume(volume.clamp(0.0, 1.0)).await
+        }
+        PlayerCommand::SetVolume(volume) => mpris.set_volume(volume.min(100) as f64 / 100.0).await,
+        PlayerCommand::Rate(_)
+        | PlayerCommand::ResumeLastDevice
+        | PlayerCommand::NextDevice
+        | PlayerCommand::AddToFavorites => Err(anyhow::anyhow!(
+            "{:?} is handled by spawn_controller",
+            command
+        )),
+    }
+}
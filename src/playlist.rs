@@ -0,0 +1,77 @@
+use crate::fuzzy;
+use crate::player::{self, Backend};
+use crate::spotify::{Playlist, Spotify};
+
+// a playlist given as its name, id or spotify uri. names are matched
+// ignoring case, the first one wins if several share it, and failing that
+// the closest fuzzy match does
+fn find_playlist<'a>(playlists: &'a [Playlist], wanted: &str) -> Option<&'a Playlist> {
+    let wanted = wanted.trim();
+    let id = wanted.strip_prefix("spotify:playlist:").unwrap_or(wanted);
+    playlists
+        .iter()
+        .find(|playlist| playlist.id == id)
+        .or_else(|| {
+            playlists
+                .iter()
+                .find(|playlist| playlist.name.eq_ignore_ascii_case(wanted))
+        })
+        .or_else(|| fuzzy::best(wanted, playlists, |playlist| &playlist.name))
+}
+
+// plays one of the user's playlists from the top, answering with its name
+pub async fn play_named(spotify: &Spotify, wanted: &str) -> Result<String, anyhow::Error> {
+    let playlists = spotify.playlists().await?;
+    let playlist = find_playlist(&playlists, wanted)
+        .ok_or_else(|| anyhow::anyhow!("no playlist like {}", wanted))?;
+    spotify
+        .play_uri(&format!("spotify:playlist:{}", playlist.id))
+        .await?;
+    Ok(playlist.name.clone())
+}
+
+// appends whatever's playing to the favorites playlist
+pub async fn add_playing(backend: &Backend, favorites: Option<&str>) -> Result<(), anyhow::Error> {
+    let favorites =
+        favorites.ok_or_else(|| anyhow::anyhow!("no favorites_playlist in the config"))?;
+    let Backend::Spotify(spotify) = backend else {
+        return Err(anyhow::anyhow!("playlists need the spotify source"));
+    };
+    let track_id = backend
+        .now_playing()
+        .await?
+        .track_id
+        .ok_or_else(|| anyhow::anyhow!("nothing playing to add"))?;
+
+    let spotify = player::spotify_ready(spotify).await?;
+    let playlists = spotify.playlists().await?;
+    let playlist = find_playlist(&playlists, favorites)
+        .ok_or_else(|| anyhow::anyhow!("no playlist called {}", favorites))?;
+    spotify
+        .add_to_playlist(&playlist.id, &format!("spotify:track:{}", track_id))
+        .await?;
+    eprintln!("added to {}", playlist.name);
+    Ok(())
+}
+
+#[test]
+fn test_find_playlist() {
+    let playlist = |id: &str, name: &str| Playlist {
+        id: id.to_owned(),
+        name: name.to_owned(),
+    };
+    let playlists = vec![
+        playlist("37i9dQZF1DXcBWIGoYBM5M", "Today's Top Hits"),
+        playlist("1a2b3c", "Favorites"),
+        playlist("4d5e6f", "favorites"),
+    ];
+    let found = |wanted| find_playlist(&playlists, wanted).map(|p| p.id.as_str());
+    assert_eq!(found("FAVORITES "), Some("1a2b3c"));
+    assert_eq!(found("4d5e6f"), Some("4d5e6f"));
+    assert_eq!(
+        found("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"),
+        Some("37i9dQZF1DXcBWIGoYBM5M")
+    );
+    assert_eq!(found("top hits"), Some("37i9dQZF1DXcBWIGoYBM5M"));
+    assert_eq!(found("chill"), None);
+}
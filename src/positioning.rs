@@ -0,0 +1,75 @@
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event_loop::ActiveEventLoop;
+use winit::monitor::MonitorHandle;
+
+// where to put the widget and how big it is, both in the physical pixels of
+// the monitor it lands on. winit reports monitor geometry in physical pixels
+// on every platform, so everything is scaled up front instead of mixing
+// logical and physical coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+}
+
+// bottom-right corner of the primary monitor (or the first one, on platforms
+// like wayland that don't have a primary), `margin` away from the edges
+pub fn place_widget(
+    event_loop: &ActiveEventLoop,
+    size: LogicalSize<u32>,
+    margin: LogicalSize<u32>,
+) -> Option<Placement> {
+    let monitor = event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())?;
+    Some(bottom_right(&monitor, size, margin))
+}
+
+fn bottom_right(
+    monitor: &MonitorHandle,
+    size: LogicalSize<u32>,
+    margin: LogicalSize<u32>,
+) -> Placement {
+    corner_placement(
+        monitor.position(),
+        monitor.size(),
+        monitor.scale_factor(),
+        size,
+        margin,
+    )
+}
+
+fn corner_placement(
+    monitor_position: PhysicalPosition<i32>,
+    monitor_size: PhysicalSize<u32>,
+    scale_factor: f64,
+    size: LogicalSize<u32>,
+    margin: LogicalSize<u32>,
+) -> Placement {
+    let size: PhysicalSize<u32> = size.to_physical(scale_factor);
+    let margin: PhysicalSize<u32> = margin.to_physical(scale_factor);
+    let x = monitor_position.x + monitor_size.width as i32 - (size.width + margin.width) as i32;
+    let y = monitor_position.y + monitor_size.height as i32 - (size.height + margin.height) as i32;
+    Placement {
+        position: PhysicalPosition::new(x, y),
+        size,
+    }
+}
+
+#[test]
+fn test_corner_placement_on_scaled_secondary_monitor() {
+    // a 4k monitor at 2x, to the right of a 1080p one
+    let placement = corner_placement(
+        PhysicalPosition::new(1920, 0),
+        PhysicalSize::new(3840, 2160),
+        2.0,
+        LogicalSize::new(256, 128),
+        LogicalSize::new(16, 8),
+    );
+
+    assert_eq!(placement.size, PhysicalSize::new(512, 256));
+    assert_eq!(
+        placement.position,
+        PhysicalPosition::new(1920 + 3840 - 512 - 32, 2160 - 256 - 16)
+    );
+}
@@ -0,0 +1,211 @@
+use std::path::{Path, PathBuf};
+use winit::dpi::PhysicalSize;
+
+use crate::config::{self, Config};
+use crate::{gpu, template, theme, App, SpotifyData, HEIGHT, WIDTH};
+
+// what every preview shows, partway through so the progress bar is visible
+fn sample_track() -> SpotifyData {
+    SpotifyData {
+        track_name: String::from("Everything In Its Right Place"),
+        artist_name: String::from("Radiohead"),
+        album_name: String::from("Kid A"),
+        is_playing: false,
+        progress_ms: Some(96_000),
+        duration_ms: Some(251_000),
+        ..Default::default()
+    }
+}
+
+// `kyomi themes preview [dir]`
+pub fn themes_command(args: &[String]) {
+    if args.first().map(String::as_str) != Some("preview") {
+        println!("usage: kyomi themes preview [dir]");
+        return;
+    }
+    let dir = Path::new(args.get(1).map_or("kyomi-themes", String::as_str));
+    match preview_themes(dir) {
+        Ok(written) => {
+            for path in written {
+                println!("wrote {}", path.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("couldn't render previews: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// renders the widget offscreen once per installed theme, to <dir>/<theme>.png
+fn preview_themes(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let config = Config::load();
+    let mut app = App {
+        themes: theme::Themes::load(&config),
+        line_templates: template::parse_lines(&config.lines, config::DEFAULT_LINES),
+        expanded_templates: template::parse_lines(
+            &config.expanded_lines,
+            config::DEFAULT_EXPANDED_LINES,
+        ),
+        config,
+        spotify_data: Some(sample_track()),
+        spotify_data_fetched: Some(std::time::Instant::now()),
+        ..Default::default()
+    };
+
+    let (_instance, adapter) =
+        gpu::connect_offscreen().ok_or_else(|| anyhow::anyhow!("no graphics adapter"))?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("preview-device-descriptor"),
+            required_limits: gpu::required_limits(&adapter),
+            ..Default::default()
+        },
+        None,
+    ))?;
+    app.device = Some(device);
+    app.queue = Some(queue);
+    app.text_views = gpu::supports_text_views(&adapter, false);
+    app.init_renderer(PhysicalSize::new(WIDTH, HEIGHT));
+
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for name in app.themes.names() {
+        app.themes
+            .select(&theme::ThemeSelection::Named(name.clone()))?;
+        let texture = app.offscreen_texture(PhysicalSize::new(WIDTH, HEIGHT));
+        app.render(&texture);
+        let rgba = read_rgba(&app, &texture)?;
+
+        let path = dir.join(format!("{}.png", name));
+        std::fs::write(&path, encode_png(WIDTH, HEIGHT, &rgba))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// copies a rendered texture back to the cpu as tightly packed rgba
+pub fn read_rgba(app: &App, texture: &wgpu::Texture) -> Result<Vec<u8>, anyhow::Error> {
+    let device = app.device.as_ref().unwrap();
+    let queue = app.queue.as_ref().unwrap();
+    let size = texture.size();
+    let row_bytes = size.width * 4;
+    // copies need rows padded to a multiple of 256 bytes
+    let padded_row_bytes =
+        row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("preview readback"),
+        size: (padded_row_bytes * size.height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("preview encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |res| {
+        let _ = tx.send(res);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    rx.recv()??;
+
+    let padded = buffer.slice(..).get_mapped_range();
+    let mut rgba = Vec::with_capacity((row_bytes * size.height) as usize);
+    let bgra = matches!(
+        texture.format(),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    );
+    for row in padded.chunks(padded_row_bytes as usize) {
+        for pixel in row[..row_bytes as usize].chunks(4) {
+            if bgra {
+                rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                rgba.extend_from_slice(pixel);
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// an 8 bit rgba png, every row unfiltered
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // bit depth, rgba, deflate, no filtering, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut rows = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks((width * 4) as usize) {
+        rows.push(0);
+        rows.extend_from_slice(row);
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(
+        &mut png,
+        b"IDAT",
+        &miniz_oxide::deflate::compress_to_vec_zlib(&rows, 6),
+    );
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[test]
+fn test_png_encoding() {
+    let png = encode_png(1, 1, &[255, 0, 0, 255]);
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // IEND with its well known crc
+    assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+    let idat = &png[33 + 8..png.len() - 12 - 4];
+    assert_eq!(
+        miniz_oxide::inflate::decompress_to_vec_zlib(idat).unwrap(),
+        vec![0, 255, 0, 0, 255]
+    );
+}
@@ -0,0 +1,71 @@
+use chrono::{Local, NaiveTime};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// while active, the track is still shown in the widget but nothing leaves it:
+// settled tracks aren't broadcast, so history, scrobbles, hooks and sinks
+// never hear about them. toggled by hotkey, or on by default/on a schedule
+// from the config.
+#[derive(Debug, Default)]
+pub struct Privacy {
+    manual: AtomicBool,
+    schedule: Option<(NaiveTime, NaiveTime)>,
+}
+
+impl Privacy {
+    pub fn new(private: bool, schedule: Option<&str>) -> Self {
+        let schedule = schedule.and_then(|schedule| {
+            let parsed = parse_schedule(schedule);
+            if parsed.is_none() {
+                eprintln!(
+                    "ignoring privacy schedule {:?}, expected HH:MM-HH:MM",
+                    schedule
+                );
+            }
+            parsed
+        });
+        Privacy {
+            manual: AtomicBool::new(private),
+            schedule,
+        }
+    }
+
+    pub fn toggle(&self) {
+        self.manual.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.manual.load(Ordering::Relaxed)
+            || self
+                .schedule
+                .is_some_and(|(start, end)| in_window(Local::now().time(), start, end))
+    }
+}
+
+// "22:00-07:00"
+fn parse_schedule(schedule: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (start, end) = schedule.split_once('-')?;
+    Some((
+        NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?,
+        NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?,
+    ))
+}
+
+// windows that end before they start wrap past midnight
+fn in_window(now: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+#[test]
+fn test_schedule_wraps_midnight() {
+    let (start, end) = parse_schedule("22:00-07:00").unwrap();
+    let at = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+
+    assert!(in_window(at("23:30"), start, end));
+    assert!(in_window(at("06:59"), start, end));
+    assert!(!in_window(at("07:00"), start, end));
+    assert!(!in_window(at("12:00"), start, end));
+}
@@ -0,0 +1,186 @@
+use std::num::NonZeroU64;
+use std::ops::RangeBounds;
+
+// what the fragment shader does with a quad, mirrors the QUAD_ consts in
+// shader.wgsl
+pub const QUAD_GRADIENT: u32 = 1;
+pub const QUAD_PROGRESS: u32 = 2;
+// drawn in the animated accent rather than its own color
+pub const QUAD_ACCENT: u32 = 4;
+// the album cover, see cover.rs
+pub const QUAD_TEXTURE: u32 = 8;
+
+const MAX_QUADS: u64 = 64;
+
+// draw order, back to front. quads are painted in layer order whatever order
+// they were pushed in, and within a layer in push order. text from the brush
+// goes between Content and Overlay
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    // art, progress bar
+    Content,
+    // toasts, menus, tooltips
+    Overlay,
+}
+
+// per-element draw data, read by the shader through a dynamic offset into one
+// shared uniform buffer
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuadParams {
+    // x, y, width, height as fractions of the widget, from the top left
+    pub rect: [f32; 4],
+    pub color: [f32; 4],
+    pub flags: u32,
+    pub _padding: [u32; 3],
+}
+
+impl QuadParams {
+    pub fn new(rect: [f32; 4], color: [f32; 4], flags: u32) -> Self {
+        QuadParams {
+            rect,
+            color,
+            flags,
+            _padding: [0; 3],
+        }
+    }
+}
+
+// every quad drawn in a frame. the params are packed into a single buffer at
+// the device's uniform offset alignment, so the whole batch shares one bind
+// group and each draw only changes its offset
+pub struct QuadBatch {
+    // kept sorted by layer
+    quads: Vec<(Layer, QuadParams)>,
+    stride: u64,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl QuadBatch {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let size = std::mem::size_of::<QuadParams>() as u64;
+        let stride = aligned_stride(
+            size,
+            device.limits().min_uniform_buffer_offset_alignment as u64,
+        );
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Quad Buffer"),
+            size: stride * MAX_QUADS,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_for_quads"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: NonZeroU64::new(size),
+                },
+                count: None,
+            }],
+        });
+
+        // binds a single QuadParams, the offset picks which one
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(size),
+                }),
+            }],
+        });
+
+        QuadBatch {
+            quads: Vec::new(),
+            stride,
+            buffer,
+            bind_group,
+            bind_group_layout,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.quads.clear();
+    }
+
+    pub fn push(&mut self, layer: Layer, quad: QuadParams) {
+        if self.quads.len() as u64 >= MAX_QUADS {
+            eprintln!("too many quads, dropping one");
+            return;
+        }
+        insert_sorted(&mut self.quads, layer, quad);
+    }
+
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        if self.quads.is_empty() {
+            return;
+        }
+        let mut bytes = vec![0u8; (self.stride * self.quads.len() as u64) as usize];
+        for (i, (_, quad)) in self.quads.iter().enumerate() {
+            let start = i * self.stride as usize;
+            let quad = bytemuck::bytes_of(quad);
+            bytes[start..start + quad.len()].copy_from_slice(quad);
+        }
+        queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+
+    // draws the quads in the given layers, back to front. expects the
+    // pipeline and the quad's vertex/index buffers to be set
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        group: u32,
+        num_indices: u32,
+        layers: impl RangeBounds<Layer>,
+    ) {
+        for (i, (layer, _)) in self.quads.iter().enumerate() {
+            if !layers.contains(layer) {
+                continue;
+            }
+            let offset = (i as u64 * self.stride) as wgpu::DynamicOffset;
+            render_pass.set_bind_group(group, &self.bind_group, &[offset]);
+            render_pass.draw_indexed(0..num_indices, 0, 0..1);
+        }
+    }
+}
+
+// after everything already in the same layer, so ties keep push order
+fn insert_sorted(quads: &mut Vec<(Layer, QuadParams)>, layer: Layer, quad: QuadParams) {
+    let at = quads.partition_point(|(other, _)| *other <= layer);
+    quads.insert(at, (layer, quad));
+}
+
+fn aligned_stride(size: u64, alignment: u64) -> u64 {
+    size.div_ceil(alignment) * alignment
+}
+
+#[test]
+fn test_stride_respects_offset_alignment() {
+    assert_eq!(aligned_stride(48, 256), 256);
+    assert_eq!(aligned_stride(300, 256), 512);
+    assert_eq!(aligned_stride(48, 16), 48);
+}
+
+#[test]
+fn test_layers_paint_in_order() {
+    let quad = |flags| QuadParams::new([0.0; 4], [0.0; 4], flags);
+    let mut quads = Vec::new();
+    insert_sorted(&mut quads, Layer::Overlay, quad(1));
+    insert_sorted(&mut quads, Layer::Background, quad(2));
+    insert_sorted(&mut quads, Layer::Content, quad(3));
+    insert_sorted(&mut quads, Layer::Background, quad(4));
+
+    let flags: Vec<u32> = quads.iter().map(|(_, quad)| quad.flags).collect();
+    assert_eq!(flags, vec![2, 4, 3, 1]);
+}
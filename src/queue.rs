@@ -0,0 +1,211 @@
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+
+use crate::player::{self, Backend};
+use crate::spotify::Queued;
+use crate::toast::Toast;
+use crate::{SpotifyData, HIDDEN_EXPLICIT_TITLE};
+
+// how much of the queue is shown under the current track
+const PREVIEW_LEN: usize = 3;
+
+// what's queued, as (artist, name)
+pub type Upcoming = Vec<(String, String)>;
+
+pub fn preview_lines(upcoming: &[(String, String)]) -> Vec<String> {
+    upcoming
+        .iter()
+        .take(PREVIEW_LEN)
+        .enumerate()
+        .map(|(i, (artist, track))| {
+            let label = if i == 0 { "next" } else { "then" };
+            if artist.is_empty() {
+                format!("{}: {}", label, track)
+            } else {
+                format!("{}: {} - {}", label, artist, track)
+            }
+        })
+        .collect()
+}
+
+// what's queued as it's shown, explicit titles left out with hide_explicit
+// like the current track's
+fn as_shown(queued: Vec<Queued>, hide_explicit: bool) -> Upcoming {
+    queued
+        .into_iter()
+        .map(|item| match hide_explicit && item.explicit {
+            true => (item.artist, String::from(HIDDEN_EXPLICIT_TITLE)),
+            false => (item.artist, item.name),
+        })
+        .collect()
+}
+
+// keeps what's up next current and adds uris to the queue. the queue is
+// fetched again whenever a track settles, and after anything is added to it,
+// as long as something shows it. only spotify has a queue to read, with MPRIS
+// both stay idle
+pub fn spawn_queue(
+    backend: Backend,
+    mut settled: broadcast::Receiver<SpotifyData>,
+    fetch: bool,
+    hide_explicit: bool,
+) -> (watch::Receiver<Upcoming>, UnboundedSender<String>) {
+    let (upcoming_tx, upcoming_rx) = watch::channel(Vec::new());
+    let (add_tx, mut add_rx) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        let Backend::Spotify(spotify) = backend else {
+            while add_rx.recv().await.is_some() {
+                eprintln!("only spotify has a queue to add to");
+            }
+            return;
+        };
+        loop {
+            tokio::select! {
+                track = settled.recv() => match track {
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                uri = add_rx.recv() => {
+                    let Some(uri) = uri else {
+                        break;
+                    };
+                    let res = async {
+                        player::spotify_ready(&spotify)
+                            .await?
+                            .add_to_queue(&uri)
+                            .await
+                    }
+                    .await;
+                    if let Err(e) = res {
+                        eprintln!("couldn't queue {}: {:?}", uri, e);
+                        continue;
+                    }
+                }
+            }
+            if !fetch {
+                continue;
+            }
+            let res = async { player::spotify_ready(&spotify).await?.get_queue().await }.await;
+            match res {
+                Ok(queued) => {
+                    upcoming_tx.send_replace(as_shown(queued, hide_explicit));
+                }
+                Err(e) => eprintln!("couldn't get the queue: {:?}", e),
+            }
+        }
+    });
+    (upcoming_rx, add_tx)
+}
+
+// how long after this poll the track is `lead` from its end, None when it
+// isn't playing or its length isn't known
+fn toast_due(poll: &SpotifyData, lead: Duration) -> Option<Duration> {
+    if !poll.is_playing {
+        return None;
+    }
+    let remaining_ms = poll.duration_ms?.saturating_sub(poll.progress_ms?);
+    Some(Duration::from_millis(remaining_ms).saturating_sub(lead))
+}
+
+// once per track, `lead` before it ends, a toast with the first thing in the
+// queue. it lasts until about when that track starts
+pub fn spawn_up_next_toast(
+    mut polls: watch::Receiver<Option<SpotifyData>>,
+    upcoming: watch::Receiver<Upcoming>,
+    lead: Duration,
+) -> watch::Receiver<Option<Toast>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        // the track the toast was shown for
+        let mut shown: Option<SpotifyData> = None;
+        let mut due: Option<(SpotifyData, Instant)> = None;
+        loop {
+            let at = due.as_ref().map(|(_, at)| *at);
+            tokio::select! {
+                changed = polls.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    due = polls.borrow_and_update().clone().and_then(|poll| {
+                        let after = toast_due(&poll, lead)?;
+                        Some((poll, Instant::now() + after))
+                    });
+                }
+                _ = tokio::time::sleep_until(at.unwrap_or_else(Instant::now)), if at.is_some() => {
+                    let (track, _) = due.take().unwrap();
+                    if shown.as_ref().is_some_and(|shown| shown.same_track(&track)) {
+                        continue;
+                    }
+                    let Some((artist, name)) = upcoming.borrow().first().cloned() else {
+                        continue;
+                    };
+                    let mut lines = vec![format!("up next: {}", name)];
+                    if !artist.is_empty() {
+                        lines.push(artist);
+                    }
+                    if tx.send(Some(Toast::new(lines).lasting(lead))).is_err() {
+                        break;
+                    }
+                    shown = Some(track);
+                }
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_up_next_toast_timing() {
+    let poll = |progress_ms, is_playing| SpotifyData {
+        is_playing,
+        progress_ms: Some(progress_ms),
+        duration_ms: Some(180_000),
+        ..Default::default()
+    };
+    let lead = Duration::from_secs(10);
+    assert_eq!(
+        toast_due(&poll(60_000, true), lead),
+        Some(Duration::from_secs(110))
+    );
+    assert_eq!(toast_due(&poll(175_000, true), lead), Some(Duration::ZERO));
+    assert_eq!(toast_due(&poll(60_000, false), lead), None);
+    let unknown = SpotifyData {
+        is_playing: true,
+        ..Default::default()
+    };
+    assert_eq!(toast_due(&unknown, lead), None);
+}
+
+#[test]
+fn test_queue_preview() {
+    let upcoming: Vec<(String, String)> = ["A", "B", "C", "D"]
+        .iter()
+        .map(|track| (String::from("Artist"), String::from(*track)))
+        .collect();
+    assert_eq!(
+        preview_lines(&upcoming),
+        ["next: Artist - A", "then: Artist - B", "then: Artist - C"]
+    );
+    assert_eq!(
+        preview_lines(&[(String::new(), String::from("Episode"))]),
+        ["next: Episode"]
+    );
+
+    let queued = || {
+        vec![Queued {
+            artist: String::from("Artist"),
+            name: String::from("Title"),
+            explicit: true,
+        }]
+    };
+    assert_eq!(
+        preview_lines(&as_shown(queued(), false)),
+        ["next: Artist - Title"]
+    );
+    assert_eq!(
+        preview_lines(&as_shown(queued(), true)),
+        ["next: Artist - explicit track"]
+    );
+}
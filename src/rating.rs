@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::player::{self, Backend};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+// what a thumbs up/down does, set per rating in the config, e.g.
+//   rate_up = { type = "save_track" }
+//   rate_down = { type = "webhook", url = "http://localhost:5000/rate" }
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RatingAction {
+    Nothing,
+    // add to / remove from the spotify library
+    SaveTrack,
+    RemoveTrack,
+    // POST the track as json
+    Webhook { url: String },
+}
+
+pub async fn rate(
+    backend: &Backend,
+    action: &RatingAction,
+    rating: Rating,
+) -> Result<(), anyhow::Error> {
+    let track = backend.now_playing().await?;
+
+    match action {
+        RatingAction::Nothing => Ok(()),
+        RatingAction::SaveTrack | RatingAction::RemoveTrack => {
+            let spotify = match backend {
+                Backend::Spotify(spotify) => spotify,
+                _ => return Err(anyhow::anyhow!("saving tracks needs the spotify source")),
+            };
+            let track_id = track
+                .track_id
+                .ok_or_else(|| anyhow::anyhow!("nothing playing to rate"))?;
+            let spotify = player::spotify_ready(spotify).await?;
+            match action {
+                RatingAction::SaveTrack => spotify.save_track(&track_id).await?,
+                _ => spotify.remove_saved_track(&track_id).await?,
+            }
+            Ok(())
+        }
+        RatingAction::Webhook { url } => {
+            let body = serde_json::json!({
+                "rating": match rating {
+                    Rating::Up => "up",
+                    Rating::Down => "down",
+                },
+                "track": track.track_name,
+                "artist": track.artist_name,
+                "album": track.album_name,
+                "track_id": track.track_id,
+            });
+            crate::http::client(Endpoint::Polling)
+                .post(url)
+                .json(&body)
+                .send_retrying(Endpoint::Polling)
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+    }
+}
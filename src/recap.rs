@@ -0,0 +1,231 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Timelike, Weekday};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::history::{self, Play, SkipStats};
+use crate::history_store::{self, HistoryBackend};
+use crate::locale::Formatter;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// the notification goes out on monday between these hours
+const MORNING: std::ops::Range<u32> = 8..12;
+const TOP: usize = 3;
+
+// what was listened to in the last full week
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recap {
+    pub plays: usize,
+    pub minutes: u64,
+    // with how many plays, most first
+    pub top_artists: Vec<(String, usize)>,
+    pub top_tracks: Vec<(String, usize)>,
+    pub most_skipped: Option<String>,
+}
+
+// midnight at the start of the monday of the week `now` is in
+fn week_start(now: DateTime<Local>) -> DateTime<Local> {
+    let days_since_monday = now.weekday().num_days_from_monday() as u64;
+    let monday = now.date_naive() - chrono::Days::new(days_since_monday);
+    Local
+        .from_local_datetime(&monday.and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(now)
+}
+
+fn ranked(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(TOP);
+    ranked
+}
+
+impl Recap {
+    // monday to monday, the week before the one `now` is in. skips don't
+    // count towards the top artists and tracks
+    pub fn last_week(plays: &[Play], now: DateTime<Local>) -> Self {
+        let end = week_start(now);
+        let start = week_start(end - chrono::Days::new(1));
+        let range = start.timestamp() as u64..end.timestamp() as u64;
+        let week: Vec<Play> = plays
+            .iter()
+            .filter(|play| range.contains(&play.played_at))
+            .cloned()
+            .collect();
+
+        let mut artists: HashMap<String, usize> = HashMap::new();
+        let mut tracks: HashMap<String, usize> = HashMap::new();
+        for play in week.iter().filter(|play| !play.skipped) {
+            *artists.entry(play.artist.clone()).or_default() += 1;
+            *tracks
+                .entry(format!("{} - {}", play.artist, play.track))
+                .or_default() += 1;
+        }
+        Recap {
+            plays: week.len(),
+            minutes: week.iter().map(|play| play.listened_ms).sum::<u64>() / 60_000,
+            top_artists: ranked(artists),
+            top_tracks: ranked(tracks),
+            most_skipped: SkipStats::from_plays(&week)
+                .most_skipped
+                .into_iter()
+                .next()
+                .map(|(track, _)| track),
+        }
+    }
+
+    // the recap page, most important first since the collapsed widget only
+    // has room for the first two
+    pub fn lines(&self, format: &Formatter) -> Vec<String> {
+        if self.plays == 0 {
+            return vec![String::from("nothing played last week")];
+        }
+        let mut lines = vec![format!(
+            "last week: {} min, {} plays",
+            format.number(self.minutes),
+            format.number(self.plays as u64)
+        )];
+        lines.extend(
+            self.top_artists
+                .first()
+                .map(|(artist, _)| format!("top artist: {}", artist)),
+        );
+        lines.extend(
+            self.top_tracks
+                .first()
+                .map(|(track, _)| format!("top track: {}", track)),
+        );
+        lines.extend(
+            self.most_skipped
+                .as_ref()
+                .map(|track| format!("most skipped: {}", track)),
+        );
+        lines
+    }
+}
+
+// holds the monday of the last week a notification went out for, so
+// restarting on a monday morning doesn't send another
+fn notified_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+        .join("recap-notified")
+}
+
+fn already_notified(week: DateTime<Local>) -> bool {
+    std::fs::read_to_string(notified_path()).is_ok_and(|raw| raw.trim() == week.to_rfc3339())
+}
+
+fn mark_notified(week: DateTime<Local>) {
+    let path = notified_path();
+    let res = std::fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| std::fs::write(&path, week.to_rfc3339()));
+    if let Err(e) = res {
+        eprintln!("couldn't write {}: {}", path.display(), e);
+    }
+}
+
+fn notification_due(now: DateTime<Local>) -> bool {
+    now.weekday() == Weekday::Mon && MORNING.contains(&now.hour())
+}
+
+#[cfg(target_os = "macos")]
+fn notify(title: &str, body: &str) -> std::io::Result<()> {
+    let script = format!(
+        "display notification {:?} with title {:?}",
+        body.replace('"', "'"),
+        title
+    );
+    std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .status()
+        .map(|_| ())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn notify(title: &str, body: &str) -> std::io::Result<()> {
+    std::process::Command::new("notify-send")
+        .args(["--app-name=kyomi", title, body])
+        .status()
+        .map(|_| ())
+}
+
+// works out last week's recap, again each time a week starts, and sends it
+// as a desktop notification on monday morning if `notification` is on
+pub fn spawn_recap(
+    backend: HistoryBackend,
+    locale: Option<String>,
+    notification: bool,
+) -> watch::Receiver<Vec<String>> {
+    let (tx, rx) = watch::channel(Vec::new());
+    tokio::spawn(async move {
+        let format = Formatter::new(locale.as_deref());
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut week = None;
+        while !tx.is_closed() {
+            interval.tick().await;
+            let now = Local::now();
+            let start = week_start(now);
+            if week != Some(start) {
+                week = Some(start);
+                let store = history_store::open(backend);
+                let recap = Recap::last_week(&history::load(store.as_ref()), now);
+                tx.send_replace(recap.lines(&format));
+            }
+            if notification && notification_due(now) && !already_notified(start) {
+                let lines = tx.borrow().clone();
+                if let Err(e) = notify("your week in music", &lines.join("\n")) {
+                    eprintln!("couldn't send the weekly recap: {}", e);
+                }
+                mark_notified(start);
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_recap_covers_last_week() {
+    // a wednesday
+    let now = Local.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+    let at = |month, day, hour| {
+        Local
+            .with_ymd_and_hms(2024, month, day, hour, 0, 0)
+            .unwrap()
+            .timestamp() as u64
+    };
+    assert_eq!(week_start(now).timestamp() as u64, at(5, 13, 0));
+
+    let play = |artist: &str, track: &str, played_at, listened_secs: u64| Play {
+        artist: artist.to_owned(),
+        track: track.to_owned(),
+        played_at,
+        listened_ms: listened_secs * 1000,
+        skipped: listened_secs < 30,
+        ..Default::default()
+    };
+    let plays = vec![
+        // the week before last, and this week
+        play("Old", "Song", at(5, 5, 23), 200),
+        play("Now", "Song", at(5, 14, 9), 200),
+        play("A", "One", at(5, 6, 0), 240),
+        play("A", "Two", at(5, 8, 10), 240),
+        play("B", "One", at(5, 12, 23), 180),
+        play("B", "Skip", at(5, 10, 10), 5),
+        play("B", "Skip", at(5, 11, 10), 5),
+    ];
+    let recap = Recap::last_week(&plays, now);
+    assert_eq!(recap.plays, 5);
+    assert_eq!(recap.minutes, 11);
+    assert_eq!(recap.top_artists[0], (String::from("A"), 2));
+    assert_eq!(recap.most_skipped.as_deref(), Some("B - Skip"));
+    assert_eq!(
+        recap.lines(&Formatter::new(Some("C")))[..2],
+        [
+            String::from("last week: 11 min, 5 plays"),
+            String::from("top artist: A"),
+        ]
+    );
+}
@@ -0,0 +1,55 @@
+// a latin reading of text in other scripts, for a line under titles that
+// can't otherwise be read. japanese is read into romaji with kakasi's
+// dictionary, everything else (pinyin for chinese, revised romanization for
+// korean, and so on) comes from any_ascii
+
+// latin, including the accented letters of e.g. "Beyoncé" or "Sigur Rós"
+fn is_latin(c: char) -> bool {
+    matches!(c as u32, 0..=0x024f | 0x1e00..=0x1eff)
+}
+
+#[cfg(feature = "romanize")]
+fn transliterate(text: &str, kanji_as_japanese: bool) -> String {
+    let japanese = match kakasi::is_japanese(text) {
+        kakasi::IsJapanese::True => true,
+        kakasi::IsJapanese::Maybe => kanji_as_japanese,
+        kakasi::IsJapanese::False => false,
+    };
+    if japanese {
+        kakasi::convert(text).romaji
+    } else {
+        any_ascii::any_ascii(text)
+    }
+}
+
+// built without the romanize feature there's nothing to read it with
+#[cfg(not(feature = "romanize"))]
+fn transliterate(text: &str, _kanji_as_japanese: bool) -> String {
+    text.to_owned()
+}
+
+// None when the text is latin already. kanji alone could be chinese or
+// japanese, so they're read as japanese only when asked to
+pub fn romanized(text: &str, kanji_as_japanese: bool) -> Option<String> {
+    if !text.chars().any(|c| c.is_alphabetic() && !is_latin(c)) {
+        return None;
+    }
+    let romanized = transliterate(text, kanji_as_japanese);
+    let romanized = romanized.trim();
+    (!romanized.is_empty() && romanized != text.trim()).then(|| romanized.to_owned())
+}
+
+#[cfg(feature = "romanize")]
+#[test]
+fn test_romanized() {
+    assert_eq!(romanized("Sigur Rós - Hoppípolla", false), None);
+    assert_eq!(romanized("", false), None);
+    assert_eq!(
+        romanized("こんにちは", false).as_deref(),
+        Some("konnichiha")
+    );
+    assert_eq!(romanized("안녕", false).as_deref(), Some("AnNyeong"));
+    assert_eq!(romanized("Кино", false).as_deref(), Some("Kino"));
+    assert_eq!(romanized("北京", false).as_deref(), Some("BeiJing"));
+    assert_eq!(romanized("東京", true).as_deref(), Some("toukyou"));
+}
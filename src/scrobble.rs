@@ -0,0 +1,183 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::{Source, SpotifyData};
+
+// listenbrainz counts a listen after half the track or four minutes,
+// whichever comes first. really short tracks never count
+const MAX_LISTEN_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+const MIN_TRACK_LENGTH: Duration = Duration::from_secs(30);
+
+fn listen_threshold(duration_ms: Option<u64>) -> Option<Duration> {
+    match duration_ms.map(Duration::from_millis) {
+        Some(duration) if duration < MIN_TRACK_LENGTH => None,
+        Some(duration) => Some((duration / 2).min(MAX_LISTEN_THRESHOLD)),
+        // unknown length, wait for the cap
+        None => Some(MAX_LISTEN_THRESHOLD),
+    }
+}
+
+// with a crossfade a track stops being current that long before it stops
+// playing, so a track the next one took over from was heard for that much
+// more than it was current
+fn listened(current_for: Duration, threshold: Duration, crossfade: Duration) -> bool {
+    current_for + crossfade >= threshold
+}
+
+pub struct ListenBrainz {
+    client: reqwest::Client,
+    token: String,
+}
+
+impl ListenBrainz {
+    pub fn new(token: String) -> Self {
+        ListenBrainz {
+            client: crate::http::client(Endpoint::Polling),
+            token,
+        }
+    }
+
+    // listen_type is "playing_now" or "single", only the latter gets a time
+    async fn submit(
+        &self,
+        listen_type: &str,
+        track: &SpotifyData,
+        listened_at: Option<u64>,
+    ) -> Result<(), anyhow::Error> {
+        let mut additional_info = serde_json::json!({
+            "submission_client": "kyomi",
+            "submission_client_version": env!("CARGO_PKG_VERSION"),
+            "media_player": match track.source {
+                Source::Spotify => "Spotify",
+                Source::Mpris => "MPRIS",
+            },
+        });
+        if let Some(duration_ms) = track.duration_ms {
+            additional_info["duration_ms"] = duration_ms.into();
+        }
+        if let Some(track_id) = &track.track_id {
+            additional_info["spotify_id"] =
+                format!("https://open.spotify.com/track/{}", track_id).into();
+        }
+
+        let mut listen = serde_json::json!({
+            "track_metadata": {
+                "artist_name": track.artist_name,
+                "track_name": track.track_name,
+                "release_name": track.album_name,
+                "additional_info": additional_info,
+            },
+        });
+        if let Some(listened_at) = listened_at {
+            listen["listened_at"] = listened_at.into();
+        }
+
+        self.client
+            .post("https://api.listenbrainz.org/1/submit-listens")
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&serde_json::json!({
+                "listen_type": listen_type,
+                "payload": [listen],
+            }))
+            .send_retrying(Endpoint::Polling)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    // a finished listen, stamped with when it started
+    async fn submit_listen(&self, track: &SpotifyData, started_at: SystemTime) {
+        let listened_at = started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Err(e) = self.submit("single", track, Some(listened_at)).await {
+            eprintln!("listenbrainz listen failed: {:?}", e);
+        }
+    }
+}
+
+// reports each settled track as now playing, then as a listen once it's been
+// current long enough, or when the next one comes after it was, crossfade
+// included. settled tracks are already debounced and kept out of privacy mode
+// by the aggregator
+pub fn spawn_listenbrainz(
+    listenbrainz: ListenBrainz,
+    mut settled: broadcast::Receiver<SpotifyData>,
+    crossfade: Duration,
+) {
+    tokio::spawn(async move {
+        // the current track, when it started and how long it has to be
+        // heard to count as a listen
+        let mut pending: Option<(SpotifyData, SystemTime, Instant, Duration)> = None;
+        loop {
+            let listen_due = pending
+                .as_ref()
+                .map(|(_, _, started, threshold)| *started + *threshold);
+            tokio::select! {
+                track = settled.recv() => {
+                    let track = match track {
+                        Ok(track) => track,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    // the last one, if the crossfade got it over the line.
+                    // otherwise it was skipped before it counted
+                    if let Some((last, started_at, started, threshold)) = pending.take() {
+                        if listened(started.elapsed(), threshold, crossfade) {
+                            listenbrainz.submit_listen(&last, started_at).await;
+                        }
+                    }
+                    if let Err(e) = listenbrainz.submit("playing_now", &track, None).await {
+                        eprintln!("listenbrainz now playing failed: {:?}", e);
+                    }
+                    pending = listen_threshold(track.duration_ms)
+                        .map(|threshold| (track, SystemTime::now(), Instant::now(), threshold));
+                }
+                _ = tokio::time::sleep_until(listen_due.unwrap_or_else(Instant::now)),
+                    if listen_due.is_some() =>
+                {
+                    let (track, started_at, _, _) = pending.take().unwrap();
+                    listenbrainz.submit_listen(&track, started_at).await;
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn test_listen_threshold() {
+    let minutes = |m: u64| Some(m * 60 * 1000);
+    assert_eq!(listen_threshold(minutes(3)), Some(Duration::from_secs(90)));
+    assert_eq!(listen_threshold(minutes(20)), Some(MAX_LISTEN_THRESHOLD));
+    // the cap is reached right at eight minutes
+    assert_eq!(listen_threshold(Some(480_000)), Some(MAX_LISTEN_THRESHOLD));
+    assert_eq!(
+        listen_threshold(Some(479_998)),
+        Some(Duration::from_millis(239_999))
+    );
+    // the shortest track that counts
+    assert_eq!(
+        listen_threshold(Some(30_000)),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(listen_threshold(Some(29_999)), None);
+    assert_eq!(listen_threshold(None), Some(MAX_LISTEN_THRESHOLD));
+}
+
+#[test]
+fn test_crossfade_counts_toward_a_listen() {
+    let threshold = Duration::from_secs(90);
+    let ms = Duration::from_millis;
+    assert!(listened(threshold, threshold, Duration::ZERO));
+    assert!(!listened(threshold - ms(1), threshold, Duration::ZERO));
+
+    let crossfade = Duration::from_secs(12);
+    assert!(listened(ms(78_000), threshold, crossfade));
+    assert!(!listened(ms(77_999), threshold, crossfade));
+    // a crossfade longer than the threshold counts any track the next one
+    // took over from
+    assert!(listened(Duration::ZERO, ms(10_000), crossfade));
+}
@@ -0,0 +1,236 @@
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{oneshot, watch};
+use tokio::time::Instant;
+
+use crate::fuzzy;
+use crate::player::{self, Backend};
+use crate::playlist;
+use crate::spotify::{SearchResults, SearchType, SimplifiedArtistObject};
+
+// shown under the query
+const RESULTS: usize = 4;
+// searching waits for typing to stop this long
+const TYPING_PAUSE: Duration = Duration::from_millis(300);
+
+// something the launcher can play
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub uri: String,
+    pub label: String,
+}
+
+#[derive(Debug)]
+pub enum Request {
+    Search(String),
+    Play(String),
+    // `kyomi ctl play-playlist`, answered with the playlist's name
+    PlayPlaylist(String, oneshot::Sender<Result<String, String>>),
+}
+
+// the quick search box, typed into while it's open. / opens it, as does
+// `kyomi ctl search`, which a desktop shortcut can run to summon it from
+// anywhere
+#[derive(Debug, Default)]
+pub struct Launcher {
+    pub query: String,
+    selected: usize,
+}
+
+impl Launcher {
+    pub fn typed(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    // up is -1, down is 1. stays within the hits
+    pub fn select_by(&mut self, delta: i32, hits: usize) {
+        let last = hits.saturating_sub(1) as i32;
+        self.selected = (self.selected as i32 + delta).clamp(0, last) as usize;
+    }
+
+    pub fn chosen<'a>(&self, hits: &'a [Hit]) -> Option<&'a Hit> {
+        hits.get(self.selected)
+    }
+
+    pub fn lines(&self, hits: &[Hit]) -> Vec<String> {
+        let mut lines = vec![format!("/ {}_", self.query)];
+        if self.query.trim().is_empty() {
+            return lines;
+        }
+        lines.extend(hits.iter().enumerate().map(|(i, hit)| {
+            let marker = if i == self.selected { ">" } else { " " };
+            format!("{} {}", marker, hit.label)
+        }));
+        lines
+    }
+}
+
+fn artist_names(artists: &[SimplifiedArtistObject]) -> String {
+    let names: Vec<&str> = artists.iter().map(|artist| artist.name.as_str()).collect();
+    names.join(", ")
+}
+
+// mostly tracks, those closest to what was typed first, then the best
+// artist and album so either can be played from the top
+fn hits(query: &str, results: SearchResults) -> Vec<Hit> {
+    let mut tracks: Vec<Hit> = results
+        .tracks
+        .into_iter()
+        .map(|track| Hit {
+            label: format!("{} - {}", artist_names(&track.artists), track.name),
+            uri: track.uri,
+        })
+        .collect();
+    fuzzy::rank(query, &mut tracks, |hit| &hit.label);
+    let artists = results.artists.into_iter().map(|artist| Hit {
+        label: format!("{} (artist)", artist.name),
+        uri: artist.uri,
+    });
+    let albums = results.albums.into_iter().map(|album| {
+        let year = album
+            .release()
+            .map(|release| format!(" ({})", release.year()))
+            .unwrap_or_default();
+        Hit {
+            label: format!("{} - {}{}", artist_names(&album.artists), album.name, year),
+            uri: album.uri,
+        }
+    });
+    tracks
+        .into_iter()
+        .take(RESULTS - 2)
+        .chain(artists.take(1))
+        .chain(albums.take(1))
+        .collect()
+}
+
+// searches spotify for what's typed into the launcher, once typing stops,
+// and plays what's picked. with MPRIS there's nothing to search
+pub fn spawn_search(backend: Backend) -> (UnboundedSender<Request>, watch::Receiver<Vec<Hit>>) {
+    let (request_tx, mut request_rx) = mpsc::unbounded_channel::<Request>();
+    let (hits_tx, hits_rx) = watch::channel(Vec::new());
+    tokio::spawn(async move {
+        let Backend::Spotify(spotify) = backend else {
+            while let Some(request) = request_rx.recv().await {
+                match request {
+                    Request::PlayPlaylist(_, answer) => {
+                        let _ = answer.send(Err(String::from("playlists need the spotify source")));
+                    }
+                    _ => eprintln!("searching needs the spotify source"),
+                }
+            }
+            return;
+        };
+        let mut pending: Option<(String, Instant)> = None;
+        loop {
+            let due = pending.as_ref().map(|(_, at)| *at);
+            tokio::select! {
+                request = request_rx.recv() => match request {
+                    Some(Request::Search(query)) => {
+                        pending = Some((query, Instant::now() + TYPING_PAUSE));
+                    }
+                    Some(Request::Play(uri)) => {
+                        let res = async {
+                            player::spotify_ready(&spotify).await?.play_uri(&uri).await
+                        }
+                        .await;
+                        if let Err(e) = res {
+                            eprintln!("couldn't play {}: {:?}", uri, e);
+                        }
+                    }
+                    Some(Request::PlayPlaylist(name, answer)) => {
+                        let res = match player::spotify_ready(&spotify).await {
+                            Ok(spotify) => playlist::play_named(&spotify, &name).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        let _ = answer.send(res.map_err(|e| e.to_string()));
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                    let (query, _) = pending.take().unwrap();
+                    if query.trim().is_empty() {
+                        hits_tx.send_replace(Vec::new());
+                        continue;
+                    }
+                    let res = async {
+                        player::spotify_ready(&spotify)
+                            .await?
+                            .search(&query, &[SearchType::Track, SearchType::Artist, SearchType::Album], RESULTS)
+                            .await
+                    }
+                    .await;
+                    match res {
+                        Ok(results) => {
+                            hits_tx.send_replace(hits(&query, results));
+                        }
+                        Err(e) => eprintln!("search for {:?} failed: {:?}", query, e),
+                    }
+                }
+            }
+        }
+    });
+    (request_tx, hits_rx)
+}
+
+#[test]
+fn test_hits_mix_types() {
+    let results: SearchResults = serde_json::from_str(
+        r#"{
+            "tracks": {"items": [
+                {"uri": "t1", "name": "One", "artists": [{"id": "", "name": "A", "href": ""}]},
+                {"uri": "t2", "name": "Two", "artists": [{"id": "", "name": "B", "href": ""}]},
+                {"uri": "t3", "name": "Three", "artists": []}
+            ]},
+            "artists": {"items": [{"uri": "a1", "name": "A"}, {"uri": "a2", "name": "B"}]},
+            "albums": {"items": [{"uri": "r1", "name": "Record",
+                "artists": [{"id": "", "name": "A", "href": ""}, {"id": "", "name": "B", "href": ""}],
+                "release_date": "2012-03-02"}]}
+        }"#,
+    )
+    .unwrap();
+    let labels: Vec<String> = hits("b two", results.clone())
+        .into_iter()
+        .map(|hit| hit.label)
+        .collect();
+    assert_eq!(
+        labels,
+        ["B - Two", "A - One", "A (artist)", "A, B - Record (2012)"]
+    );
+    // spotify's order stands between equally good matches
+    let labels: Vec<String> = hits("", results).into_iter().map(|hit| hit.label).collect();
+    assert_eq!(labels[..2], ["A - One", "B - Two"]);
+}
+
+#[test]
+fn test_launcher() {
+    let hit = |label: &str| Hit {
+        uri: format!("spotify:track:{}", label),
+        label: label.to_owned(),
+    };
+    let hits = vec![hit("a"), hit("b")];
+    let mut launcher = Launcher::default();
+    assert_eq!(launcher.lines(&hits), ["/ _"]);
+
+    launcher.typed("ab");
+    launcher.backspace();
+    launcher.typed("c");
+    launcher.select_by(1, hits.len());
+    launcher.select_by(1, hits.len());
+    assert_eq!(launcher.lines(&hits), ["/ ac_", "  a", "> b"]);
+    assert_eq!(launcher.chosen(&hits), Some(&hits[1]));
+
+    launcher.select_by(-5, hits.len());
+    assert_eq!(launcher.chosen(&hits), Some(&hits[0]));
+    // typing starts again from the top
+    launcher.select_by(1, hits.len());
+    launcher.typed("d");
+    assert_eq!(launcher.chosen(&hits), Some(&hits[0]));
+    assert_eq!(launcher.chosen(&[]), None);
+}
@@ -0,0 +1,157 @@
+use base64::{engine::general_purpose, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::{Digest, Sha256};
+
+// sealed files start with this, anything else is a plaintext token from
+// before tokens were encrypted
+const MAGIC: &[u8] = b"kyomi-sealed-1\n";
+const NONCE_LEN: usize = 12;
+// config values sealed the same way, as base64 after this
+const SEALED_VALUE: &str = "sealed:";
+
+#[cfg(target_os = "linux")]
+fn machine_id() -> Result<String, anyhow::Error> {
+    std::fs::read_to_string("/etc/machine-id")
+        .or_else(|_| std::fs::read_to_string("/var/lib/dbus/machine-id"))
+        .map_err(|e| anyhow::anyhow!("no machine id: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn machine_id() -> Result<String, anyhow::Error> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))
+        .and_then(|line| line.rsplit('"').nth(1))
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("no IOPlatformUUID"))
+}
+
+#[cfg(target_os = "windows")]
+fn machine_id() -> Result<String, anyhow::Error> {
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("MachineGuid"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("no MachineGuid"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn machine_id() -> Result<String, anyhow::Error> {
+    Err(anyhow::anyhow!("no machine id on this platform"))
+}
+
+// anyone on this machine can derive it again, there's no keyring to keep it
+// in. what it stops is the token being read off a copied disk or a backup
+fn machine_key() -> Result<Key, anyhow::Error> {
+    let hash = Sha256::new()
+        .chain_update(b"kyomi token key")
+        .chain_update(machine_id()?.trim())
+        .finalize();
+    Ok(hash)
+}
+
+fn seal(key: &Key, plaintext: &str) -> Vec<u8> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(key)
+        .encrypt(&nonce, plaintext.as_bytes())
+        .expect("encrypting to memory can't fail");
+    [MAGIC, nonce.as_slice(), &ciphertext].concat()
+}
+
+// None for a file that was never sealed
+fn open(key: &Key, contents: &[u8]) -> Result<Option<String>, anyhow::Error> {
+    let sealed = match contents.strip_prefix(MAGIC) {
+        Some(sealed) if sealed.len() >= NONCE_LEN => sealed,
+        Some(_) => return Err(anyhow::anyhow!("sealed token is truncated")),
+        None => return Ok(None),
+    };
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("token was sealed on another machine"))?;
+    Ok(Some(String::from_utf8(plaintext)?))
+}
+
+// what goes in the token file
+pub fn seal_token(token: &str) -> Result<Vec<u8>, anyhow::Error> {
+    Ok(seal(&machine_key()?, token))
+}
+
+// the token from the token file, and whether it was still plaintext and
+// should be written back sealed
+pub fn open_token(contents: &[u8]) -> Result<(String, bool), anyhow::Error> {
+    if !contents.starts_with(MAGIC) {
+        return Ok((String::from_utf8(contents.to_vec())?, true));
+    }
+    let token = open(&machine_key()?, contents)?.unwrap_or_default();
+    Ok((token, false))
+}
+
+fn seal_value_with(key: &Key, value: &str) -> String {
+    let sealed = general_purpose::STANDARD.encode(seal(key, value));
+    format!("{}{}", SEALED_VALUE, sealed)
+}
+
+fn open_value_with(key: &Key, value: &str) -> Result<String, anyhow::Error> {
+    let Some(sealed) = value.strip_prefix(SEALED_VALUE) else {
+        return Ok(value.to_owned());
+    };
+    let contents = general_purpose::STANDARD.decode(sealed)?;
+    open(key, &contents)?.ok_or_else(|| anyhow::anyhow!("sealed value is missing its header"))
+}
+
+// a secret for the config file, like the client secret
+pub fn seal_value(value: &str) -> Result<String, anyhow::Error> {
+    Ok(seal_value_with(&machine_key()?, value))
+}
+
+// a config value as it's meant to be used, whether it was sealed or typed in
+// by hand
+pub fn open_value(value: &str) -> Result<String, anyhow::Error> {
+    if !value.starts_with(SEALED_VALUE) {
+        return Ok(value.to_owned());
+    }
+    open_value_with(&machine_key()?, value)
+}
+
+#[test]
+fn test_sealed_tokens_round_trip() {
+    let key = Key::from([7; 32]);
+    let sealed = seal(&key, "access-token");
+    assert!(!sealed
+        .windows(b"access-token".len())
+        .any(|w| w == b"access-token"));
+    assert_eq!(
+        open(&key, &sealed).unwrap().as_deref(),
+        Some("access-token")
+    );
+
+    assert!(open(&Key::from([8; 32]), &sealed).is_err());
+    assert_eq!(open(&key, b"plain-token").unwrap(), None);
+}
+
+#[test]
+fn test_sealed_config_values_round_trip() {
+    let key = Key::from([7; 32]);
+    let sealed = seal_value_with(&key, "client-secret");
+    assert!(sealed.starts_with(SEALED_VALUE));
+    assert!(!sealed.contains("client-secret"));
+    assert_eq!(open_value_with(&key, &sealed).unwrap(), "client-secret");
+
+    assert!(open_value_with(&Key::from([8; 32]), &sealed).is_err());
+    // typed in by hand
+    assert_eq!(open_value_with(&key, "plain").unwrap(), "plain");
+}
@@ -0,0 +1,213 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::config::Config;
+use crate::http;
+use crate::spotify::{self, Spotify};
+
+const DASHBOARD_URL: &str = "https://developer.spotify.com/dashboard";
+// spotify only allows plain http for loopback addresses, and no longer for
+// "localhost"
+const DEFAULT_REDIRECT_URI: &str = "http://127.0.0.1:8000";
+// spotify shows its own error page for a redirect uri the app doesn't know,
+// so a bad one looks like nothing happening
+const DRY_RUN_TIMEOUT: Duration = Duration::from_secs(120);
+
+// client ids and secrets are 32 lowercase hex digits
+fn is_valid_credential(value: &str) -> bool {
+    value.len() == 32
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn prompt(question: &str, default: Option<&str>) -> Result<String, anyhow::Error> {
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_owned(),
+        _ => answer.to_owned(),
+    })
+}
+
+// an empty answer is allowed when `optional`
+fn prompt_credential(question: &str, optional: bool) -> Result<String, anyhow::Error> {
+    loop {
+        let answer = prompt(question, None)?;
+        if is_valid_credential(&answer) || (optional && answer.is_empty()) {
+            return Ok(answer);
+        }
+        println!("that doesn't look right, it should be 32 letters and digits (0-9, a-f)");
+    }
+}
+
+// waits for spotify to send the browser back with a code, or an error if
+// permission was refused
+pub async fn wait_for_redirect(address: &str) -> Result<String, anyhow::Error> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    let (mut socket, _) = listener.accept().await?;
+    let mut buffer = [0; 2048];
+    let n = socket.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..n]).to_string();
+    let query = request
+        .split_whitespace()
+        .nth(1)
+        .and_then(|target| target.split_once('?'))
+        .map(|(_, query)| query)
+        .unwrap_or_default();
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            .map(String::from)
+    };
+
+    let page = match (param("code"), param("error")) {
+        (Some(code), _) => Ok(code),
+        (None, Some(error)) => Err(anyhow::anyhow!("spotify answered {}", error)),
+        (None, None) => Err(anyhow::anyhow!("the redirect didn't carry a code")),
+    };
+    let body = match &page {
+        Ok(_) => "kyomi is logged in, you can close this tab",
+        Err(_) => "kyomi couldn't log in, see the terminal",
+    };
+    let _ = socket
+        .write_all(format!("HTTP/1.1 200 OK\r\n\r\n{}\n", body).as_bytes())
+        .await;
+    page
+}
+
+// adds the credentials to config.toml, leaving everything else in it alone.
+// an empty client secret means PKCE, and any old secret is taken out
+// edited in place, so the rest of the file keeps its comments and layout.
+// the secret goes in already sealed, see secrets::seal_value
+fn write_credentials(
+    path: &Path,
+    client_id: &str,
+    sealed_secret: Option<&str>,
+    redirect_uri: &str,
+) -> Result<(), anyhow::Error> {
+    let mut doc: toml_edit::DocumentMut = match std::fs::read_to_string(path) {
+        Ok(raw) => raw.parse()?,
+        Err(_) => toml_edit::DocumentMut::new(),
+    };
+    doc["spotify_client_id"] = toml_edit::value(client_id);
+    match sealed_secret {
+        Some(secret) => doc["spotify_client_secret"] = toml_edit::value(secret),
+        None => {
+            doc.remove("spotify_client_secret");
+        }
+    }
+    doc["spotify_redirect_uri"] = toml_edit::value(redirect_uri);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, doc.to_string())?;
+    Ok(())
+}
+
+async fn setup_spotify() -> Result<(), anyhow::Error> {
+    println!("kyomi needs a spotify app of your own to read what's playing.");
+    println!("1. log in to the developer dashboard (opening it now) and create an app");
+    println!("2. add this redirect uri to it: {}", DEFAULT_REDIRECT_URI);
+    println!("3. tick \"Web API\", save, then open the app's settings");
+    if webbrowser::open(DASHBOARD_URL).is_err() {
+        eprintln!("couldn't open a browser, go to {}", DASHBOARD_URL);
+    }
+    println!();
+
+    let client_id = prompt_credential("client id", false)?;
+    let client_secret = prompt_credential("client secret (leave empty to use PKCE)", true)?;
+    let redirect_uri = prompt("redirect uri", Some(DEFAULT_REDIRECT_URI))?;
+    let address = spotify::loopback_address(&redirect_uri).ok_or_else(|| {
+        anyhow::anyhow!("the redirect uri has to look like http://127.0.0.1:<port>")
+    })?;
+
+    http::configure(&Config::load());
+    println!("checking it works, approve kyomi in the browser");
+    let mut spotify = Spotify::from_client_id(&client_id)
+        .with_client_secret(&client_secret)
+        .with_redirect_uri(&redirect_uri)
+        .with_scope(spotify::SCOPES)
+        .build();
+    webbrowser::open(&spotify.login_url())?;
+    let code = tokio::time::timeout(DRY_RUN_TIMEOUT, wait_for_redirect(&address))
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "spotify never sent the browser back, check {} is listed exactly as a redirect uri",
+                redirect_uri
+            )
+        })??;
+    spotify
+        .check_auth_code(&code)
+        .await
+        .map_err(|e| anyhow::anyhow!("spotify didn't accept the credentials: {}", e))?;
+
+    // never kept in plain text. without a machine key to seal it with, PKCE
+    // does without it
+    let sealed_secret = match client_secret.as_str() {
+        "" => None,
+        secret => Some(crate::secrets::seal_value(secret).map_err(|e| {
+            anyhow::anyhow!(
+                "couldn't seal the client secret ({}), run setup again and leave it empty",
+                e
+            )
+        })?),
+    };
+    let path = Config::path();
+    write_credentials(&path, &client_id, sealed_secret.as_deref(), &redirect_uri)?;
+    println!("all good, saved to {}", path.display());
+    Ok(())
+}
+
+// `kyomi setup spotify`
+pub async fn setup_command(args: &[String]) {
+    let res = match args.first().map(String::as_str) {
+        Some("spotify") => setup_spotify().await,
+        _ => {
+            println!("usage: kyomi setup spotify");
+            return;
+        }
+    };
+    if let Err(e) = res {
+        eprintln!("setup failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[test]
+fn test_credentials_are_added_to_config() {
+    assert!(is_valid_credential("0123456789abcdef0123456789abcdef"));
+    assert!(!is_valid_credential("0123456789ABCDEF0123456789ABCDEF"));
+    assert!(!is_valid_credential("too short"));
+
+    let dir = std::env::temp_dir().join(format!("kyomi-setup-{}", std::process::id()));
+    let path = dir.join("config.toml");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "# roomier\npadding = 4.0 # px\n").unwrap();
+    write_credentials(&path, "id", Some("sealed:abc"), DEFAULT_REDIRECT_URI).unwrap();
+
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(raw.starts_with("# roomier\npadding = 4.0 # px\n"));
+    let config: Config = toml::from_str(&raw).unwrap();
+    assert_eq!(config.padding, 4.0);
+    assert_eq!(config.spotify_client_id.as_deref(), Some("id"));
+    assert_eq!(config.spotify_client_secret.as_deref(), Some("sealed:abc"));
+    assert_eq!(
+        config.spotify_redirect_uri.as_deref(),
+        Some(DEFAULT_REDIRECT_URI)
+    );
+
+    // switching to PKCE takes the old secret out
+    write_credentials(&path, "id", None, DEFAULT_REDIRECT_URI).unwrap();
+    let config: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(config.spotify_client_secret, None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
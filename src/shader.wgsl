@@ -1,8 +1,55 @@
-struct TimerUniform {
-  t: f32
+// shared by the whole frame, mirrors Globals in main.rs
+struct Globals {
+  // animation time, seconds
+  time: f32,
+  // fraction of the track played, negative while unknown
+  progress: f32,
+  // physical pixels
+  resolution: vec2<f32>,
+  accent: vec4<f32>,
+  corner_radius: f32,
+  opacity: f32,
+  // one of the ACCENT_ consts
+  accent_effect: u32,
+  // seconds per breath or trip through the palette
+  accent_period: f32,
+  palette: array<vec4<f32>, 4>,
+  palette_len: u32,
+  // the track's, zero while unknown
+  tempo: f32,
+  // 0-1
+  energy: f32,
+  // seconds into the track
+  position: f32,
 };
 @group(0) @binding(0)
-var<uniform> timer: TimerUniform;
+var<uniform> globals: Globals;
+
+// per-element, mirrors QuadParams in quad.rs
+struct Quad {
+  // x, y, width, height as fractions of the widget, from the top left
+  rect: vec4<f32>,
+  color: vec4<f32>,
+  flags: u32,
+};
+@group(1) @binding(0)
+var<uniform> quad: Quad;
+
+const QUAD_GRADIENT: u32 = 1u;
+const QUAD_PROGRESS: u32 = 2u;
+const QUAD_ACCENT: u32 = 4u;
+const QUAD_TEXTURE: u32 = 8u;
+
+// the album cover, see cover.rs
+@group(2) @binding(0)
+var cover: texture_2d<f32>;
+@group(2) @binding(1)
+var cover_sampler: sampler;
+
+const ACCENT_BREATHE: u32 = 1u;
+const ACCENT_CYCLE: u32 = 2u;
+const ACCENT_PULSE: u32 = 3u;
+const TAU: f32 = 6.2831853;
 
 
 struct VertexInput {
@@ -13,6 +60,10 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    // position in the whole widget
+    @location(1) uv: vec2<f32>,
+    // position within this quad
+    @location(2) local: vec2<f32>,
 };
 
 @vertex
@@ -20,15 +71,92 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color.x = model.color.x + cos(model.position.x) + sin(timer.t);
-    out.color.y = model.color.y + sin(model.position.x) + cos(timer.t);
-    out.color.z = model.color.z + cos(model.position.x) + cos(timer.t);
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.color.x = model.color.x + cos(model.position.x) + sin(globals.time);
+    out.color.y = model.color.y + sin(model.position.x) + cos(globals.time);
+    out.color.z = model.color.z + cos(model.position.x) + cos(globals.time);
+    // the unit quad stretched over this element's rect. 0,0 at the bottom left
+    out.local = model.position.xy * 0.5 + 0.5;
+    out.uv = vec2<f32>(
+        quad.rect.x + out.local.x * quad.rect.z,
+        1.0 - quad.rect.y - (1.0 - out.local.y) * quad.rect.w,
+    );
+    out.clip_position = vec4<f32>(out.uv * 2.0 - 1.0, model.position.z, 1.0);
     return out;
 }
 
+const INDETERMINATE_WIDTH: f32 = 0.3;
+
+// 1.0 where the progress bar is filled in
+fn progress_fill(x: f32) -> f32 {
+    if (globals.progress >= 0.0) {
+        return step(x, globals.progress);
+    }
+    // unknown progress: a segment sweeping across, wrapping at the edges
+    let head = fract(globals.time * 0.5) * (1.0 + INDETERMINATE_WIDTH);
+    return step(head - INDETERMINATE_WIDTH, x) * step(x, head);
+}
+
+// 1.0 inside the widget's rounded outline, antialiased over a pixel
+fn corner_mask(uv: vec2<f32>) -> f32 {
+    let half_size = globals.resolution * 0.5;
+    let radius = min(globals.corner_radius, min(half_size.x, half_size.y));
+    let p = abs(uv * globals.resolution - half_size) - (half_size - radius);
+    let dist = length(max(p, vec2<f32>(0.0))) - radius;
+    return clamp(0.5 - dist, 0.0, 1.0);
+}
+
+// turns a color around the gray axis, keeping its brightness
+fn rotate_hue(color: vec3<f32>, angle: f32) -> vec3<f32> {
+    let axis = vec3<f32>(0.57735);
+    let c = cos(angle);
+    return color * c + cross(axis, color) * sin(angle) + axis * dot(axis, color) * (1.0 - c);
+}
+
+// the accent as it is right now, see theme::AccentEffect
+fn animated_accent() -> vec3<f32> {
+    let phase = fract(globals.time / globals.accent_period);
+    if (globals.accent_effect == ACCENT_PULSE && globals.tempo > 0.0) {
+        // bright on each beat, dying away before the next. counted from the
+        // track's start so it stays on the music across pauses and seeks
+        let beat = fract(globals.position * globals.tempo / 60.0);
+        let depth = 0.2 + 0.3 * clamp(globals.energy, 0.0, 1.0);
+        return globals.accent.rgb * (1.0 - depth + depth * exp(-beat * 5.0));
+    }
+    if (globals.accent_effect == ACCENT_BREATHE || globals.accent_effect == ACCENT_PULSE) {
+        // between 60% and full brightness, easing at both ends
+        return globals.accent.rgb * (0.8 + 0.2 * cos(phase * TAU));
+    }
+    if (globals.accent_effect == ACCENT_CYCLE) {
+        if (globals.palette_len == 0u) {
+            return clamp(rotate_hue(globals.accent.rgb, phase * TAU), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        let position = phase * f32(globals.palette_len);
+        let current = u32(position) % globals.palette_len;
+        let next = (current + 1u) % globals.palette_len;
+        return mix(globals.palette[current].rgb, globals.palette[next].rgb, smoothstep(0.0, 1.0, fract(position)));
+    }
+    return globals.accent.rgb;
+}
+
 // Fragment shader
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    let alpha = globals.opacity * corner_mask(in.uv);
+    if ((quad.flags & QUAD_GRADIENT) != 0u) {
+        return vec4<f32>(in.color, alpha);
+    }
+    if ((quad.flags & QUAD_TEXTURE) != 0u) {
+        // local goes bottom to top, the cover's rows top to bottom
+        let texel = textureSampleLevel(cover, cover_sampler, vec2<f32>(in.local.x, 1.0 - in.local.y), 0.0);
+        return vec4<f32>(texel.rgb, texel.a * alpha);
+    }
+    var color = quad.color.rgb;
+    if ((quad.flags & QUAD_ACCENT) != 0u) {
+        color = animated_accent();
+    }
+    if ((quad.flags & QUAD_PROGRESS) != 0u) {
+        let track = color * 0.3;
+        return vec4<f32>(mix(track, color, progress_fill(in.local.x)), alpha);
+    }
+    return vec4<f32>(color, quad.color.a * alpha);
 }
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time::Instant;
+
+use crate::player::{Backend, PlayerCommand};
+
+// how often the volume steps down while fading
+const FADE_STEP: Duration = Duration::from_secs(2);
+
+// the longest a sleep timer or its fade can be set for
+pub const MAX_SLEEP: Duration = Duration::from_secs(24 * 60 * 60);
+
+// how the volume falls over the fade, from where it was to nothing
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    // evenly
+    Linear,
+    // gently at first, then faster towards the end
+    EaseIn,
+    // quickly at first, then tailing off
+    EaseOut,
+}
+
+impl FadeCurve {
+    // how much of the volume is left, `through` the fade from 0 to 1
+    fn level(self, through: f32) -> f32 {
+        let through = through.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => 1.0 - through,
+            FadeCurve::EaseIn => 1.0 - through * through,
+            FadeCurve::EaseOut => (1.0 - through) * (1.0 - through),
+        }
+    }
+}
+
+// the volume with `left` to go before the timer runs out. untouched until
+// the fade starts
+fn faded_volume(start: u8, curve: FadeCurve, fade: Duration, left: Duration) -> u8 {
+    if left >= fade {
+        return start;
+    }
+    let through = 1.0 - left.as_secs_f32() / fade.as_secs_f32();
+    (start as f32 * curve.level(through)).round() as u8
+}
+
+// pauses playback once the time sent on the returned channel is up, or
+// forgets about it when None is sent. over the last `fade` the volume is
+// stepped down along the curve, and it's put back once paused, so playing
+// again the next day isn't silent
+pub fn spawn_sleep_timer(
+    backend: Backend,
+    controls: UnboundedSender<PlayerCommand>,
+    fade: Duration,
+    curve: FadeCurve,
+) -> UnboundedSender<Option<Duration>> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Option<Duration>>();
+    tokio::spawn(async move {
+        let mut ends: Option<Instant> = None;
+        // the volume before the fade, once it's begun
+        let mut start: Option<u8> = None;
+        // the volume couldn't be read, so it just pauses at the end
+        let mut unfadable = false;
+        loop {
+            let wake = ends.map(|ends| {
+                if unfadable {
+                    ends
+                } else if start.is_some() {
+                    (Instant::now() + FADE_STEP).min(ends)
+                } else {
+                    ends.checked_sub(fade).unwrap_or(ends)
+                }
+            });
+            tokio::select! {
+                set = rx.recv() => {
+                    let Some(set) = set else {
+                        break;
+                    };
+                    if let Some(volume) = start.take() {
+                        let _ = controls.send(PlayerCommand::SetVolume(volume));
+                    }
+                    ends = set.map(|after| Instant::now() + after);
+                    unfadable = false;
+                }
+                _ = tokio::time::sleep_until(wake.unwrap_or_else(Instant::now)), if wake.is_some() => {
+                    let Some(at) = ends else {
+                        continue;
+                    };
+                    let now = Instant::now();
+                    if now >= at {
+                        eprintln!("sleep timer's up, pausing");
+                        let _ = controls.send(PlayerCommand::Pause);
+                        if let Some(volume) = start.take() {
+                            let _ = controls.send(PlayerCommand::SetVolume(volume));
+                        }
+                        ends = None;
+                        continue;
+                    }
+                    if unfadable {
+                        continue;
+                    }
+                    let volume = match start {
+                        Some(volume) => volume,
+                        None => match backend.volume().await {
+                            Ok(volume) => *start.insert(volume),
+                            Err(e) => {
+                                eprintln!("can't fade, pausing at the end instead: {:?}", e);
+                                unfadable = true;
+                                continue;
+                            }
+                        },
+                    };
+                    let volume = faded_volume(volume, curve, fade, at - now);
+                    let _ = controls.send(PlayerCommand::SetVolume(volume));
+                }
+            }
+        }
+    });
+    tx
+}
+
+#[test]
+fn test_fade_curves() {
+    let fade = Duration::from_secs(60);
+    let left = Duration::from_secs;
+    for curve in [FadeCurve::Linear, FadeCurve::EaseIn, FadeCurve::EaseOut] {
+        assert_eq!(faded_volume(80, curve, fade, left(120)), 80);
+        assert_eq!(faded_volume(80, curve, fade, left(60)), 80);
+        assert_eq!(faded_volume(80, curve, fade, left(0)), 0);
+    }
+    assert_eq!(faded_volume(80, FadeCurve::Linear, fade, left(30)), 40);
+    assert_eq!(faded_volume(80, FadeCurve::EaseIn, fade, left(30)), 60);
+    assert_eq!(faded_volume(80, FadeCurve::EaseOut, fade, left(30)), 20);
+    // no fade at all just pauses
+    assert_eq!(
+        faded_volume(80, FadeCurve::Linear, Duration::ZERO, left(1)),
+        80
+    );
+}
@@ -1,16 +1,300 @@
 use base64::{engine::general_purpose, Engine};
-use reqwest::{Client, Response};
-use serde::Deserialize;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use chrono::Datelike;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+use crate::http::{Endpoint, SendRetrying};
+
+// everything the widget does with the web api
+pub const SCOPES: &str = "user-read-private user-read-playback-state user-read-currently-playing user-modify-playback-state user-library-read user-library-modify playlist-read-private playlist-modify-private playlist-modify-public";
+
 #[derive(Deserialize)]
 struct TokenResponse {
     access_token: String,
     token_type: String,
     expires_in: i32,
-    refresh_token: String,
+    // only sent again on refresh if spotify rotated it
+    refresh_token: Option<String>,
+    // the scopes actually granted, which a refresh can leave out
+    #[serde(default)]
     scope: String,
 }
 
+// what went wrong talking to spotify, so callers can tell a login that needs
+// redoing from a busy api or an idle player rather than just logging it
+#[derive(Debug)]
+pub enum SpotifyError {
+    // no token, or spotify turned it down. logging in again fixes it
+    AuthError(String),
+    // 429. how long spotify asked to wait, when it said
+    RateLimited { retry_after: Option<Duration> },
+    // no active device to play on or ask about
+    NotPlaying,
+    // spotify answered with something that isn't what we expected
+    Deserialization(String),
+    // couldn't reach spotify, or it answered with some other error
+    Network(reqwest::Error),
+}
+
+impl std::fmt::Display for SpotifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpotifyError::AuthError(reason) => write!(f, "not logged in to spotify: {}", reason),
+            SpotifyError::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "spotify is rate limiting, retry in {}s",
+                retry_after.as_secs()
+            ),
+            SpotifyError::RateLimited { retry_after: None } => {
+                write!(f, "spotify is rate limiting")
+            }
+            SpotifyError::NotPlaying => write!(f, "no active spotify device"),
+            SpotifyError::Deserialization(e) => write!(f, "unexpected answer from spotify: {}", e),
+            SpotifyError::Network(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SpotifyError {}
+
+impl From<reqwest::Error> for SpotifyError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            SpotifyError::Deserialization(e.to_string())
+        } else {
+            SpotifyError::Network(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for SpotifyError {
+    fn from(e: serde_json::Error) -> Self {
+        SpotifyError::Deserialization(e.to_string())
+    }
+}
+
+// error_for_status, telling apart the statuses callers handle differently
+trait CheckStatus {
+    fn check_status(self) -> Result<Response, SpotifyError>;
+}
+
+impl CheckStatus for Response {
+    fn check_status(self) -> Result<Response, SpotifyError> {
+        let accounts = self.url().host_str() == Some("accounts.spotify.com");
+        match self.status() {
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Err(SpotifyError::RateLimited {
+                retry_after: retry_after(&self),
+            }),
+            // the token endpoint turns down bad refresh tokens and codes with
+            // a 400
+            status
+                if status == reqwest::StatusCode::UNAUTHORIZED
+                    || accounts && status.is_client_error() =>
+            {
+                Err(SpotifyError::AuthError(format!(
+                    "spotify answered {}",
+                    status
+                )))
+            }
+            // the player endpoints' way of saying no device is active
+            reqwest::StatusCode::NOT_FOUND if self.url().path().starts_with("/v1/me/player") => {
+                Err(SpotifyError::NotPlaying)
+            }
+            _ => Ok(self.error_for_status()?),
+        }
+    }
+}
+
+// a 404 or 403, for something deleted or private rather than a call that
+// might work next time
+fn unavailable(e: &SpotifyError) -> bool {
+    let SpotifyError::Network(e) = e else {
+        return false;
+    };
+    matches!(
+        e.status(),
+        Some(reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN)
+    )
+}
+
+// spotify's Retry-After, in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|secs| secs.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+// a 429 holds off every request until spotify's Retry-After, or a jittered
+// backoff doubling with each 429 in a row when it doesn't say. waits up to
+// RATE_LIMIT_MAX_WAIT are sat out before the client is locked, see
+// wait_out_rate_limit, and anything sent meanwhile comes back RateLimited
+// without going out
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(10);
+// the wait when spotify doesn't say, doubling up to this many times
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(1);
+const RATE_LIMIT_MAX_DOUBLINGS: u32 = 6;
+
+static RATE_LIMITED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+// 429s in a row, for the backoff
+static RATE_LIMIT_STREAK: AtomicU32 = AtomicU32::new(0);
+
+// what spotify asked for, or the backoff, plus up to a quarter more at
+// random (`jitter` from 0 to 1) so requests held up together don't all come
+// back at once
+fn rate_limit_wait(retry_after: Option<Duration>, attempt: u32, jitter: f64) -> Duration {
+    let doublings = attempt.min(RATE_LIMIT_MAX_DOUBLINGS);
+    let wait = retry_after.unwrap_or(RATE_LIMIT_BACKOFF * 2u32.pow(doublings));
+    wait + wait.mul_f64(jitter.clamp(0.0, 1.0) / 4.0)
+}
+
+fn jitter() -> f64 {
+    OsRng.next_u32() as f64 / u32::MAX as f64
+}
+
+// how much longer requests are held off, if they are
+fn held_off(until: Option<Instant>, now: Instant) -> Option<Duration> {
+    until.filter(|until| *until > now).map(|until| until - now)
+}
+
+fn rate_limited_for() -> Option<Duration> {
+    held_off(*RATE_LIMITED_UNTIL.lock().unwrap(), Instant::now())
+}
+
+// sits out a short rate limit before a call, holding nothing so other tasks
+// carry on. a long one is handed back for the caller to give up on
+pub async fn wait_out_rate_limit() -> Result<(), SpotifyError> {
+    match rate_limited_for() {
+        Some(wait) if wait > RATE_LIMIT_MAX_WAIT => Err(SpotifyError::RateLimited {
+            retry_after: Some(wait),
+        }),
+        Some(wait) => {
+            tokio::time::sleep(wait).await;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+// send_retrying, which already covers dropped connections and 5xx answers,
+// held back while spotify is rate limiting. a 429 sets how long for and is
+// handed back for check_status to turn into RateLimited, rather than waited
+// out here while the caller holds the client
+async fn send(
+    request: reqwest::RequestBuilder,
+    endpoint: Endpoint,
+) -> Result<Response, SpotifyError> {
+    if let Some(wait) = rate_limited_for() {
+        return Err(SpotifyError::RateLimited {
+            retry_after: Some(wait),
+        });
+    }
+    let response = request.send_retrying(endpoint).await?;
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        RATE_LIMIT_STREAK.store(0, Ordering::Relaxed);
+        return Ok(response);
+    }
+    let attempt = RATE_LIMIT_STREAK.fetch_add(1, Ordering::Relaxed);
+    let wait = rate_limit_wait(retry_after(&response), attempt, jitter());
+    eprintln!(
+        "rate limited by spotify, holding off for {:.1}s",
+        wait.as_secs_f32()
+    );
+    let until = Instant::now() + wait;
+    let mut limited = RATE_LIMITED_UNTIL.lock().unwrap();
+    *limited = Some(limited.map_or(until, |earlier| earlier.max(until)));
+    Ok(response)
+}
+
+// used in place of send_retrying for everything sent to spotify
+trait SendSpotify {
+    fn send_spotify(
+        self,
+        endpoint: Endpoint,
+    ) -> impl std::future::Future<Output = Result<Response, SpotifyError>> + Send;
+}
+
+impl SendSpotify for reqwest::RequestBuilder {
+    fn send_spotify(
+        self,
+        endpoint: Endpoint,
+    ) -> impl std::future::Future<Output = Result<Response, SpotifyError>> + Send {
+        send(self, endpoint)
+    }
+}
+
+// an access token from outside, used as is. nothing refreshes it
+const EXTERNAL_TOKEN_ENV: &str = "KYOMI_SPOTIFY_TOKEN";
+
+// tokens this close to expiring are refreshed before the next call, unless
+// the config says otherwise
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+// what's kept in the token file between runs
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct SavedToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    // unix seconds
+    expires_at: Option<u64>,
+    // what was granted, space separated. None for files from before it was
+    // kept, which can't be trusted to cover what's asked for now
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+impl SavedToken {
+    // token files from before refresh tokens were kept hold just the access
+    // token
+    fn parse(contents: String) -> Self {
+        serde_json::from_str(&contents).unwrap_or(SavedToken {
+            access_token: contents,
+            refresh_token: None,
+            expires_at: None,
+            scope: None,
+        })
+    }
+
+    // whether the token was granted every scope in `wanted`
+    fn covers(&self, wanted: &str) -> bool {
+        let Some(granted) = &self.scope else {
+            return false;
+        };
+        let granted: Vec<&str> = granted.split_whitespace().collect();
+        wanted
+            .split_whitespace()
+            .all(|scope| granted.contains(&scope))
+    }
+}
+
+// the verifier is 43 to 128 characters, this many random bytes make 86
+const CODE_VERIFIER_BYTES: usize = 64;
+
+// a fresh PKCE code verifier, random and url safe
+fn code_verifier() -> String {
+    let mut bytes = [0; CODE_VERIFIER_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// what the auth url carries in place of the verifier, which is only sent
+// with the code
+fn code_challenge(verifier: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
 #[derive(Debug, Default)]
 enum ResponseType {
     #[default]
@@ -21,6 +305,7 @@ enum ResponseType {
 #[derive(Debug, Default)]
 pub struct Spotify {
     client_id: String, // 	Required	The Client ID generated after registering your application.
+    client_secret: String,
     response_type: ResponseType, //Required	Set to code.
     redirect_uri: String, // Required	The URI to redirect to after the user grants or denies permission.
     // This URI needs to have been entered in the Redirect URI allowlist that you specified when you registered your application (See the app guide).
@@ -30,29 +315,102 @@ pub struct Spotify {
     //	that is, only information normally visible in the Spotify desktop, web, and mobile players.
     pub show_dialog: bool, // Optional	Whether or not to force the user to approve the app again if they’ve already done so. If false (default), a user who has already approved the application may be automatically redirected to the URI specified by redirect_uri. If true, the user will not be automatically redirected and will have to approve the app again.
 
+    // set by auth_url_pkce, for trading the code without a client secret
+    code_verifier: Option<String>,
+    // a token file something else keeps fresh, and when it last changed
+    external_token: Option<PathBuf>,
+    external_modified: Option<SystemTime>,
     token: Option<String>,
+    refresh_token: Option<String>,
+    // the scopes the token was granted, saved with it
+    granted_scope: Option<String>,
+    // wall clock rather than Instant, so time spent asleep counts
+    token_expires_at: Option<SystemTime>,
+    refresh_margin: Duration,
+    // playlist, album and artist names by context uri. None for ones that
+    // are gone or private, so they aren't asked for on every poll
+    context_names: HashMap<String, Option<String>>,
+    // full albums by id. what get_album returns doesn't change
+    albums: HashMap<String, AlbumObject>,
+    // the last few covers fetched
+    album_art: Arc<Mutex<ArtCache>>,
+    // sent as Accept-Language, for names in that language where spotify has
+    // them
+    language: Option<reqwest::header::HeaderValue>,
+    // kept for every call, so connections and tls sessions are reused. built
+    // once, by build or else on first use
+    clients: OnceLock<Clients>,
 }
 
+// the token endpoint and the web api each get their own, built with their
+// Endpoint's timeouts and the network settings from the config
+#[derive(Debug)]
+struct Clients {
+    auth: reqwest::Client,
+    api: reqwest::Client,
+    art: reqwest::Client,
+}
+
+impl Clients {
+    fn new() -> Self {
+        Clients {
+            auth: client(Endpoint::Auth),
+            api: client(Endpoint::Polling),
+            art: client(Endpoint::Art),
+        }
+    }
+}
+
+fn client(endpoint: Endpoint) -> reqwest::Client {
+    crate::http::builder(endpoint)
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap_or_else(|e| {
+            eprintln!("couldn't set up the http client: {}", e);
+            reqwest::Client::new()
+        })
+}
+
+const USER_AGENT: &str = concat!("kyomi/", env!("CARGO_PKG_VERSION"));
+
 impl Spotify {
     fn new() -> Self {
         Spotify {
             client_id: String::from(""),
+            client_secret: String::from(""),
             response_type: ResponseType::Code,
             redirect_uri: String::from(""),
             state: None,
             scope: None,
             show_dialog: false,
+            code_verifier: None,
+            external_token: None,
+            external_modified: None,
             token: None,
+            refresh_token: None,
+            granted_scope: None,
+            token_expires_at: None,
+            refresh_margin: TOKEN_REFRESH_MARGIN,
+            context_names: HashMap::new(),
+            albums: HashMap::new(),
+            album_art: Arc::default(),
+            language: None,
+            clients: OnceLock::new(),
         }
     }
 
     pub fn from_client_id(client_id: &str) -> Self {
         Spotify {
             client_id: String::from(client_id),
-            ..Default::default()
+            ..Spotify::new()
         }
     }
 
+    pub fn with_client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = String::from(client_secret);
+        self
+    }
+
     pub fn with_state(mut self, state: &str) -> Self {
         self.state = Some(String::from(state));
         self
@@ -68,6 +426,53 @@ impl Spotify {
         self
     }
 
+    // for setups where librespot, spotifyd or a script already keep a web
+    // api token fresh: the file holds either the bare access token or json
+    // with access_token and optionally expires_at (unix seconds). it's read
+    // again whenever it changes, and kyomi never refreshes it itself
+    pub fn with_external_token(mut self, path: Option<&Path>) -> Self {
+        self.external_token = path.map(Path::to_path_buf);
+        self
+    }
+
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    // the end of the with_ chain, once the config's network settings are in
+    pub fn build(self) -> Self {
+        self.clients.get_or_init(Clients::new);
+        self
+    }
+
+    fn client(&self, endpoint: Endpoint) -> &reqwest::Client {
+        let clients = self.clients.get_or_init(Clients::new);
+        match endpoint {
+            Endpoint::Auth => &clients.auth,
+            Endpoint::Polling => &clients.api,
+            Endpoint::Art => &clients.art,
+        }
+    }
+
+    // checked here, so a bad one is only mentioned the once
+    pub fn with_language(mut self, language: Option<&str>) -> Self {
+        self.language = language.and_then(|language| match language.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                eprintln!("ignoring metadata_language {:?}", language);
+                None
+            }
+        });
+        self
+    }
+
+    fn insert_language(&self, headers: &mut reqwest::header::HeaderMap) {
+        if let Some(language) = &self.language {
+            headers.insert(reqwest::header::ACCEPT_LANGUAGE, language.clone());
+        }
+    }
+
     pub fn auth_url(&self) -> String {
         let base = "https://accounts.spotify.com/authorize".to_owned();
         let params = format!(
@@ -81,35 +486,207 @@ impl Spotify {
         base + params.as_str()
     }
 
-    async fn token_from_disk(&mut self) -> Result<String, anyhow::Error> {
-        let mut buf = String::new();
-        match tokio::fs::File::open("token").await {
-            Ok(mut f) => {
-                f.read_to_string(&mut buf).await.unwrap();
-                self.token = Some(buf.clone());
-                Ok(buf)
-            }
-            Err(_) => {
-                tokio::fs::File::create("token").await.unwrap();
-                anyhow::Result::Err(anyhow::anyhow!("no token saved"))
-            }
+    // the same with a PKCE challenge, for apps without a client secret. the
+    // code that comes back can only be traded by this Spotify
+    pub fn auth_url_pkce(&mut self) -> String {
+        let verifier = code_verifier();
+        let challenge = code_challenge(&verifier);
+        self.code_verifier = Some(verifier);
+        format!(
+            "{}&code_challenge_method=S256&code_challenge={}",
+            self.auth_url(),
+            challenge
+        )
+    }
+
+    // whichever of the two suits the app
+    pub fn login_url(&mut self) -> String {
+        if self.client_secret.is_empty() {
+            self.auth_url_pkce()
+        } else {
+            self.auth_url()
+        }
+    }
+
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    // without a client secret the app identifies itself in the body instead,
+    // and proves it started the auth with the verifier
+    fn public_client_params(&self) -> String {
+        if !self.client_secret.is_empty() {
+            return String::new();
+        }
+        let mut params = format!("&client_id={}", urlencoding::encode(&self.client_id));
+        if let Some(verifier) = &self.code_verifier {
+            params += &format!("&code_verifier={}", verifier);
         }
+        params
     }
 
-    pub async fn token(&mut self, auth_code: &str) -> Result<String, anyhow::Error> {
-        let disk_token = self.token_from_disk().await;
-        if disk_token.is_ok() && disk_token.as_ref().unwrap().len() > 0 {
-            self.token = Some(disk_token.as_ref().unwrap().clone());
-            return Ok(disk_token.unwrap());
+    async fn token_from_disk(&mut self) -> Result<(), SpotifyError> {
+        let no_token = || SpotifyError::AuthError(String::from("no token saved"));
+        let buf = tokio::fs::read("token").await.map_err(|_| no_token())?;
+        let (contents, plaintext) = crate::secrets::open_token(&buf)
+            .map_err(|e| SpotifyError::AuthError(format!("couldn't open the token: {}", e)))?;
+        let saved = SavedToken::parse(contents);
+        if saved.access_token.is_empty() {
+            return Err(no_token());
         }
+        // a scope added since the login needs the user to agree to it, which
+        // a refresh can't do
+        if !saved.covers(self.scope.as_deref().unwrap_or(SCOPES)) {
+            return Err(SpotifyError::AuthError(String::from(
+                "the saved token is missing scopes, log in again",
+            )));
+        }
+        self.token = Some(saved.access_token);
+        self.refresh_token = saved.refresh_token;
+        self.granted_scope = saved.scope;
+        self.token_expires_at = saved
+            .expires_at
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        // from before tokens were sealed
+        if plaintext {
+            self.save_token().await;
+        }
+        Ok(())
+    }
 
+    async fn save_token(&self) {
+        let Some(access_token) = self.token.clone() else {
+            return;
+        };
+        let saved = SavedToken {
+            access_token,
+            refresh_token: self.refresh_token.clone(),
+            expires_at: self
+                .token_expires_at
+                .map(|at| at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
+            scope: self.granted_scope.clone(),
+        };
+        let written = match serde_json::to_string(&saved) {
+            Ok(saved) => write_token_to_disk(saved).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            eprintln!("couldn't save the token: {}", e);
+        }
+    }
+
+    // picks up where the last run left off. false when there's no saved token
+    // that works or can be refreshed, and the browser auth is needed
+    pub async fn load_saved_token(&mut self) -> bool {
+        if let Ok(token) = std::env::var(EXTERNAL_TOKEN_ENV) {
+            self.token = Some(token);
+            return true;
+        }
+        if self.external_token.is_some() {
+            return match self.read_external_token() {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("couldn't read the external token: {}", e);
+                    false
+                }
+            };
+        }
+        if self.token_from_disk().await.is_err() {
+            return false;
+        }
+        if self.refresh_token.is_some() {
+            return self.ensure_valid_token().await.is_ok();
+        }
+        self.token_expires_at
+            .is_some_and(|at| at > SystemTime::now() + self.refresh_margin)
+    }
+
+    // whether there's no token, or it's run out with nothing to refresh it
+    // with, so only logging in again in the browser will do. tokens from
+    // outside are someone else's to renew
+    pub fn needs_login(&self) -> bool {
+        if self.external_token.is_some() || std::env::var(EXTERNAL_TOKEN_ENV).is_ok() {
+            return false;
+        }
+        let expired = self
+            .token_expires_at
+            .is_some_and(|at| at <= SystemTime::now());
+        self.token.is_none() || expired && self.refresh_token.is_none()
+    }
+
+    // what every call goes through first, see player::spotify_ready. refreshes
+    // ahead of expiry, so no call goes out with a token that runs out on the
+    // way, and fails with AuthError once the token is past saving
+    pub async fn ensure_valid_token(&mut self) -> Result<(), SpotifyError> {
+        if self.external_token.is_some() {
+            return self.read_external_token();
+        }
+        let expiring = self
+            .token_expires_at
+            .is_some_and(|at| at <= SystemTime::now() + self.refresh_margin);
+        if expiring && self.refresh_token.is_some() {
+            if let Err(e) = self.refresh_access_token().await {
+                // turned down, so it won't work next time either
+                if matches!(e, SpotifyError::AuthError(_)) {
+                    self.refresh_token = None;
+                }
+                return Err(e);
+            }
+        }
+        if self.needs_login() {
+            return Err(SpotifyError::AuthError(String::from(
+                "the token has run out, log in again",
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn token(&mut self, auth_code: &str) -> Result<String, SpotifyError> {
         let url = String::from("https://accounts.spotify.com/api/token");
         let redirect_uri = self.redirect_uri.clone();
-        let client = Client::new();
+        let client = self.client(Endpoint::Auth);
+        let headers = self.client_auth_headers();
+        let body = reqwest::Body::from(format!(
+            "grant_type=authorization_code&code={auth_code}&redirect_uri={redirect_uri}{}",
+            self.public_client_params()
+        ));
+
+        let spotify_server_res = client
+            .post(url)
+            .headers(headers)
+            .body(body)
+            .send_spotify(Endpoint::Auth)
+            .await;
+
+        let j: Result<TokenResponse, SpotifyError> = match spotify_server_res {
+            Ok(res) => match res.check_status() {
+                Ok(res) => res.json().await.map_err(SpotifyError::from),
+                Err(e) => Err(e),
+            },
+            Err(e) => {
+                eprintln!("Server Error: {:?}", e);
+                return Err(e);
+            }
+        };
 
-        // encode client_id and client_secret
+        match j {
+            Ok(data) => {
+                eprintln!("got token for: {:?}", data.scope);
+                self.store_token(&data);
+                self.save_token().await;
+                Ok(data.access_token)
+            }
+            Err(e) => {
+                eprintln!("token exchange failed: {:?}", e);
+                Err(e)
+            }
+        }
+    }
 
-        let raw_auth_str: Vec<u8> = format!("{}:{}", CLIENT_ID, CLIENT_SECRET).into_bytes();
+    // the app's own credentials, for the token endpoint. PKCE apps have none
+    fn client_auth_headers(&self) -> reqwest::header::HeaderMap {
+        let raw_auth_str: Vec<u8> =
+            format!("{}:{}", self.client_id, self.client_secret).into_bytes();
         let encoded_auth_str = general_purpose::STANDARD.encode(&raw_auth_str);
 
         let mut headers = reqwest::header::HeaderMap::new();
@@ -117,87 +694,565 @@ impl Spotify {
             "Content-Type",
             "application/x-www-form-urlencoded".parse().unwrap(),
         );
+        if self.client_secret.is_empty() {
+            return headers;
+        }
         headers.insert(
             "Authorization",
             format!("Basic {}", encoded_auth_str).parse().unwrap(),
         );
-        let body = reqwest::Body::from(format!(
-            "grant_type=authorization_code&code={auth_code}&redirect_uri={redirect_uri}"
-        ));
+        headers
+    }
 
-        let spotify_server_res = client.post(url).headers(headers).body(body).send().await;
+    // trades an auth code for a token without keeping it, to check the app's
+    // credentials and redirect uri work. answers with the granted scopes
+    pub async fn check_auth_code(&self, auth_code: &str) -> Result<String, SpotifyError> {
+        let body = format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}{}",
+            urlencoding::encode(auth_code),
+            urlencoding::encode(&self.redirect_uri),
+            self.public_client_params()
+        );
+        let data = self
+            .client(Endpoint::Auth)
+            .post("https://accounts.spotify.com/api/token")
+            .headers(self.client_auth_headers())
+            .body(body)
+            .send_spotify(Endpoint::Auth)
+            .await?
+            .check_status()?
+            .json::<TokenResponse>()
+            .await?;
+        Ok(data.scope)
+    }
 
-        let j: Result<TokenResponse, reqwest::Error> = match spotify_server_res {
-            Ok(res) => res.json().await,
-            Err(e) => {
-                println!("Server Error: {:?}", e);
-                return anyhow::Result::Err(anyhow::anyhow!("Server Error: {:?}", e));
-            }
+    fn store_token(&mut self, data: &TokenResponse) {
+        // a verifier only goes with the one code
+        self.code_verifier = None;
+        self.token = Some(data.access_token.clone());
+        if let Some(refresh_token) = &data.refresh_token {
+            self.refresh_token = Some(refresh_token.clone());
+        }
+        if !data.scope.is_empty() {
+            self.granted_scope = Some(data.scope.clone());
+        }
+        self.token_expires_at =
+            Some(SystemTime::now() + Duration::from_secs(data.expires_in.max(0) as u64));
+    }
+
+    // picks up the external token file when it's changed since last read
+    fn read_external_token(&mut self) -> Result<(), SpotifyError> {
+        let Some(path) = &self.external_token else {
+            return Ok(());
+        };
+        let unreadable = |e: std::io::Error| {
+            SpotifyError::AuthError(format!("couldn't read {}: {}", path.display(), e))
         };
+        let modified = std::fs::metadata(path).map_err(unreadable)?.modified().ok();
+        if modified.is_some() && modified == self.external_modified {
+            return Ok(());
+        }
+        let contents = std::fs::read_to_string(path).map_err(unreadable)?;
+        let saved = SavedToken::parse(contents.trim().to_owned());
+        if saved.access_token.is_empty() {
+            return Err(SpotifyError::AuthError(format!(
+                "{} is empty",
+                path.display()
+            )));
+        }
+        self.token = Some(saved.access_token);
+        self.token_expires_at = saved
+            .expires_at
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        self.external_modified = modified;
+        Ok(())
+    }
 
-        match j {
-            Ok(data) => {
-                println!("got token for: {:?}", data.scope);
-                self.token = Some(data.access_token.clone());
-                write_token_to_disk(data.access_token.clone()).await;
-                return Ok(data.access_token);
-            }
-            Err(e) => {
-                println!("json parsing error: {:?}", e);
-                return anyhow::Result::Err(anyhow::anyhow!("json parsing error: {:?}", e));
-            }
+    // swaps the refresh token from the original auth for a new access token.
+    // an external token is read again instead, in case it was just replaced
+    pub async fn refresh_access_token(&mut self) -> Result<String, SpotifyError> {
+        if self.external_token.is_some() {
+            self.external_modified = None;
+            self.read_external_token()?;
+            return Ok(self.token.clone().unwrap_or_default());
         }
+        let refresh_token = self.refresh_token.clone().ok_or_else(|| {
+            SpotifyError::AuthError(String::from("no refresh token, re-run auth"))
+        })?;
+
+        let headers = self.client_auth_headers();
+        let body = reqwest::Body::from(format!(
+            "grant_type=refresh_token&refresh_token={}{}",
+            urlencoding::encode(refresh_token.as_str()),
+            self.public_client_params()
+        ));
+
+        let data = self
+            .client(Endpoint::Auth)
+            .post("https://accounts.spotify.com/api/token")
+            .headers(headers)
+            .body(body)
+            .send_spotify(Endpoint::Auth)
+            .await?
+            .check_status()?
+            .json::<TokenResponse>()
+            .await?;
+
+        self.store_token(&data);
+        self.save_token().await;
+        Ok(data.access_token)
     }
 
-    pub async fn get_currently_playing(&self) -> Result<CurrentlyPlayingResponse, anyhow::Error> {
+    // nothing playing comes back as an empty 204. so does a private session,
+    // which only the player state's device owns up to
+    pub async fn get_currently_playing(&self) -> Result<CurrentlyPlaying, SpotifyError> {
         let url = "https://api.spotify.com/v1/me/player/currently-playing";
-        let client = Client::new();
+        let client = self.client(Endpoint::Polling);
 
-        let raw_auth_str: Vec<u8> = format!("{}:{}", CLIENT_ID, CLIENT_SECRET).into_bytes();
-        let encoded_auth_str = general_purpose::STANDARD.encode(&raw_auth_str);
         let mut headers = reqwest::header::HeaderMap::new();
         // headers.insert("Content-Type",
         //     "application/x-www-form-urlencoded".parse().unwrap(),);
         headers.insert(
             "Authorization",
-            format!("Bearer {}", self.token.clone().unwrap())
-                .parse()
-                .unwrap(),
+            format!(
+                "Bearer {}",
+                self.token
+                    .clone()
+                    .ok_or_else(|| SpotifyError::AuthError(String::from("no token")))?
+            )
+            .parse()
+            .unwrap(),
         );
+        self.insert_language(&mut headers);
 
         let currently_playing_res = client
             .get(url)
+            .query(&[("additional_types", "episode")])
             .headers(headers)
-            .send()
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?;
+        if currently_playing_res.status() == reqwest::StatusCode::NO_CONTENT {
+            let private = self
+                .get_player_state()
+                .await?
+                .and_then(|state| state.device)
+                .is_some_and(|device| device.is_private_session);
+            return Ok(if private {
+                CurrentlyPlaying::PrivateSession
+            } else {
+                CurrentlyPlaying::NothingPlaying
+            });
+        }
+
+        Ok(CurrentlyPlaying::Playing(Box::new(
+            currently_playing_res
+                .json::<CurrentlyPlayingResponse>()
+                .await?,
+        )))
+    }
+
+    fn auth_headers(&self) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Authorization",
+            format!("Bearer {}", self.token.clone().unwrap_or_default())
+                .parse()
+                .unwrap(),
+        );
+        self.insert_language(&mut headers);
+        headers
+    }
+
+    // PUT/POST/DELETE on a web API endpoint that doesn't take a body. spotify
+    // rejects body-less PUTs without an explicit Content-Length
+    async fn empty_request(&self, method: reqwest::Method, path: &str) -> Result<(), SpotifyError> {
+        let url = format!("https://api.spotify.com/v1/{}", path);
+        let client = self.client(Endpoint::Polling);
+
+        let mut headers = self.auth_headers();
+        headers.insert("Content-Length", "0".parse().unwrap());
+
+        client
+            .request(method, url)
+            .headers(headers)
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?;
+        Ok(())
+    }
+
+    async fn player_request(
+        &self,
+        method: reqwest::Method,
+        endpoint: &str,
+    ) -> Result<(), SpotifyError> {
+        self.empty_request(method, format!("me/player/{}", endpoint).as_str())
+            .await
+    }
+
+    // the full album, which unlike the one tracks come with has the label,
+    // genres and copyrights. each album is only asked for once
+    pub async fn get_album(&mut self, album_id: &str) -> Result<AlbumObject, SpotifyError> {
+        if let Some(album) = self.albums.get(album_id) {
+            return Ok(album.clone());
+        }
+        let album = self
+            .client(Endpoint::Polling)
+            .get(format!("https://api.spotify.com/v1/albums/{}", album_id))
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<AlbumObject>()
+            .await?;
+        self.albums.insert(album_id.to_owned(), album.clone());
+        Ok(album)
+    }
+
+    // covers come from spotify's cdn rather than the api, so there's no token
+    // or rate limit to mind, and they're fetched apart from the client that
+    // every poll and command waits on
+    pub fn album_art_fetcher(&self) -> AlbumArtFetcher {
+        AlbumArtFetcher {
+            client: self.client(Endpoint::Art).clone(),
+            cache: self.album_art.clone(),
+        }
+    }
+
+    pub async fn get_audio_features(&self, track_id: &str) -> Result<AudioFeatures, SpotifyError> {
+        Ok(self
+            .client(Endpoint::Polling)
+            .get(format!(
+                "https://api.spotify.com/v1/audio-features/{}",
+                track_id
+            ))
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<AudioFeatures>()
+            .await?)
+    }
+
+    // what plays next, as (artist, name). episodes have no artist
+    pub async fn get_queue(&self) -> Result<Vec<Queued>, SpotifyError> {
+        let res = self
+            .client(Endpoint::Polling)
+            .get("https://api.spotify.com/v1/me/player/queue")
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<QueueResponse>()
+            .await?;
+        Ok(res
+            .queue
+            .into_iter()
+            .map(|item| {
+                let artist = item
+                    .artists
+                    .first()
+                    .map(|artist| artist.name.clone())
+                    .unwrap_or_default();
+                Queued {
+                    artist,
+                    name: item.name,
+                    explicit: item.explicit,
+                }
+            })
+            .collect())
+    }
+
+    // a track or episode uri, e.g. spotify:track:4uLU6hMCjMI75M1A2tKUQC
+    pub async fn add_to_queue(&self, uri: &str) -> Result<(), SpotifyError> {
+        self.player_request(
+            reqwest::Method::POST,
+            format!("queue?uri={}", urlencoding::encode(uri)).as_str(),
+        )
+        .await
+    }
+
+    pub async fn save_track(&self, track_id: &str) -> Result<(), SpotifyError> {
+        self.empty_request(
+            reqwest::Method::PUT,
+            format!("me/tracks?ids={}", track_id).as_str(),
+        )
+        .await
+    }
+
+    // whether the track is in the user's library
+    pub async fn is_track_saved(&self, track_id: &str) -> Result<bool, SpotifyError> {
+        let saved = self
+            .client(Endpoint::Polling)
+            .get(format!(
+                "https://api.spotify.com/v1/me/tracks/contains?ids={}",
+                track_id
+            ))
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<Vec<bool>>()
+            .await?;
+        Ok(saved.first().copied().unwrap_or(false))
+    }
+
+    pub async fn remove_saved_track(&self, track_id: &str) -> Result<(), SpotifyError> {
+        self.empty_request(
+            reqwest::Method::DELETE,
+            format!("me/tracks?ids={}", track_id).as_str(),
+        )
+        .await
+    }
+
+    pub async fn pause(&self) -> Result<(), SpotifyError> {
+        self.player_request(reqwest::Method::PUT, "pause").await
+    }
+
+    pub async fn resume(&self) -> Result<(), SpotifyError> {
+        self.player_request(reqwest::Method::PUT, "play").await
+    }
+
+    // starts a track, or an album, artist or playlist from the top
+    pub async fn play_uri(&self, uri: &str) -> Result<(), SpotifyError> {
+        let body = if uri.starts_with("spotify:track:") || uri.starts_with("spotify:episode:") {
+            serde_json::json!({ "uris": [uri] })
+        } else {
+            serde_json::json!({ "context_uri": uri })
+        };
+        self.client(Endpoint::Polling)
+            .put("https://api.spotify.com/v1/me/player/play")
+            .headers(self.auth_headers())
+            .json(&body)
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?;
+        Ok(())
+    }
+
+    // what spotify thinks best matches the query, best first, up to `limit`
+    // of each type asked for. the other types come back empty
+    pub async fn search(
+        &self,
+        query: &str,
+        types: &[SearchType],
+        limit: usize,
+    ) -> Result<SearchResults, SpotifyError> {
+        let types: Vec<&str> = types.iter().map(SearchType::as_str).collect();
+        Ok(self
+            .client(Endpoint::Polling)
+            .get(format!(
+                "https://api.spotify.com/v1/search?type={}&limit={}&q={}",
+                types.join(","),
+                limit,
+                urlencoding::encode(query)
+            ))
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<SearchResults>()
+            .await?)
+    }
+
+    pub async fn next_track(&self) -> Result<(), SpotifyError> {
+        self.player_request(reqwest::Method::POST, "next").await
+    }
+
+    pub async fn previous_track(&self) -> Result<(), SpotifyError> {
+        self.player_request(reqwest::Method::POST, "previous").await
+    }
+
+    pub async fn seek_to(&self, position_ms: u64) -> Result<(), SpotifyError> {
+        self.player_request(
+            reqwest::Method::PUT,
+            format!("seek?position_ms={}", position_ms).as_str(),
+        )
+        .await
+    }
+
+    pub async fn set_shuffle(&self, shuffle: bool) -> Result<(), SpotifyError> {
+        self.player_request(
+            reqwest::Method::PUT,
+            format!("shuffle?state={}", shuffle).as_str(),
+        )
+        .await
+    }
+
+    pub async fn set_repeat(&self, repeat: RepeatState) -> Result<(), SpotifyError> {
+        self.player_request(
+            reqwest::Method::PUT,
+            format!("repeat?state={}", repeat.as_str()).as_str(),
+        )
+        .await
+    }
+
+    pub async fn set_volume(&self, volume_percent: u8) -> Result<(), SpotifyError> {
+        self.player_request(
+            reqwest::Method::PUT,
+            format!("volume?volume_percent={}", volume_percent).as_str(),
+        )
+        .await
+    }
+
+    // None when no device is active, spotify answers with an empty 204
+    pub async fn get_player_state(&self) -> Result<Option<PlaybackStateResponse>, SpotifyError> {
+        let url = "https://api.spotify.com/v1/me/player";
+        let client = self.client(Endpoint::Polling);
+
+        let res = client
+            .get(url)
+            .query(&[("additional_types", "episode")])
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
             .await?
-            .json::<CurrentlyPlayingResponse>()
+            .check_status()?;
+        if res.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        Ok(Some(res.json::<PlaybackStateResponse>().await?))
+    }
+
+    // what's being played from, e.g. the playlist's name. liked songs have no
+    // name to look up
+    pub async fn context_name(&mut self, context: &Context) -> Option<String> {
+        if let Some(name) = self.context_names.get(&context.uri) {
+            return name.clone();
+        }
+        let id = context.uri.rsplit(':').next().unwrap_or_default();
+        let path = match context.context_type.as_str() {
+            "playlist" => format!("playlists/{}?fields=name", id),
+            "album" => format!("albums/{}", id),
+            "artist" => format!("artists/{}", id),
+            "show" => format!("shows/{}", id),
+            "collection" => return Some(String::from("Liked Songs")),
+            _ => return None,
+        };
+        let res = async {
+            self.client(Endpoint::Polling)
+                .get(format!("https://api.spotify.com/v1/{}", path))
+                .headers(self.auth_headers())
+                .send_spotify(Endpoint::Polling)
+                .await?
+                .check_status()?
+                .json::<Named>()
+                .await
+                .map_err(SpotifyError::from)
+        }
+        .await;
+        let name = match res {
+            Ok(named) => Some(named.name),
+            // gone or private, asking again won't change that
+            Err(e) if unavailable(&e) => None,
+            // tried again on the next poll
+            Err(e) => {
+                eprintln!("couldn't look up {}: {}", context.uri, e);
+                return None;
+            }
+        };
+        self.context_names.insert(context.uri.clone(), name.clone());
+        name
+    }
+
+    // the connect devices spotify can play on right now
+    pub async fn devices(&self) -> Result<Vec<Device>, SpotifyError> {
+        let res = self
+            .client(Endpoint::Polling)
+            .get("https://api.spotify.com/v1/me/player/devices")
+            .headers(self.auth_headers())
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?
+            .json::<DevicesResponse>()
             .await?;
+        Ok(res.devices)
+    }
 
-        Ok(currently_playing_res)
+    // moves playback to another connect device, starting it there if `play`
+    pub async fn transfer_playback(&self, device_id: &str, play: bool) -> Result<(), SpotifyError> {
+        self.client(Endpoint::Polling)
+            .put("https://api.spotify.com/v1/me/player")
+            .headers(self.auth_headers())
+            .json(&serde_json::json!({
+                "device_ids": [device_id],
+                "play": play,
+            }))
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?;
+        Ok(())
     }
 
-    //         let j: Result<CurrentlyPlayingResponse, anyhow::Error> = match currently_playing_res {
-    //             Ok(res) => {
-    //              res.json().await
-    //             },
-    //             Err(e) => {
-    //                 println!("Server Error: {:?}", e);
-    //                 return anyhow::Result::Err(anyhow::anyhow!("Server Error: {:?}", e));
-    //             }
-    //         };
-    //             match j {
-    //                 Ok(data) {
-    //                     return j;
-    //                 },
-    //                 Err(e) => {
-    //                     return anyhow::Result::Err(anyhow::anyhow!("json parsing error: {:?}", e));
-    //                 }
-    //             }
+    // every playlist the user owns or follows, a page at a time
+    pub async fn playlists(&self) -> Result<Vec<Playlist>, SpotifyError> {
+        let mut playlists = Vec::new();
+        let mut page = Some(String::from(
+            "https://api.spotify.com/v1/me/playlists?limit=50",
+        ));
+        while let Some(url) = page {
+            let res = self
+                .client(Endpoint::Polling)
+                .get(url)
+                .headers(self.auth_headers())
+                .send_spotify(Endpoint::Polling)
+                .await?
+                .check_status()?
+                .json::<PlaylistsResponse>()
+                .await?;
+            playlists.extend(res.items.into_iter().flatten());
+            page = res.next;
+        }
+        Ok(playlists)
+    }
+
+    // appends to the end of the playlist
+    pub async fn add_to_playlist(&self, playlist_id: &str, uri: &str) -> Result<(), SpotifyError> {
+        self.client(Endpoint::Polling)
+            .post(format!(
+                "https://api.spotify.com/v1/playlists/{}/tracks",
+                playlist_id
+            ))
+            .headers(self.auth_headers())
+            .json(&serde_json::json!({ "uris": [uri] }))
+            .send_spotify(Endpoint::Polling)
+            .await?
+            .check_status()?;
+        Ok(())
+    }
 }
 
-async fn write_token_to_disk(token: String) {
-    let mut f = tokio::fs::File::create("token").await.unwrap();
-    f.write_all(token.as_bytes()).await.unwrap();
+// where the loopback server listens for the auth redirect, from a redirect
+// uri like http://127.0.0.1:8000
+pub fn loopback_address(redirect_uri: &str) -> Option<String> {
+    let host_port = redirect_uri.strip_prefix("http://")?.split('/').next()?;
+    host_port.contains(':').then(|| host_port.to_owned())
+}
+
+// sealed with a key tied to this machine, see secrets.rs. when that can't be
+// done it's saved as is, readable by the user alone, and said so
+async fn write_token_to_disk(token: String) -> std::io::Result<()> {
+    let contents = crate::secrets::seal_token(&token).unwrap_or_else(|e| {
+        eprintln!(
+            "couldn't seal the token ({:?}), saving it unencrypted where only you can read it",
+            e
+        );
+        token.into_bytes()
+    });
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut f = options.open("token").await?;
+    // an older file keeps its permissions, so tighten them too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        f.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    f.write_all(&contents).await
 }
 
 #[derive(Deserialize)]
@@ -211,50 +1266,851 @@ enum CurrentlyPlayingType {
     #[serde(rename = "unknown")]
     Unknown,
 }
-// #[derive(Deserialize)]
-// pub enum PlayableItem {
-//     TrackObject(TrackObject),
-//     EpisodeObject(EpisodeObject),
-// }
+// what's playing. spotify only sends episodes to requests that ask for them
+// with additional_types=episode, otherwise the item is null while one plays
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PlayableItem {
+    Track(Box<TrackObject>),
+    Episode(EpisodeObject),
+}
 #[derive(Deserialize)]
 pub struct TrackObject {
-    album: AlbumObject,
+    // null for local files
+    pub id: Option<String>,
+    pub name: String,
+    pub album: AlbumObject,
     pub artists: Vec<SimplifiedArtistObject>,
-    duration_ms: i32,
-    id: String,
+    pub duration_ms: i32,
+    #[serde(default)]
+    pub explicit: bool,
+    // on its disc
+    #[serde(default)]
+    pub track_number: Option<u32>,
+    #[serde(default)]
+    pub disc_number: Option<u32>,
+}
+#[derive(Deserialize)]
+pub struct EpisodeObject {
+    // the episode's title
     pub name: String,
-    popularity: i32,
-    is_local: bool,
+    pub duration_ms: i32,
+    #[serde(default)]
+    pub explicit: bool,
+    pub show: ShowObject,
 }
 #[derive(Deserialize)]
-pub struct EpisodeObject {}
+pub struct ShowObject {
+    pub name: String,
+    pub publisher: String,
+}
+
+pub enum CurrentlyPlaying {
+    Playing(Box<CurrentlyPlayingResponse>),
+    NothingPlaying,
+    // spotify won't say what
+    PrivateSession,
+}
+
+impl CurrentlyPlaying {
+    pub fn playing(self) -> Result<CurrentlyPlayingResponse, SpotifyError> {
+        match self {
+            CurrentlyPlaying::Playing(res) => Ok(*res),
+            CurrentlyPlaying::NothingPlaying | CurrentlyPlaying::PrivateSession => {
+                Err(SpotifyError::NotPlaying)
+            }
+        }
+    }
+}
 
 #[derive(Deserialize)]
 pub struct CurrentlyPlayingResponse {
-    timestamp: u64,
-    progress_ms: i32,
-    is_playing: bool,
-    // could ALSO be an EpisodeObject maybe?
-    pub item: Option<Item>,
+    pub timestamp: u64,
+    pub progress_ms: i32,
+    pub is_playing: bool,
+    pub item: Option<PlayableItem>,
+    pub context: Option<Context>,
     currently_playing_type: CurrentlyPlayingType,
+    // in spotify's docs for currently-playing as well as /me/player, but
+    // often left out of the response, so None means not known rather than off
+    pub shuffle_state: Option<bool>,
+    pub repeat_state: Option<RepeatState>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum RepeatState {
+    #[serde(rename = "off")]
+    Off,
+    #[serde(rename = "track")]
+    Track,
+    #[serde(rename = "context")]
+    Context,
+}
+
+impl RepeatState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatState::Off => "off",
+            RepeatState::Track => "track",
+            RepeatState::Context => "context",
+        }
+    }
+
+    // same order the spotify client cycles through
+    pub fn next(&self) -> Self {
+        match self {
+            RepeatState::Off => RepeatState::Context,
+            RepeatState::Context => RepeatState::Track,
+            RepeatState::Track => RepeatState::Off,
+        }
+    }
 }
 
 #[derive(Deserialize)]
-pub struct Item {
-    pub album: AlbumObject,
+pub struct PlaybackStateResponse {
+    pub timestamp: u64,
+    // missing when nothing's playing
+    pub progress_ms: Option<u64>,
+    pub is_playing: bool,
+    pub item: Option<PlayableItem>,
+    pub context: Option<Context>,
+    pub shuffle_state: bool,
+    pub repeat_state: RepeatState,
+    // its volume_percent is the playback volume
+    pub device: Option<Device>,
+}
+
+// where playback is coming from
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Context {
+    // e.g. spotify:playlist:37i9dQZF1DXcBWIGoYBM5M
+    pub uri: String,
+    // "playlist", "album", "artist", "show" or "collection"
+    #[serde(rename = "type")]
+    pub context_type: String,
 }
 
 #[derive(Deserialize)]
-pub struct AlbumObject {
-    id: String,
+struct Named {
+    name: String,
+}
+
+// a spotify connect device, e.g. the desktop app, a phone or a speaker
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Device {
+    // missing for some restricted devices
+    pub id: Option<String>,
+    pub name: String,
+    // "Computer", "Smartphone", "Speaker", ...
+    #[serde(rename = "type")]
+    pub device_type: String,
+    // null for devices whose volume can't be controlled
+    pub volume_percent: Option<u8>,
+    #[serde(default)]
+    pub is_private_session: bool,
+    // whether playback is on it, as of the request it came with
+    #[serde(default)]
+    pub is_active: bool,
+}
+
+// spotify's analysis of a track, the parts kyomi uses
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct AudioFeatures {
+    // beats per minute
+    pub tempo: f32,
+    // 0 to 1
+    pub energy: f32,
+    // integrated loudness of the whole track in dB, around -60 to 0
+    pub loudness: f32,
+}
+
+// tracks and episodes alike, only what the preview needs
+#[derive(Deserialize)]
+struct QueuedItem {
     name: String,
-    release_date: String,
-    release_date_precision: String,
+    #[serde(default)]
+    artists: Vec<SimplifiedArtistObject>,
+    #[serde(default)]
+    explicit: bool,
+}
+
+// what get_queue gives back for each item
+pub struct Queued {
+    // the first artist, or empty for episodes
+    pub artist: String,
+    pub name: String,
+    pub explicit: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchType {
+    Track,
+    Artist,
+    Album,
+}
+
+impl SearchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchType::Track => "track",
+            SearchType::Artist => "artist",
+            SearchType::Album => "album",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchTrack {
+    pub uri: String,
+    pub name: String,
     pub artists: Vec<SimplifiedArtistObject>,
 }
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchArtist {
+    pub uri: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchAlbum {
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<SimplifiedArtistObject>,
+    // "2012-03-02", or just the year or month for older releases
+    pub release_date: Option<String>,
+}
+
+impl SearchAlbum {
+    pub fn release(&self) -> Option<ReleaseDate> {
+        ReleaseDate::parse(self.release_date.as_deref()?, None)
+    }
+}
+
+// spotify leaves a type out entirely when it wasn't searched for, and can put
+// nulls among the items
 #[derive(Deserialize)]
+struct SearchPage<T> {
+    items: Vec<Option<T>>,
+}
+
+fn search_items<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let page = Option::<SearchPage<T>>::deserialize(deserializer)?;
+    Ok(page
+        .map(|page| page.items.into_iter().flatten().collect())
+        .unwrap_or_default())
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SearchResults {
+    #[serde(default, deserialize_with = "search_items")]
+    pub tracks: Vec<SearchTrack>,
+    #[serde(default, deserialize_with = "search_items")]
+    pub artists: Vec<SearchArtist>,
+    #[serde(default, deserialize_with = "search_items")]
+    pub albums: Vec<SearchAlbum>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct PlaylistsResponse {
+    // spotify sometimes has nulls in here
+    items: Vec<Option<Playlist>>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QueueResponse {
+    queue: Vec<QueuedItem>,
+}
+
+#[derive(Deserialize)]
+struct DevicesResponse {
+    devices: Vec<Device>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlbumObject {
+    // these are null for local files
+    pub id: Option<String>,
+    pub name: String,
+    release_date: Option<String>,
+    release_date_precision: Option<String>,
+    pub artists: Vec<SimplifiedArtistObject>,
+    #[serde(default)]
+    pub total_tracks: Option<u32>,
+    // only on the full album from get_album
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub copyrights: Vec<Copyright>,
+    // the cover in a few sizes, widest first. none for local files
+    #[serde(default)]
+    pub images: Vec<ImageObject>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ImageObject {
+    pub url: String,
+    // null when spotify doesn't know. covers are square
+    pub width: Option<u32>,
+}
+
+// the widget draws covers at around this many pixels
+const COVER_SIZE: u32 = 300;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Copyright {
+    pub text: String,
+    // C for the copyright, P for the sound recording's
+    #[serde(rename = "type")]
+    pub copyright_type: String,
+}
+
+impl AlbumObject {
+    // the cover nearest the size it's drawn at, or any one when none say
+    // how big they are
+    pub fn cover_url(&self) -> Option<&str> {
+        self.images
+            .iter()
+            .min_by_key(|image| {
+                image
+                    .width
+                    .map_or(u32::MAX, |width| width.abs_diff(COVER_SIZE))
+            })
+            .map(|image| image.url.as_str())
+    }
+
+    // the album's own copyright over the recording's, as spotify prints it
+    pub fn copyright(&self) -> Option<&str> {
+        self.copyrights
+            .iter()
+            .find(|copyright| copyright.copyright_type == "C")
+            .or(self.copyrights.first())
+            .map(|copyright| copyright.text.as_str())
+    }
+
+    pub fn release(&self) -> Option<ReleaseDate> {
+        ReleaseDate::parse(
+            self.release_date.as_deref()?,
+            self.release_date_precision.as_deref(),
+        )
+    }
+}
+
+// covers kept decoded, around 1.6MB each at spotify's largest size
+const ALBUM_ART_CACHE: usize = 4;
+
+// made by Spotify::album_art_fetcher, sharing its cache of decoded covers
+#[derive(Clone)]
+pub struct AlbumArtFetcher {
+    client: reqwest::Client,
+    cache: Arc<Mutex<ArtCache>>,
+}
+
+// for covers from anywhere else, with a cache of its own
+impl Default for AlbumArtFetcher {
+    fn default() -> Self {
+        AlbumArtFetcher {
+            client: client(Endpoint::Art),
+            cache: Arc::default(),
+        }
+    }
+}
+
+impl AlbumArtFetcher {
+    // the cover at album_art_url, decoded to rgba. mpris players often point
+    // at a file they've saved
+    pub async fn fetch(&self, url: &str) -> Result<Arc<AlbumArt>, anyhow::Error> {
+        if let Some(art) = self.cache.lock().unwrap().get(url) {
+            return Ok(art);
+        }
+        let (bytes, content_type) = match reqwest::Url::parse(url)
+            .ok()
+            .filter(|parsed| parsed.scheme() == "file")
+        {
+            Some(file) => read_art_file(&file).await?,
+            None => {
+                let res = self
+                    .client
+                    .get(url)
+                    .send_retrying(Endpoint::Art)
+                    .await?
+                    .check_status()?;
+                let content_type = res
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_owned();
+                (res.bytes().await?.to_vec(), content_type)
+            }
+        };
+        // decoding a 640px jpeg takes a few milliseconds, too long for a worker
+        let art =
+            tokio::task::spawn_blocking(move || decode_album_art(&bytes, &content_type)).await??;
+        let art = Arc::new(art);
+        self.cache.lock().unwrap().insert(url, art.clone());
+        Ok(art)
+    }
+}
+
+// a local cover with the content type its bytes look like, since there's no
+// header to say
+async fn read_art_file(url: &reqwest::Url) -> Result<(Vec<u8>, String), anyhow::Error> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow::anyhow!("bad file url {}", url))?;
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| anyhow::anyhow!("couldn't read {}: {}", path.display(), e))?;
+    let content_type = match image::guess_format(&bytes) {
+        Ok(image::ImageFormat::Png) => "image/png",
+        Ok(image::ImageFormat::Jpeg) => "image/jpeg",
+        _ => "",
+    };
+    Ok((bytes, content_type.to_owned()))
+}
+
+// decoded covers by url, least recently used first
+#[derive(Debug, Default)]
+struct ArtCache(Vec<(String, Arc<AlbumArt>)>);
+
+impl ArtCache {
+    fn get(&mut self, url: &str) -> Option<Arc<AlbumArt>> {
+        let at = self.0.iter().position(|(cached, _)| cached == url)?;
+        let entry = self.0.remove(at);
+        let art = entry.1.clone();
+        self.0.push(entry);
+        Some(art)
+    }
+
+    fn insert(&mut self, url: &str, art: Arc<AlbumArt>) {
+        if self.0.len() >= ALBUM_ART_CACHE {
+            self.0.remove(0);
+        }
+        self.0.push((url.to_owned(), art));
+    }
+}
+
+// album art ready to upload as an Rgba8 texture, rows top to bottom
+#[derive(Debug)]
+pub struct AlbumArt {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+// only what the cdn serves covers as. anything else is most likely an error
+// page sent with a 200
+fn decode_album_art(bytes: &[u8], content_type: &str) -> Result<AlbumArt, SpotifyError> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    let format = match mime.to_ascii_lowercase().as_str() {
+        "image/jpeg" | "image/jpg" => image::ImageFormat::Jpeg,
+        "image/png" => image::ImageFormat::Png,
+        _ => {
+            return Err(SpotifyError::Deserialization(format!(
+                "album art came as {:?}, not an image",
+                content_type
+            )))
+        }
+    };
+    let image = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| SpotifyError::Deserialization(format!("couldn't decode album art: {}", e)))?
+        .into_rgba8();
+    Ok(AlbumArt {
+        width: image.width(),
+        height: image.height(),
+        rgba: image.into_raw(),
+    })
+}
+
+// when an album came out, as precisely as spotify knows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReleaseDate {
+    Year(i32),
+    Month(i32, u32),
+    Day(chrono::NaiveDate),
+}
+
+impl ReleaseDate {
+    // "1999", "1999-03" or "1999-03-02". spotify pads dates it only knows the
+    // year of out to a day, so the precision it sends alongside wins
+    pub fn parse(date: &str, precision: Option<&str>) -> Option<Self> {
+        let mut parts = date.trim().split('-');
+        let year = parts.next()?.parse().ok().filter(|year| *year > 0)?;
+        let month = parts.next().and_then(|month| month.parse().ok());
+        let day = parts.next().and_then(|day| day.parse().ok());
+        let parsed = match (month, day) {
+            (Some(month), Some(day)) => {
+                ReleaseDate::Day(chrono::NaiveDate::from_ymd_opt(year, month, day)?)
+            }
+            (Some(month), None) if (1..=12).contains(&month) => ReleaseDate::Month(year, month),
+            (Some(_), None) => return None,
+            (None, _) => ReleaseDate::Year(year),
+        };
+        Some(match (precision, parsed) {
+            (Some("year"), _) => ReleaseDate::Year(year),
+            (Some("month"), ReleaseDate::Day(date)) => ReleaseDate::Month(year, date.month()),
+            _ => parsed,
+        })
+    }
+
+    pub fn year(self) -> i32 {
+        match self {
+            ReleaseDate::Year(year) | ReleaseDate::Month(year, _) => year,
+            ReleaseDate::Day(date) => date.year(),
+        }
+    }
+}
+#[derive(Clone, Debug, Deserialize)]
 pub struct SimplifiedArtistObject {
-    id: String,
+    // null for the artists of local files
+    id: Option<String>,
     pub name: String,
-    href: String,
+    href: Option<String>,
+}
+
+#[test]
+fn test_saved_token_reads_old_files() {
+    assert_eq!(
+        SavedToken::parse(String::from("BQD-access")),
+        SavedToken {
+            access_token: String::from("BQD-access"),
+            refresh_token: None,
+            expires_at: None,
+            scope: None,
+        }
+    );
+    let saved = SavedToken {
+        access_token: String::from("access"),
+        refresh_token: Some(String::from("refresh")),
+        expires_at: Some(1_700_000_000),
+        scope: Some(String::from("user-read-private user-library-read")),
+    };
+    assert_eq!(
+        SavedToken::parse(serde_json::to_string(&saved).unwrap()),
+        saved
+    );
+    // and from before the scope was kept
+    let old = SavedToken::parse(String::from(
+        r#"{"access_token":"access","refresh_token":"refresh","expires_at":1700000000}"#,
+    ));
+    assert_eq!(old.scope, None);
+}
+
+#[test]
+fn test_saved_token_scope_check() {
+    let saved = SavedToken {
+        access_token: String::from("access"),
+        refresh_token: None,
+        expires_at: None,
+        scope: Some(String::from("user-library-read user-read-private")),
+    };
+    assert!(saved.covers("user-read-private user-library-read"));
+    assert!(saved.covers("user-read-private"));
+    assert!(!saved.covers("user-read-private playlist-modify-public"));
+    // old files log in again rather than fail later on a missing scope
+    let old = SavedToken {
+        scope: None,
+        ..saved
+    };
+    assert!(!old.covers("user-read-private"));
+}
+
+#[test]
+fn test_player_state_parsing() {
+    let state: PlaybackStateResponse = serde_json::from_str(
+        r#"{
+            "timestamp": 1700000000000, "progress_ms": 42000, "is_playing": true,
+            "shuffle_state": false, "repeat_state": "context",
+            "context": {"type": "playlist", "uri": "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M",
+                "href": "", "external_urls": {}},
+            "device": {"id": "d", "name": "Kitchen", "type": "Speaker", "volume_percent": 40},
+            "item": null
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(state.progress_ms, Some(42000));
+    assert_eq!(state.context.unwrap().context_type, "playlist");
+    assert_eq!(state.device.unwrap().volume_percent, Some(40));
+
+    // nothing loaded on the device
+    let idle: PlaybackStateResponse = serde_json::from_str(
+        r#"{"timestamp": 0, "is_playing": false, "shuffle_state": false,
+            "repeat_state": "off", "context": null, "device": null, "item": null}"#,
+    )
+    .unwrap();
+    assert_eq!(idle.progress_ms, None);
+}
+
+#[test]
+fn test_search_results() {
+    let results: SearchResults = serde_json::from_str(
+        r#"{
+            "tracks": {"items": [{
+                "uri": "spotify:track:1", "name": "Song",
+                "artists": [{"id": "a", "name": "Band", "href": ""}]
+            }, null]},
+            "artists": {"items": [{"uri": "spotify:artist:a", "name": "Band"}]},
+            "albums": {"items": [{"uri": "spotify:album:2", "name": "Record", "artists": [],
+                "release_date": "1999"}]}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(results.tracks.len(), 1);
+    assert_eq!(results.tracks[0].artists[0].name, "Band");
+    assert_eq!(results.artists[0].name, "Band");
+    assert_eq!(results.albums[0].release_date.as_deref(), Some("1999"));
+}
+
+#[test]
+fn test_release_dates() {
+    let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    assert_eq!(
+        ReleaseDate::parse("1997-05-21", Some("day")),
+        Some(ReleaseDate::Day(date(1997, 5, 21)))
+    );
+    assert_eq!(
+        ReleaseDate::parse("1997-05", None),
+        Some(ReleaseDate::Month(1997, 5))
+    );
+    // padded out past what spotify knows
+    assert_eq!(
+        ReleaseDate::parse("1967-01-01", Some("year")),
+        Some(ReleaseDate::Year(1967))
+    );
+    assert_eq!(
+        ReleaseDate::parse("1967-06-01", Some("month")),
+        Some(ReleaseDate::Month(1967, 6))
+    );
+    assert_eq!(ReleaseDate::parse("0000", Some("year")), None);
+    assert_eq!(ReleaseDate::parse("1997-13", None), None);
+    assert_eq!(ReleaseDate::parse("1997-02-30", None), None);
+    assert_eq!(
+        ReleaseDate::parse("2012-03-02", None).map(ReleaseDate::year),
+        Some(2012)
+    );
+}
+
+#[test]
+fn test_full_album_parsing() {
+    let raw = r#"{"id": "a1", "name": "OK Computer", "release_date": "1997-05-21",
+        "release_date_precision": "day", "artists": [], "total_tracks": 12,
+        "label": "Parlophone", "genres": [],
+        "copyrights": [
+            {"text": "(P) 1997 Parlophone Records", "type": "P"},
+            {"text": "(C) 1997 Parlophone Records", "type": "C"}
+        ]}"#;
+    let album: AlbumObject = serde_json::from_str(raw).unwrap();
+    assert_eq!(album.label.as_deref(), Some("Parlophone"));
+    assert_eq!(album.copyright(), Some("(C) 1997 Parlophone Records"));
+
+    // the simplified album a track comes with has none of it
+    let raw = r#"{"id": "a1", "name": "OK Computer", "artists": []}"#;
+    let album: AlbumObject = serde_json::from_str(raw).unwrap();
+    assert_eq!(album.copyright(), None);
+    assert!(album.genres.is_empty());
+    assert_eq!(album.cover_url(), None);
+}
+
+#[test]
+fn test_cover_nearest_drawn_size() {
+    let raw = r#"{"id": "a1", "name": "OK Computer", "artists": [], "images": [
+        {"url": "https://i.scdn.co/image/640", "width": 640, "height": 640},
+        {"url": "https://i.scdn.co/image/300", "width": 300, "height": 300},
+        {"url": "https://i.scdn.co/image/64", "width": 64, "height": 64}
+    ]}"#;
+    let album: AlbumObject = serde_json::from_str(raw).unwrap();
+    assert_eq!(album.cover_url(), Some("https://i.scdn.co/image/300"));
+
+    let raw = r#"{"id": "a1", "name": "OK Computer", "artists": [], "images": [
+        {"url": "https://i.scdn.co/image/any", "width": null, "height": null}
+    ]}"#;
+    let album: AlbumObject = serde_json::from_str(raw).unwrap();
+    assert_eq!(album.cover_url(), Some("https://i.scdn.co/image/any"));
+}
+
+#[test]
+fn test_album_art_decoding() {
+    let mut png = Vec::new();
+    image::RgbImage::from_pixel(2, 1, image::Rgb([255, 0, 0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let art = decode_album_art(&png, "image/png").unwrap();
+    assert_eq!((art.width, art.height), (2, 1));
+    assert_eq!(art.rgba, [255, 0, 0, 255, 255, 0, 0, 255]);
+
+    assert!(matches!(
+        decode_album_art(b"<html>", "text/html; charset=utf-8"),
+        Err(SpotifyError::Deserialization(_))
+    ));
+    // says it's a jpeg but isn't
+    assert!(decode_album_art(&png, "image/jpeg").is_err());
+}
+
+#[test]
+fn test_album_art_cache_drops_least_recently_used() {
+    let art = |width| {
+        Arc::new(AlbumArt {
+            width,
+            height: 1,
+            rgba: Vec::new(),
+        })
+    };
+    let mut cache = ArtCache::default();
+    for i in 0..ALBUM_ART_CACHE {
+        cache.insert(&format!("cover{}", i), art(i as u32));
+    }
+    // using the oldest keeps it, so the next oldest goes instead
+    assert_eq!(cache.get("cover0").unwrap().width, 0);
+    cache.insert("new", art(99));
+
+    assert!(cache.get("cover1").is_none());
+    assert_eq!(cache.get("cover0").unwrap().width, 0);
+    assert_eq!(cache.get("new").unwrap().width, 99);
+    assert!(cache.get("missing").is_none());
+}
+
+#[test]
+fn test_pkce_challenge() {
+    // the example from RFC 7636
+    assert_eq!(
+        code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+    let verifier = code_verifier();
+    assert_eq!(verifier.len(), 86);
+    assert_ne!(verifier, code_verifier());
+
+    let mut spotify = Spotify::from_client_id("id").with_redirect_uri("http://127.0.0.1:8000");
+    assert!(spotify
+        .auth_url_pkce()
+        .contains("&code_challenge_method=S256&code_challenge="));
+    assert!(spotify
+        .public_client_params()
+        .starts_with("&client_id=id&code_verifier="));
+    assert_eq!(
+        spotify.with_client_secret("secret").public_client_params(),
+        ""
+    );
+}
+
+#[test]
+fn test_external_token_file() {
+    let path = std::env::temp_dir().join(format!("kyomi-token-{}", std::process::id()));
+    std::fs::write(&path, "BQD-external\n").unwrap();
+    let mut spotify = Spotify::from_client_id("id").with_external_token(Some(&path));
+    spotify.read_external_token().unwrap();
+    assert_eq!(spotify.token.as_deref(), Some("BQD-external"));
+
+    std::fs::write(
+        &path,
+        r#"{"access_token": "BQD-json", "expires_at": 1700000000}"#,
+    )
+    .unwrap();
+    spotify.external_modified = None;
+    spotify.read_external_token().unwrap();
+    assert_eq!(spotify.token.as_deref(), Some("BQD-json"));
+    assert_eq!(
+        spotify.token_expires_at,
+        Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_needs_login() {
+    let mut spotify = Spotify::from_client_id("id");
+    assert!(spotify.needs_login());
+    spotify.token = Some(String::from("BQD"));
+    spotify.token_expires_at = Some(SystemTime::now() + Duration::from_secs(30));
+    assert!(!spotify.needs_login());
+    // run out, and only a refresh token can bring it back
+    spotify.token_expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+    assert!(spotify.needs_login());
+    spotify.refresh_token = Some(String::from("AQD"));
+    assert!(!spotify.needs_login());
+}
+
+#[test]
+fn test_spotify_errors() {
+    let e: SpotifyError = serde_json::from_str::<Device>("{}").unwrap_err().into();
+    assert!(matches!(e, SpotifyError::Deserialization(_)));
+
+    let limited = SpotifyError::RateLimited {
+        retry_after: Some(Duration::from_secs(30)),
+    };
+    assert_eq!(
+        limited.to_string(),
+        "spotify is rate limiting, retry in 30s"
+    );
+    // spotify's wait when it gives one, doubling backoff when it doesn't
+    let secs = Duration::from_secs;
+    assert_eq!(rate_limit_wait(Some(secs(3)), 1, 0.0), secs(3));
+    assert_eq!(rate_limit_wait(None, 0, 0.0), secs(1));
+    assert_eq!(rate_limit_wait(None, 2, 0.0), secs(4));
+    assert_eq!(rate_limit_wait(Some(secs(4)), 0, 1.0), secs(5));
+    assert_eq!(rate_limit_wait(None, 40, 0.0), secs(64));
+
+    // held off until the limit is over, then free to send
+    let now = Instant::now();
+    assert_eq!(held_off(Some(now + secs(30)), now), Some(secs(30)));
+    assert_eq!(held_off(Some(now), now), None);
+    assert_eq!(held_off(None, now), None);
+
+    // only a missing or private context is remembered as having no name
+    assert!(!unavailable(&limited));
+    assert!(!unavailable(&SpotifyError::NotPlaying));
+
+    // still reachable once it's been through anyhow
+    let e = anyhow::Error::from(SpotifyError::NotPlaying);
+    assert!(matches!(
+        e.downcast_ref::<SpotifyError>(),
+        Some(SpotifyError::NotPlaying)
+    ));
+}
+
+#[test]
+fn test_metadata_language() {
+    let mut headers = reqwest::header::HeaderMap::new();
+    Spotify::from_client_id("id")
+        .with_language(Some("ja"))
+        .insert_language(&mut headers);
+    assert_eq!(headers[reqwest::header::ACCEPT_LANGUAGE], "ja");
+
+    // dropped when it can't be a header, rather than on every call
+    let spotify = Spotify::from_client_id("id").with_language(Some("ja\n"));
+    assert!(spotify.language.is_none());
+    let mut headers = reqwest::header::HeaderMap::new();
+    spotify.insert_language(&mut headers);
+    assert!(headers.is_empty());
+}
+
+#[test]
+fn test_currently_playing_without_shuffle_state() {
+    let raw = r#"{"timestamp": 0, "progress_ms": 0, "is_playing": false, "item": null,
+        "context": null, "currently_playing_type": "unknown"}"#;
+    let res: CurrentlyPlayingResponse = serde_json::from_str(raw).unwrap();
+    assert_eq!(res.shuffle_state, None);
+    assert!(res.repeat_state.is_none());
+
+    let raw = r#"{"timestamp": 0, "progress_ms": 0, "is_playing": false, "item": null,
+        "context": null, "currently_playing_type": "unknown", "shuffle_state": true,
+        "repeat_state": "track"}"#;
+    let res: CurrentlyPlayingResponse = serde_json::from_str(raw).unwrap();
+    assert_eq!(res.shuffle_state, Some(true));
+    assert!(res.repeat_state.is_some());
 }
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// what the widget is currently showing
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Page {
+    #[default]
+    NowPlaying,
+    Details,
+    Recap,
+}
+
+impl Page {
+    // the recap page is only in the rotation while it's turned on
+    pub fn next(&self, recap: bool) -> Self {
+        match self {
+            Page::NowPlaying => Page::Details,
+            Page::Details if recap => Page::Recap,
+            Page::Details | Page::Recap => Page::NowPlaying,
+        }
+    }
+}
+
+// ui state the app changes on its own, restored on the next launch. kept out
+// of config.toml so the user's hand-edited file never gets rewritten.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UiState {
+    pub expanded: bool,
+    pub hidden: bool,
+    pub page: Page,
+    // remembered so a restart mid-stream doesn't leak the track
+    pub redacted: bool,
+    // picked with t or `kyomi ctl theme`, None for the config's own look
+    pub theme: Option<String>,
+}
+
+fn state_path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("kyomi")
+        .join("state.json")
+}
+
+impl UiState {
+    pub fn load() -> Self {
+        std::fs::read_to_string(state_path())
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let path = state_path();
+        let res = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, serde_json::to_string(self).unwrap()));
+        if let Err(e) = res {
+            eprintln!("couldn't save ui state to {}: {}", path.display(), e);
+        }
+    }
+}
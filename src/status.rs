@@ -0,0 +1,59 @@
+use crate::spotify::Device;
+use crate::{format_ms, SpotifyData};
+
+// one plain sentence about what's playing, for `kyomi status`, e.g.
+// "Playing 'Karma Police' by Radiohead, 1:23 of 4:21, on Living Room"
+pub fn sentence(data: Option<&SpotifyData>, device: Option<&Device>) -> String {
+    let data = match data {
+        Some(data) if !data.track_name.is_empty() => data,
+        _ => return String::from("Nothing is playing"),
+    };
+    let mut sentence = format!(
+        "{} '{}'",
+        if data.is_playing { "Playing" } else { "Paused" },
+        data.track_name
+    );
+    if !data.artist_name.is_empty() {
+        sentence.push_str(&format!(" by {}", data.artist_name));
+    }
+    match (data.progress_ms, data.duration_ms) {
+        (Some(progress_ms), Some(duration_ms)) => sentence.push_str(&format!(
+            ", {} of {}",
+            format_ms(progress_ms.min(duration_ms)),
+            format_ms(duration_ms)
+        )),
+        (None, Some(duration_ms)) => {
+            sentence.push_str(&format!(", {} long", format_ms(duration_ms)))
+        }
+        _ => {}
+    }
+    if let Some(device) = device {
+        sentence.push_str(&format!(", on {}", device.name));
+    }
+    sentence
+}
+
+#[test]
+fn test_status_sentence() {
+    let data = SpotifyData {
+        track_name: String::from("X"),
+        artist_name: String::from("Y"),
+        is_playing: true,
+        progress_ms: Some(83_000),
+        duration_ms: Some(225_000),
+        ..Default::default()
+    };
+    let device = Device {
+        id: None,
+        name: String::from("Living Room speaker"),
+        device_type: String::from("Speaker"),
+        volume_percent: None,
+        is_private_session: false,
+        is_active: false,
+    };
+    assert_eq!(
+        sentence(Some(&data), Some(&device)),
+        "Playing 'X' by Y, 1:23 of 3:45, on Living Room speaker"
+    );
+    assert_eq!(sentence(None, None), "Nothing is playing");
+}
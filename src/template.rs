@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use crate::{format_ms, Source, SpotifyData};
+
+// a filter applied to a field, e.g. {{track | truncate:20}}
+#[derive(Clone, Debug, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    // at most this many characters, ending in … when cut
+    Truncate(usize),
+    // milliseconds as m:ss
+    Duration,
+}
+
+impl Filter {
+    fn parse(filter: &str) -> Result<Self, anyhow::Error> {
+        let (name, arg) = match filter.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg.trim())),
+            None => (filter.trim(), None),
+        };
+        match (name, arg) {
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("truncate", Some(len)) => Ok(Filter::Truncate(len.parse()?)),
+            ("duration", None) => Ok(Filter::Duration),
+            _ => Err(anyhow::anyhow!("unknown filter {:?}", filter.trim())),
+        }
+    }
+
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Truncate(len) if value.chars().count() > *len => {
+                let mut cut: String = value.chars().take(len.saturating_sub(1)).collect();
+                cut.push('…');
+                cut
+            }
+            Filter::Truncate(_) => value,
+            Filter::Duration => value.parse().map(format_ms).unwrap_or(value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Field { name: String, filters: Vec<Filter> },
+}
+
+// handlebars-like text with {{field | filter | filter:arg}} placeholders.
+// every text output goes through one of these so they all format the same way
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, anyhow::Error> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| anyhow::anyhow!("unclosed {{{{ in {:?}", template))?;
+            let mut parts = rest[start + 2..start + end].split('|');
+            let name = parts.next().unwrap_or_default().trim().to_owned();
+            let filters = parts.map(Filter::parse).collect::<Result<_, _>>()?;
+            segments.push(Segment::Field { name, filters });
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_owned()));
+        }
+        Ok(Template { segments })
+    }
+
+    // whether the whole template is just this field, maybe filtered
+    pub fn is_field(&self, field: &str) -> bool {
+        matches!(self.segments.as_slice(), [Segment::Field { name, .. }] if name == field)
+    }
+
+    // whether the field turns up anywhere in the template
+    pub fn uses_field(&self, field: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field { name, .. } if name == field))
+    }
+
+    // unknown fields render empty
+    pub fn render(&self, context: &HashMap<&str, String>) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Field { name, filters } => filters.iter().fold(
+                    context.get(name.as_str()).cloned().unwrap_or_default(),
+                    |value, filter| filter.apply(value),
+                ),
+            })
+            .collect()
+    }
+}
+
+// parses each line, falling back to the defaults if any of them is broken
+pub fn parse_lines(lines: &[String], defaults: &[&str]) -> Vec<Template> {
+    match lines.iter().map(|line| Template::parse(line)).collect() {
+        Ok(templates) => templates,
+        Err(e) => {
+            eprintln!("bad template, using the default: {:?}", e);
+            defaults
+                .iter()
+                .map(|line| Template::parse(line).unwrap())
+                .collect()
+        }
+    }
+}
+
+// albums longer than this don't get dots, they wouldn't fit
+const ALBUM_DOTS: u32 = 20;
+
+// "Track 4 of 12". an album playing straight through is drawn out as well,
+// with a dot for each track and the one playing filled in. track numbers
+// start over on each disc while the total counts them all, so past the first
+// disc it's "Disc 2, track 4" with no dots
+fn position(data: &SpotifyData) -> String {
+    let Some(number) = data.track_number else {
+        return String::new();
+    };
+    if let Some(disc) = data.disc_number.filter(|disc| *disc > 1) {
+        return format!("Disc {}, track {}", disc, number);
+    }
+    let Some(total) = data.total_tracks else {
+        return format!("Track {}", number);
+    };
+    let mut position = format!("Track {} of {}", number, total);
+    if data.in_album_order() && total <= ALBUM_DOTS && number <= total {
+        position.push(' ');
+        position.extend((1..=total).map(|n| if n == number { '●' } else { '○' }));
+    }
+    position
+}
+
+// the fields every output can use for a track
+pub fn track_context(data: &SpotifyData, status: &str) -> HashMap<&'static str, String> {
+    let ms = |ms: Option<u64>| ms.map(|ms| ms.to_string()).unwrap_or_default();
+    HashMap::from([
+        ("track", data.track_name.clone()),
+        ("artist", data.artist_name.clone()),
+        ("album", data.album_name.clone()),
+        ("art_url", data.album_art_url.clone()),
+        ("status", status.to_owned()),
+        ("progress_ms", ms(data.progress_ms)),
+        ("duration_ms", ms(data.duration_ms)),
+        ("context", data.context_name.clone().unwrap_or_default()),
+        (
+            "track_number",
+            data.track_number.map(|n| n.to_string()).unwrap_or_default(),
+        ),
+        (
+            "total_tracks",
+            data.total_tracks.map(|n| n.to_string()).unwrap_or_default(),
+        ),
+        ("position", position(data)),
+        (
+            "year",
+            data.release
+                .map(|release| release.year().to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "album_year",
+            match data.release {
+                Some(release) => format!("{} ({})", data.album_name, release.year()),
+                None => data.album_name.clone(),
+            },
+        ),
+        (
+            "progress",
+            data.progress_ms.map(format_ms).unwrap_or_default(),
+        ),
+        (
+            "duration",
+            data.duration_ms.map(format_ms).unwrap_or_default(),
+        ),
+        (
+            "source",
+            String::from(match data.source {
+                Source::Spotify => "spotify",
+                Source::Mpris => "mpris",
+            }),
+        ),
+    ])
+}
+
+#[test]
+fn test_template_filters() {
+    let context = HashMap::from([
+        ("track", String::from("Paranoid Android")),
+        ("artist", String::from("Radiohead")),
+        ("duration_ms", String::from("387000")),
+    ]);
+    let template = Template::parse(
+        "{{artist | upper}}: {{ track | truncate:9 }} [{{duration_ms | duration}}]{{missing}}",
+    )
+    .unwrap();
+    assert_eq!(template.render(&context), "RADIOHEAD: Paranoid… [6:27]");
+
+    assert!(Template::parse("{{track | shout}}").is_err());
+    assert!(Template::parse("{{track").is_err());
+}
+
+#[test]
+fn test_album_position() {
+    let track = SpotifyData {
+        album_id: Some(String::from("4aawyAB9vmqN3uQ7FjRGTy")),
+        track_number: Some(2),
+        total_tracks: Some(5),
+        ..Default::default()
+    };
+    assert_eq!(position(&track), "Track 2 of 5");
+    let straight_through = SpotifyData {
+        context_uri: Some(String::from("spotify:album:4aawyAB9vmqN3uQ7FjRGTy")),
+        ..track.clone()
+    };
+    assert_eq!(position(&straight_through), "Track 2 of 5 ○●○○○");
+    let shuffled = SpotifyData {
+        shuffle: Some(true),
+        ..straight_through
+    };
+    assert_eq!(position(&shuffled), "Track 2 of 5");
+    let second_disc = SpotifyData {
+        disc_number: Some(2),
+        shuffle: None,
+        ..shuffled
+    };
+    assert_eq!(position(&second_disc), "Disc 2, track 2");
+    assert_eq!(position(&SpotifyData::default()), "");
+}
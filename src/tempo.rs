@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// a longer gap than this starts a new count
+const MAX_GAP: Duration = Duration::from_secs(2);
+// the tool goes away once tapping stops for this long
+const IDLE: Duration = Duration::from_secs(5);
+// the bpm is averaged over this many taps
+const KEPT_TAPS: usize = 8;
+
+// counts beats per minute from taps on a key
+#[derive(Default)]
+pub struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    pub fn tap(&mut self, now: Instant) {
+        if self
+            .taps
+            .back()
+            .is_some_and(|last| now.duration_since(*last) > MAX_GAP)
+        {
+            self.taps.clear();
+        }
+        self.taps.push_back(now);
+        if self.taps.len() > KEPT_TAPS {
+            self.taps.pop_front();
+        }
+    }
+
+    // needs two taps to have an interval
+    pub fn bpm(&self) -> Option<f32> {
+        let (first, last) = (self.taps.front()?, self.taps.back()?);
+        let intervals = self.taps.len() as u32 - 1;
+        if intervals == 0 {
+            return None;
+        }
+        let beat = last.duration_since(*first) / intervals;
+        Some(60.0 / beat.as_secs_f32())
+    }
+
+    pub fn is_idle(&self, now: Instant) -> bool {
+        self.taps
+            .back()
+            .is_none_or(|last| now.duration_since(*last) > IDLE)
+    }
+
+    // what's shown while tapping, against spotify's tempo when it's known
+    pub fn lines(&self, track_tempo: Option<f32>) -> Vec<String> {
+        let tapped = match self.bpm() {
+            Some(bpm) => format!("tapped {:.0} bpm", bpm),
+            None => String::from("keep tapping b"),
+        };
+        let track = match (track_tempo, self.bpm()) {
+            (Some(tempo), Some(bpm)) => format!("track {:.0} bpm ({:+.0})", tempo, bpm - tempo),
+            (Some(tempo), None) => format!("track {:.0} bpm", tempo),
+            (None, _) => String::from("track tempo unknown"),
+        };
+        vec![tapped, track]
+    }
+}
+
+#[test]
+fn test_tap_tempo() {
+    let start = Instant::now();
+    let mut tempo = TapTempo::default();
+    tempo.tap(start);
+    assert_eq!(tempo.bpm(), None);
+    for beat in 1..4 {
+        tempo.tap(start + Duration::from_millis(500 * beat));
+    }
+    assert_eq!(tempo.bpm(), Some(120.0));
+    assert_eq!(
+        tempo.lines(Some(118.0)),
+        ["tapped 120 bpm", "track 118 bpm (+2)"]
+    );
+
+    // a pause starts over
+    tempo.tap(start + Duration::from_secs(10));
+    assert_eq!(tempo.bpm(), None);
+    assert!(tempo.is_idle(start + Duration::from_secs(16)));
+}
@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::config::{self, Config};
+
+// the look of the widget, swappable at runtime. only uniforms and text colors
+// depend on it, so switching never touches the device or pipelines
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub accent: [f32; 3],
+    pub text_color: [f32; 3],
+    pub corner_radius: f32,
+    pub opacity: f32,
+    pub accent_effect: AccentEffect,
+    pub accent_period: f32,
+    pub accent_palette: Vec<[f32; 3]>,
+}
+
+// how many palette colors the shader has room for
+pub const PALETTE_SIZE: usize = 4;
+
+// drawn by the shader, so it costs nothing per frame on the cpu
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccentEffect {
+    Still,
+    // pulses the accent's brightness
+    Breathe,
+    // fades through the palette, or around the color wheel from the accent
+    // without one
+    Cycle,
+    // flashes the accent on the beat, harder for more energetic tracks.
+    // breathes when the tempo isn't known
+    Pulse,
+}
+
+impl AccentEffect {
+    // mirrors the ACCENT_ consts in shader.wgsl
+    pub fn shader_value(self) -> u32 {
+        match self {
+            AccentEffect::Still => 0,
+            AccentEffect::Breathe => 1,
+            AccentEffect::Cycle => 2,
+            AccentEffect::Pulse => 3,
+        }
+    }
+}
+
+// themes/<name>.toml in the config directory. anything a theme leaves out
+// comes from config.toml
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    accent: Option<[f32; 3]>,
+    text_color: Option<[f32; 3]>,
+    corner_radius: Option<f32>,
+    opacity: Option<f32>,
+    accent_effect: Option<AccentEffect>,
+    accent_period: Option<f32>,
+    accent_palette: Option<Vec<[f32; 3]>>,
+}
+
+impl Theme {
+    fn from_config(config: &Config) -> Self {
+        Theme {
+            name: String::from("default"),
+            accent: config.accent,
+            text_color: config.text_color,
+            corner_radius: config.corner_radius,
+            opacity: config.opacity,
+            accent_effect: config.accent_effect,
+            accent_period: config.accent_period,
+            accent_palette: config.accent_palette.clone(),
+        }
+    }
+
+    fn with_file(&self, name: &str, file: ThemeFile) -> Self {
+        Theme {
+            name: name.to_owned(),
+            accent: file.accent.unwrap_or(self.accent),
+            text_color: file.text_color.unwrap_or(self.text_color),
+            corner_radius: file.corner_radius.unwrap_or(self.corner_radius),
+            opacity: file.opacity.unwrap_or(self.opacity),
+            accent_effect: file.accent_effect.unwrap_or(self.accent_effect),
+            accent_period: file.accent_period.unwrap_or(self.accent_period),
+            accent_palette: file
+                .accent_palette
+                .unwrap_or_else(|| self.accent_palette.clone()),
+        }
+    }
+
+    // the palette as the shader takes it, and how much of it is used. colors
+    // past PALETTE_SIZE are dropped
+    pub fn shader_palette(&self) -> ([[f32; 4]; PALETTE_SIZE], u32) {
+        let mut palette = [[0.0; 4]; PALETTE_SIZE];
+        for (slot, [r, g, b]) in palette.iter_mut().zip(&self.accent_palette) {
+            *slot = [*r, *g, *b, 1.0];
+        }
+        (palette, self.accent_palette.len().min(PALETTE_SIZE) as u32)
+    }
+}
+
+// a look for whatever's played from one playlist, album or artist, set in
+// the config as e.g.
+//   [[context_themes]]
+//   context = "Workout"
+//   accent = [1.0, 0.2, 0.2]
+//   icon = "▲"
+// context is a spotify uri or id, or the name as spotify shows it, in any
+// case. the first rule that matches wins
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ContextTheme {
+    pub context: String,
+    // takes the place of the theme's accent
+    pub accent: Option<[f32; 3]>,
+    // drawn with the indicators, in the accent color
+    pub icon: Option<String>,
+}
+
+impl ContextTheme {
+    fn matches(&self, uri: Option<&str>, name: Option<&str>) -> bool {
+        let wanted = self.context.trim();
+        if wanted.is_empty() {
+            return false;
+        }
+        let id = |uri: &str| uri.rsplit(':').next() == Some(wanted);
+        uri.is_some_and(|uri| uri == wanted || id(uri))
+            || name.is_some_and(|name| name.eq_ignore_ascii_case(wanted))
+    }
+}
+
+pub fn for_context<'a>(
+    rules: &'a [ContextTheme],
+    uri: Option<&str>,
+    name: Option<&str>,
+) -> Option<&'a ContextTheme> {
+    rules.iter().find(|rule| rule.matches(uri, name))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThemeSelection {
+    Next,
+    Named(String),
+}
+
+impl ThemeSelection {
+    pub fn parse(selection: &str) -> Self {
+        match selection {
+            "next" => ThemeSelection::Next,
+            name => ThemeSelection::Named(name.to_owned()),
+        }
+    }
+}
+
+// the config's own look, followed by every installed theme by name
+pub struct Themes {
+    themes: Vec<Theme>,
+    current: usize,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Themes {
+            themes: vec![Theme::from_config(&Config::default())],
+            current: 0,
+        }
+    }
+}
+
+impl Themes {
+    pub fn load(config: &Config) -> Self {
+        Themes::load_from(&config::config_dir().join("themes"), config)
+    }
+
+    fn load_from(dir: &Path, config: &Config) -> Self {
+        let base = Theme::from_config(config);
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|entry| entry.ok()).collect())
+            .unwrap_or_default();
+        files.sort_by_key(|entry| entry.file_name());
+
+        let mut themes = vec![base.clone()];
+        for entry in files {
+            let path = entry.path();
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) if path.extension().is_some_and(|ext| ext == "toml") => name,
+                _ => continue,
+            };
+            let file = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| Ok(toml::from_str::<ThemeFile>(&raw)?));
+            match file {
+                Ok(file) => themes.push(base.with_file(name, file)),
+                Err(e) => eprintln!("couldn't load theme {}: {:?}", path.display(), e),
+            }
+        }
+        Themes { themes, current: 0 }
+    }
+
+    pub fn uses(&self, effect: AccentEffect) -> bool {
+        self.themes
+            .iter()
+            .any(|theme| theme.accent_effect == effect)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme.name.clone()).collect()
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.themes[self.current]
+    }
+
+    pub fn select(&mut self, selection: &ThemeSelection) -> Result<&Theme, anyhow::Error> {
+        self.current = match selection {
+            ThemeSelection::Next => (self.current + 1) % self.themes.len(),
+            ThemeSelection::Named(name) => self
+                .themes
+                .iter()
+                .position(|theme| &theme.name == name)
+                .ok_or_else(|| anyhow::anyhow!("no theme called {:?}", name))?,
+        };
+        Ok(self.current())
+    }
+}
+
+#[test]
+fn test_themes_override_config_and_cycle() {
+    let dir = std::env::temp_dir().join(format!("kyomi-themes-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("dusk.toml"),
+        "accent = [1.0, 0.5, 0.0]\naccent_effect = \"cycle\"\naccent_palette = [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]",
+    )
+    .unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+
+    let config = Config::default();
+    let mut themes = Themes::load_from(&dir, &config);
+    assert_eq!(themes.current().name, "default");
+    assert!(themes.uses(AccentEffect::Cycle));
+    assert!(!themes.uses(AccentEffect::Pulse));
+
+    let dusk = themes.select(&ThemeSelection::Next).unwrap();
+    assert_eq!(dusk.name, "dusk");
+    assert_eq!(dusk.accent, [1.0, 0.5, 0.0]);
+    assert_eq!(dusk.opacity, config.opacity);
+    assert_eq!(dusk.accent_effect, AccentEffect::Cycle);
+    let (palette, len) = dusk.shader_palette();
+    assert_eq!(len, 2);
+    assert_eq!(palette[1], [0.0, 0.0, 1.0, 1.0]);
+
+    assert_eq!(
+        themes.select(&ThemeSelection::Next).unwrap().name,
+        "default"
+    );
+    assert!(themes.select(&ThemeSelection::parse("nope")).is_err());
+    themes.select(&ThemeSelection::parse("dusk")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_context_themes_match_uri_id_or_name() {
+    let rule = |context: &str, accent| ContextTheme {
+        context: context.to_owned(),
+        accent: Some(accent),
+        icon: None,
+    };
+    let rules = [
+        rule("spotify:album:4aawyAB9vmqN3uQ7FjRGTy", [0.0, 0.0, 1.0]),
+        rule("37i9dQZF1DX76Wlfdnj7AP", [1.0, 0.0, 0.0]),
+        rule("focus mix", [0.2, 0.4, 1.0]),
+        rule("", [1.0, 1.0, 1.0]),
+    ];
+    let accent = |uri, name| for_context(&rules, uri, name).and_then(|rule| rule.accent);
+
+    assert_eq!(
+        accent(Some("spotify:album:4aawyAB9vmqN3uQ7FjRGTy"), None),
+        Some([0.0, 0.0, 1.0])
+    );
+    assert_eq!(
+        accent(
+            Some("spotify:playlist:37i9dQZF1DX76Wlfdnj7AP"),
+            Some("Beast Mode")
+        ),
+        Some([1.0, 0.0, 0.0])
+    );
+    assert_eq!(
+        accent(Some("spotify:playlist:1a2b3c"), Some("Focus Mix")),
+        Some([0.2, 0.4, 1.0])
+    );
+    // a name has to match all of it, and an empty rule matches nothing
+    assert_eq!(accent(Some("spotify:playlist:1a2b3c"), Some("Focus")), None);
+    assert_eq!(accent(None, None), None);
+}
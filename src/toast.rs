@@ -0,0 +1,29 @@
+use std::time::{Duration, Instant};
+
+const TOAST_DURATION: Duration = Duration::from_secs(8);
+
+// a short message over the widget that goes away by itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub lines: Vec<String>,
+    pub shown_at: Instant,
+    pub lasts: Duration,
+}
+
+impl Toast {
+    pub fn new(lines: Vec<String>) -> Self {
+        Toast {
+            lines,
+            shown_at: Instant::now(),
+            lasts: TOAST_DURATION,
+        }
+    }
+
+    pub fn lasting(self, lasts: Duration) -> Self {
+        Toast { lasts, ..self }
+    }
+
+    pub fn visible(&self, now: Instant) -> bool {
+        now.duration_since(self.shown_at) < self.lasts
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+
+use crate::player::PlayerCommand;
+use crate::toast::Toast;
+use crate::{Source, SpotifyData};
+
+// this many drops inside the window and it's the device, not the user
+const DROPS_TO_WARN: usize = 3;
+const DROP_WINDOW: Duration = Duration::from_secs(10 * 60);
+// stopping this close to the end is just the track finishing
+const END_MARGIN_MS: u64 = 5_000;
+// a pause this soon after a command from the widget was asked for
+const COMMAND_GRACE: Duration = Duration::from_secs(5);
+
+// watches for spotify connect playback stopping mid-track without being
+// asked to. one of those is probably the user pausing on another device,
+// several in a row usually means a flaky connection
+pub struct PlaybackWatchdog {
+    last: Option<SpotifyData>,
+    drops: VecDeque<Instant>,
+}
+
+impl PlaybackWatchdog {
+    pub fn new() -> Self {
+        PlaybackWatchdog {
+            last: None,
+            drops: VecDeque::new(),
+        }
+    }
+
+    // returns true when this poll is a drop and enough have happened lately to
+    // warn about
+    pub fn observe(
+        &mut self,
+        data: &SpotifyData,
+        last_command: Option<Instant>,
+        now: Instant,
+    ) -> bool {
+        let last = self.last.replace(data.clone());
+        let commanded = last_command.is_some_and(|at| now.duration_since(at) < COMMAND_GRACE);
+        if commanded || !last.is_some_and(|last| dropped(&last, data)) {
+            return false;
+        }
+
+        while self
+            .drops
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > DROP_WINDOW)
+        {
+            self.drops.pop_front();
+        }
+        self.drops.push_back(now);
+        self.drops.len() >= DROPS_TO_WARN
+    }
+}
+
+// the same track went from playing to paused, with a good chunk left
+fn dropped(last: &SpotifyData, data: &SpotifyData) -> bool {
+    if data.source != Source::Spotify || !last.is_playing || data.is_playing {
+        return false;
+    }
+    let remaining_ms = match (data.progress_ms, data.duration_ms) {
+        (Some(progress_ms), Some(duration_ms)) => duration_ms.saturating_sub(progress_ms),
+        _ => return false,
+    };
+    last.same_track(data) && remaining_ms > END_MARGIN_MS
+}
+
+// checks every poll and raises a toast when playback keeps dropping,
+// resuming it too if asked to
+pub fn spawn_watchdog(
+    mut polls: watch::Receiver<Option<SpotifyData>>,
+    last_command: watch::Receiver<Option<Instant>>,
+    controls: UnboundedSender<PlayerCommand>,
+    auto_resume: bool,
+) -> watch::Receiver<Option<Toast>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let mut watchdog = PlaybackWatchdog::new();
+        while polls.changed().await.is_ok() {
+            let data = match polls.borrow_and_update().clone() {
+                Some(data) => data,
+                None => continue,
+            };
+            let last_command = *last_command.borrow();
+            if !watchdog.observe(&data, last_command, Instant::now()) {
+                continue;
+            }
+
+            eprintln!("playback dropped mid-track again: {}", data.track_name);
+            let action = if auto_resume {
+                let _ = controls.send(PlayerCommand::Play);
+                "resuming"
+            } else {
+                "check the device"
+            };
+            let toast = Toast::new(vec![
+                String::from("playback keeps dropping"),
+                String::from(action),
+            ]);
+            if tx.send(Some(toast)).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[test]
+fn test_repeated_drops_warn() {
+    let playing = SpotifyData {
+        track_name: String::from("a"),
+        is_playing: true,
+        progress_ms: Some(60_000),
+        duration_ms: Some(200_000),
+        source: Source::Spotify,
+        ..Default::default()
+    };
+    let paused = SpotifyData {
+        is_playing: false,
+        ..playing.clone()
+    };
+    let mut watchdog = PlaybackWatchdog::new();
+    let start = Instant::now();
+
+    // asked for through the widget, so not a drop
+    watchdog.observe(&playing, None, start);
+    assert!(!watchdog.observe(&paused, Some(start), start));
+
+    let mut warned = Vec::new();
+    for i in 1..=3 {
+        let now = start + Duration::from_secs(i * 60);
+        watchdog.observe(&playing, Some(start), now);
+        warned.push(watchdog.observe(&paused, Some(start), now));
+    }
+    assert_eq!(warned, vec![false, false, true]);
+
+    // finishing the track isn't a drop
+    let finished = SpotifyData {
+        progress_ms: Some(199_000),
+        ..paused.clone()
+    };
+    watchdog.observe(&playing, None, start + Duration::from_secs(300));
+    assert!(!watchdog.observe(&finished, None, start + Duration::from_secs(300)));
+}
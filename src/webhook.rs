@@ -0,0 +1,235 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::http::{Endpoint, SendRetrying};
+use crate::privacy::Privacy;
+use crate::template::{self, Template};
+use crate::SpotifyData;
+
+const DEFAULT_BODY: &str = r#"{"event": "{{event}}", "track": "{{track}}", "artist": "{{artist}}", "album": "{{album}}", "art_url": "{{art_url}}", "progress_ms": {{progress_ms}}, "duration_ms": {{duration_ms}}, "source": "{{source}}"}"#;
+// waited before the first retry, doubling after that
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    TrackChange,
+    Pause,
+    Resume,
+}
+
+impl WebhookEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::TrackChange => "track_change",
+            WebhookEvent::Pause => "pause",
+            WebhookEvent::Resume => "resume",
+        }
+    }
+}
+
+// set in the config under [webhook], e.g.
+//   url = "http://homeassistant.local:8123/api/webhook/kyomi"
+//   body = '{"text": "{{artist}} - {{track}}"}'
+//   secret = "..."
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Webhook {
+    pub url: String,
+    // a template with the usual track fields plus {{event}}. values are
+    // escaped for use inside json strings, apart from progress_ms and
+    // duration_ms which are numbers, or null when unknown
+    pub body: String,
+    // when set, the body's HMAC-SHA256 goes in X-Kyomi-Signature as
+    // sha256=<hex>
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEvent>,
+    // further attempts after a failed delivery
+    pub retries: u32,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Webhook {
+            url: String::new(),
+            body: String::from(DEFAULT_BODY),
+            secret: None,
+            events: vec![
+                WebhookEvent::TrackChange,
+                WebhookEvent::Pause,
+                WebhookEvent::Resume,
+            ],
+            retries: 3,
+        }
+    }
+}
+
+fn render(body: &Template, event: WebhookEvent, track: &SpotifyData) -> String {
+    let status = if track.is_playing {
+        "playing"
+    } else {
+        "paused"
+    };
+    let mut context: HashMap<&str, String> = template::track_context(track, status);
+    context.insert("event", event.name().to_owned());
+    for (key, value) in context.iter_mut() {
+        if matches!(*key, "progress_ms" | "duration_ms") {
+            if value.is_empty() {
+                *value = String::from("null");
+            }
+            continue;
+        }
+        let quoted = serde_json::to_string(value).unwrap();
+        *value = quoted[1..quoted.len() - 1].to_owned();
+    }
+    body.render(&context)
+}
+
+fn signature(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any length");
+    mac.update(body.as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+impl Webhook {
+    async fn post(&self, client: &reqwest::Client, body: &str) -> reqwest::Result<()> {
+        let mut request = client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(body.to_owned());
+        if let Some(secret) = &self.secret {
+            request = request.header("X-Kyomi-Signature", signature(secret, body));
+        }
+        request
+            .send_retrying(Endpoint::Polling)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    // retried with backoff unless the receiver turned the body down, they're
+    // expected to cope with the odd duplicate
+    async fn deliver(&self, client: &reqwest::Client, body: &str) {
+        let mut backoff = FIRST_BACKOFF;
+        for attempt in 0..=self.retries {
+            match self.post(client, body).await {
+                Ok(()) => return,
+                Err(e) if e.status().is_some_and(|status| status.is_client_error()) => {
+                    eprintln!("webhook rejected: {:?}", e);
+                    return;
+                }
+                Err(e) if attempt == self.retries => eprintln!("webhook failed: {:?}", e),
+                Err(_) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+}
+
+// posts on each settled track, and when that track pauses or resumes.
+// settled tracks are already kept out of privacy mode by the aggregator,
+// pauses are checked here. deliveries go through a worker in order, so a
+// slow receiver doesn't hold up listening
+pub fn spawn_webhook(
+    webhook: Webhook,
+    mut settled: broadcast::Receiver<SpotifyData>,
+    mut polls: watch::Receiver<Option<SpotifyData>>,
+    privacy: Arc<Privacy>,
+) {
+    let body = match Template::parse(&webhook.body) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("bad webhook body, using the default: {:?}", e);
+            Template::parse(DEFAULT_BODY).unwrap()
+        }
+    };
+    let (deliveries, mut queued) = mpsc::unbounded_channel::<String>();
+    let events = webhook.events.clone();
+    tokio::spawn(async move {
+        let client = crate::http::client(Endpoint::Polling);
+        while let Some(body) = queued.recv().await {
+            webhook.deliver(&client, &body).await;
+        }
+    });
+    tokio::spawn(async move {
+        let mut current: Option<SpotifyData> = None;
+        loop {
+            let (event, track) = tokio::select! {
+                track = settled.recv() => match track {
+                    Ok(track) => {
+                        current = Some(track.clone());
+                        (WebhookEvent::TrackChange, track)
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                changed = polls.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let poll = polls.borrow_and_update().clone();
+                    let (Some(poll), Some(last)) = (poll, current.as_mut()) else {
+                        continue;
+                    };
+                    if !poll.same_track(last) || poll.is_playing == last.is_playing {
+                        continue;
+                    }
+                    last.is_playing = poll.is_playing;
+                    if privacy.is_active() {
+                        continue;
+                    }
+                    let event = if poll.is_playing {
+                        WebhookEvent::Resume
+                    } else {
+                        WebhookEvent::Pause
+                    };
+                    (event, poll)
+                }
+            };
+            if events.contains(&event) && deliveries.send(render(&body, event, &track)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[test]
+fn test_webhook_body_and_signature() {
+    // RFC 4231, test case 2
+    assert_eq!(
+        signature("Jefe", "what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    let track = SpotifyData {
+        track_name: String::from("Say \"Hi\""),
+        artist_name: String::from("Y"),
+        is_playing: true,
+        duration_ms: Some(387000),
+        ..Default::default()
+    };
+    let body = render(
+        &Template::parse(DEFAULT_BODY).unwrap(),
+        WebhookEvent::Pause,
+        &track,
+    );
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["event"], "pause");
+    assert_eq!(json["track"], "Say \"Hi\"");
+    assert_eq!(json["duration_ms"], 387000);
+    assert!(json["progress_ms"].is_null());
+}
@@ -0,0 +1,89 @@
+use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use winit::window::Window;
+
+// the title window manager rules can match the widget by
+pub const WINDOW_TITLE: &str = "kyomi";
+
+// keeps the widget on every workspace / virtual desktop, however the platform
+// allows it
+pub fn show_on_all_workspaces(window: &Window) {
+    match window.window_handle().map(|handle| handle.as_raw()) {
+        Ok(RawWindowHandle::Xlib(handle)) => wmctrl(format!("0x{:x}", handle.window)),
+        Ok(RawWindowHandle::Xcb(handle)) => wmctrl(format!("0x{:x}", handle.window.get())),
+        Ok(RawWindowHandle::Wayland(_)) => wayland(),
+        #[cfg(target_os = "macos")]
+        Ok(RawWindowHandle::AppKit(handle)) => join_all_spaces(handle),
+        // windows has no public api for pinning a window to every virtual
+        // desktop
+        _ => {}
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<(), anyhow::Error> {
+    let status = tokio::process::Command::new(program)
+        .args(args)
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+// sticky, off the taskbar and off the pager. winit can't ask for these, so
+// wmctrl sends the messages a window manager expects once the window is mapped
+fn wmctrl(id: String) {
+    tokio::spawn(async move {
+        // give the window manager a moment to map the window first
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        // wmctrl takes at most two states at a time
+        for states in ["add,sticky,skip_taskbar", "add,skip_pager"] {
+            if let Err(e) = run("wmctrl", &["-i", "-r", &id, "-b", states]).await {
+                eprintln!(
+                    "couldn't make the widget sticky, is wmctrl installed? {:?}",
+                    e
+                );
+                return;
+            }
+        }
+    });
+}
+
+// there's no wayland protocol for this, but sway and hyprland take it over
+// their own ipc. hyprland only pins floating windows
+fn wayland() {
+    let title = format!("^{}$", WINDOW_TITLE);
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let res = if std::env::var_os("SWAYSOCK").is_some() {
+            run(
+                "swaymsg",
+                &[&format!("[title=\"{}\"]", title), "sticky", "enable"],
+            )
+            .await
+        } else if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            run("hyprctl", &["dispatch", "pin", &format!("title:{}", title)]).await
+        } else {
+            return;
+        };
+        if let Err(e) = res {
+            eprintln!("couldn't make the widget sticky: {:?}", e);
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn join_all_spaces(handle: winit::raw_window_handle::AppKitWindowHandle) {
+    use objc2_app_kit::{NSView, NSWindowCollectionBehavior};
+
+    // winit hands out the view, which stays alive as long as the window
+    let view: &NSView = unsafe { handle.ns_view.cast().as_ref() };
+    if let Some(ns_window) = view.window() {
+        unsafe {
+            let behavior = ns_window.collectionBehavior()
+                | NSWindowCollectionBehavior::CanJoinAllSpaces
+                | NSWindowCollectionBehavior::Stationary;
+            ns_window.setCollectionBehavior(behavior);
+        }
+    }
+}
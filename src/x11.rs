@@ -0,0 +1,25 @@
+use winit::window::WindowAttributes;
+
+use crate::config::{Config, X11WindowType};
+
+// window type hints so tiling window managers float the widget rather than
+// tile it. they only matter when the window ends up on x11, wayland
+// compositors never see them
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn with_hints(attributes: WindowAttributes, config: &Config) -> WindowAttributes {
+    use winit::platform::x11::{WindowAttributesExtX11, WindowType};
+
+    let window_type = match config.x11_window_type {
+        X11WindowType::Normal => WindowType::Normal,
+        X11WindowType::Utility => WindowType::Utility,
+        X11WindowType::Dock => WindowType::Dock,
+    };
+    attributes
+        .with_x11_window_type(vec![window_type])
+        .with_override_redirect(config.x11_override_redirect)
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn with_hints(attributes: WindowAttributes, _config: &Config) -> WindowAttributes {
+    attributes
+}